@@ -1,31 +1,184 @@
 //file does not exist
 //word does not exist
+use crate::import::ImportSummary;
 use actix_web::{error, http::StatusCode, HttpResponse};
 use derive_more::{Display, Error};
+use serde_json::error::Category;
+use serde_json::{json, Value};
 
 #[derive(Debug, Display, Error, PartialEq, Eq)]
 pub enum AppError {
     #[display(fmt = "Source file has invalid content.")]
     InvalidFileContent,
+    #[display(fmt = "Source file has invalid content at line {}: {}", line, reason)]
+    InvalidSourceLine { line: usize, reason: String },
     #[display(fmt = "Word does not exist.")]
-    WordDoesNotExist,
+    WordDoesNotExist { name: String },
+    //same as WordDoesNotExist, for the routes that look the name up as a resource.
+    #[display(fmt = "Name not found.")]
+    NameNotFound { name: String },
     #[display(fmt = "Unexpected internal error.")]
     UnexpectedError,
     #[display(fmt = "Bad request.")]
     BadRequest,
+    #[display(fmt = "Cursor is invalid, it must be the next_cursor of a previous page.")]
+    InvalidCursor,
+    #[display(fmt = "Query string is invalid: {}", reason)]
+    InvalidQuery { reason: String },
+    #[display(fmt = "Path is invalid: {}", reason)]
+    InvalidPath { reason: String },
+    #[display(fmt = "Content type must be application/json.")]
+    UnsupportedMediaType,
+    #[display(fmt = "Payload is not valid UTF-8.")]
+    InvalidPayloadEncoding { valid_up_to: usize },
+    #[display(fmt = "Payload is not valid JSON: {}", reason)]
+    MalformedPayload { reason: String, line: usize, column: usize },
+    #[display(fmt = "Payload does not have the expected fields: {}", reason)]
+    InvalidPayload { reason: String, line: usize, column: usize },
+    #[display(fmt = "Payload could not be read: {}", reason)]
+    UnreadablePayload { reason: String },
+    #[display(fmt = "Payload is larger than {} bytes.", limit)]
+    PayloadTooLarge { limit: usize },
+    #[display(fmt = "Payload line {} is longer than {} bytes.", line, limit)]
+    PayloadLineTooLong { line: usize, limit: usize },
+    #[display(fmt = "Word popularity can not grow any further.")]
+    PopularityOverflow,
+    #[display(fmt = "Word already exists.")]
+    WordAlreadyExists,
+    #[display(fmt = "Missing or invalid admin token.")]
+    Unauthorized,
+    #[display(fmt = "Journal file has invalid content.")]
+    InvalidJournalContent,
+    #[display(fmt = "Index file is missing, corrupted or not an index.")]
+    InvalidIndexFile,
+    #[display(
+        fmt = "Index file format version {} is not supported, expected version {}. Build the index again.",
+        found,
+        expected
+    )]
+    IndexVersionMismatch { found: u32, expected: u32 },
+    #[display(fmt = "Index file was built with other SUGGESTION_NUMBER or ACCENT_INSENSITIVE. Build the index again.")]
+    IndexOptionsMismatch,
+    //the import failed part way, with the summary of the entries merged until then.
+    #[display(fmt = "Import stopped before the end of the body: {}", cause)]
+    ImportInterrupted { cause: Box<AppError>, summary: ImportSummary },
+}
+
+impl AppError {
+    //tells a payload that is not JSON at all from one without the expected fields, keeping where serde stopped.
+    pub fn from_payload_error(e: &serde_json::Error) -> AppError {
+        let message = e.to_string();
+        let position = format!(" at line {} column {}", e.line(), e.column());
+        let reason = message.strip_suffix(&position).unwrap_or(&message).to_string();
+
+        match e.classify() {
+            Category::Data => AppError::InvalidPayload {
+                reason,
+                line: e.line(),
+                column: e.column(),
+            },
+            Category::Syntax | Category::Eof | Category::Io => AppError::MalformedPayload {
+                reason,
+                line: e.line(),
+                column: e.column(),
+            },
+        }
+    }
+
+    //stable identifier of the error, for clients to match on instead of the message. Also labels the error metrics.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::InvalidFileContent => "invalid_file_content",
+            AppError::InvalidSourceLine { .. } => "invalid_source_line",
+            AppError::WordDoesNotExist { .. } => "word_does_not_exist",
+            AppError::NameNotFound { .. } => "name_not_found",
+            AppError::UnexpectedError => "unexpected_error",
+            AppError::BadRequest => "bad_request",
+            AppError::InvalidCursor => "invalid_cursor",
+            AppError::InvalidQuery { .. } => "invalid_query",
+            AppError::InvalidPath { .. } => "invalid_path",
+            AppError::UnsupportedMediaType => "unsupported_media_type",
+            AppError::InvalidPayloadEncoding { .. } => "invalid_payload_encoding",
+            AppError::MalformedPayload { .. } => "malformed_payload",
+            AppError::InvalidPayload { .. } => "invalid_payload",
+            AppError::UnreadablePayload { .. } => "unreadable_payload",
+            AppError::PayloadTooLarge { .. } => "payload_too_large",
+            AppError::PayloadLineTooLong { .. } => "payload_line_too_long",
+            AppError::PopularityOverflow => "popularity_overflow",
+            AppError::WordAlreadyExists => "word_already_exists",
+            AppError::Unauthorized => "unauthorized",
+            AppError::InvalidJournalContent => "invalid_journal_content",
+            AppError::InvalidIndexFile => "invalid_index_file",
+            AppError::IndexVersionMismatch { .. } => "index_version_mismatch",
+            AppError::IndexOptionsMismatch => "index_options_mismatch",
+            AppError::ImportInterrupted { .. } => "import_interrupted",
+        }
+    }
+
+    fn details(&self) -> Option<Value> {
+        match self {
+            AppError::WordDoesNotExist { name } | AppError::NameNotFound { name } => Some(json!({ "name": name })),
+            AppError::InvalidSourceLine { line, .. } => Some(json!({ "line": line })),
+            AppError::InvalidQuery { reason } | AppError::InvalidPath { reason } => Some(json!({ "reason": reason })),
+            AppError::UnreadablePayload { reason } => Some(json!({ "reason": reason })),
+            AppError::PayloadTooLarge { limit } => Some(json!({ "limit": limit })),
+            AppError::PayloadLineTooLong { line, limit } => Some(json!({ "line": line, "limit": limit })),
+            AppError::InvalidPayloadEncoding { valid_up_to } => Some(json!({ "valid_up_to": valid_up_to })),
+            AppError::MalformedPayload { line, column, .. } | AppError::InvalidPayload { line, column, .. } => {
+                Some(json!({ "line": line, "column": column }))
+            }
+            AppError::IndexVersionMismatch { found, expected } => {
+                Some(json!({ "found": found, "expected": expected }))
+            }
+            AppError::ImportInterrupted { cause, summary } => {
+                let mut cause_error = json!({ "code": cause.code(), "message": cause.to_string() });
+                if let Some(cause_details) = cause.details() {
+                    cause_error["details"] = cause_details;
+                }
+
+                Some(json!({ "cause": cause_error, "summary": summary }))
+            }
+            _ => None,
+        }
+    }
 }
 
 impl error::ResponseError for AppError {
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code()).body(self.to_string())
+        let mut error = json!({ "code": self.code(), "message": self.to_string() });
+        if let Some(details) = self.details() {
+            error["details"] = details;
+        }
+
+        HttpResponse::build(self.status_code()).json(json!({ "error": error }))
     }
 
     fn status_code(&self) -> StatusCode {
         match *self {
-            AppError::InvalidFileContent | AppError::UnexpectedError => {
+            AppError::ImportInterrupted { ref cause, .. } => cause.status_code(),
+            AppError::InvalidFileContent
+            | AppError::InvalidSourceLine { .. }
+            | AppError::UnexpectedError
+            | AppError::InvalidJournalContent
+            | AppError::InvalidIndexFile
+            | AppError::IndexVersionMismatch { .. }
+            | AppError::IndexOptionsMismatch => {
                 StatusCode::INTERNAL_SERVER_ERROR
             }
-            AppError::WordDoesNotExist | AppError::BadRequest => StatusCode::BAD_REQUEST,
+            AppError::WordDoesNotExist { .. }
+            | AppError::BadRequest
+            | AppError::InvalidCursor
+            | AppError::InvalidQuery { .. }
+            | AppError::InvalidPath { .. }
+            | AppError::InvalidPayloadEncoding { .. }
+            | AppError::MalformedPayload { .. }
+            | AppError::InvalidPayload { .. }
+            | AppError::UnreadablePayload { .. } => StatusCode::BAD_REQUEST,
+            AppError::PayloadTooLarge { .. } | AppError::PayloadLineTooLong { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::PopularityOverflow | AppError::WordAlreadyExists => StatusCode::CONFLICT,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::NameNotFound { .. } => StatusCode::NOT_FOUND,
+            AppError::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
         }
     }
 }
@@ -0,0 +1,161 @@
+use crate::config::{SourceConfig, TrieConfig};
+use crate::index;
+use crate::source::{self, SourceOptions};
+use crate::trie::TrieOptions;
+use crate::validation;
+
+const USAGE: &str = "Usage:
+    type-ahead-api                                         starts the server
+    type-ahead-api build-index <names file> <index file>   builds the binary index of the names file
+    type-ahead-api validate <names file>                   lists the problems found in the names file
+
+SUGGESTION_NUMBER, ACCENT_INSENSITIVE and the FILE_* settings of the names file are read from the environment, the same
+way the server does.";
+
+//runs the command given in the arguments instead of the server. Returns the exit code.
+pub fn run(args: &[String]) -> i32 {
+    let result = match args.first().map(String::as_str) {
+        Some("build-index") => TrieConfig::from_env()
+            .and_then(|trie_config| Ok((trie_config, SourceConfig::from_env()?)))
+            .map_err(|e| format!("Could not load configuration from environment: {}", e))
+            .and_then(|(trie_config, source_config)| {
+                build_index(&args[1..], trie_config.trie_options(), &source_config.source_options())
+            }),
+        Some("validate") => SourceConfig::from_env()
+            .map_err(|e| format!("Could not load configuration from environment: {}", e))
+            .and_then(|source_config| validate(&args[1..], &source_config.source_options())),
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(message) => {
+            println!("{}", message);
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+fn build_index(args: &[String], options: TrieOptions, source_options: &SourceOptions) -> Result<String, String> {
+    let (names_file, index_file) = match args {
+        [names_file, index_file] => (names_file, index_file),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let trie = source::load(names_file, source_options, options).map_err(|e| format!("{}: {}", names_file, e))?;
+
+    index::write(&trie, index_file).map_err(|e| format!("{}: {}", index_file, e))?;
+
+    Ok(format!(
+        "Wrote index {} with {} names (format version {})",
+        index_file,
+        trie.all_words().len(),
+        index::INDEX_FORMAT_VERSION
+    ))
+}
+
+//lists every problem of the names file, failing when there is any.
+fn validate(args: &[String], source_options: &SourceOptions) -> Result<String, String> {
+    let names_file = match args {
+        [names_file] => names_file,
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let (content, format) =
+        source::read_file(names_file, source_options).map_err(|e| format!("{}: {}", names_file, e))?;
+    let (_, report) = validation::validate(&content, format, source_options);
+
+    let mut lines: Vec<String> = report
+        .issues
+        .iter()
+        .map(|issue| format!("{}:{}: {}", names_file, issue.line, issue.problem))
+        .collect();
+    lines.push(format!(
+        "{}: {} names, {} problems",
+        names_file,
+        report.names,
+        report.issues.len()
+    ));
+
+    if report.issues.is_empty() {
+        Ok(lines.join("\n"))
+    } else {
+        Err(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::tests::temp_file_path;
+    use std::fs;
+
+    #[test]
+    fn t_build_index() {
+        let names_file = temp_file_path("cli-names.json");
+        let index_file = temp_file_path("cli-names.idx");
+        fs::write(&names_file, "{\"Aar\": 361, \"Aari\": 151}").unwrap();
+
+        let message =
+            build_index(&[names_file.clone(), index_file.clone()], 10.into(), &SourceOptions::default()).unwrap();
+
+        assert_eq!(
+            format!("Wrote index {} with 2 names (format version 1)", index_file),
+            message
+        );
+        assert_eq!(2, index::load(&index_file, 10.into()).unwrap().all_words().len());
+    }
+
+    #[test]
+    fn t_build_index_invalid_names_file() {
+        let names_file = temp_file_path("cli-invalid-names.json");
+        let index_file = temp_file_path("cli-invalid-names.idx");
+        fs::write(&names_file, "[\"Aar\"]").unwrap();
+
+        let message =
+            build_index(&[names_file.clone(), index_file.clone()], 10.into(), &SourceOptions::default()).unwrap_err();
+
+        assert_eq!(
+            format!(
+                "{}: Source file has invalid content at line 1: invalid type: sequence, expected an object with names and popularities",
+                names_file
+            ),
+            message
+        );
+        assert!(fs::metadata(&index_file).is_err());
+    }
+
+    #[test]
+    fn t_build_index_wrong_arguments() {
+        assert_eq!(Err(USAGE.to_string()), build_index(&["names.json".to_string()], 10.into(), &SourceOptions::default()));
+    }
+
+    #[test]
+    fn t_validate() {
+        let names_file = temp_file_path("cli-validate.csv");
+        let args = [names_file.to_string()];
+        fs::write(&names_file, "name,times\nAar,361\nAbel,-1\naar,2\n").unwrap();
+
+        let message = validate(&args, &SourceOptions::default()).unwrap_err();
+
+        assert_eq!(
+            format!(
+                "{0}:3: Popularity -1 of \"Abel\" is not a whole number from 0 to 18446744073709551615\n\
+                 {0}:4: Name \"aar\" differs only in casing from \"Aar\" at line 2\n\
+                 {0}: 2 names, 2 problems",
+                names_file
+            ),
+            message
+        );
+
+        fs::write(&names_file, "name,times\nAar,361\n").unwrap();
+
+        assert_eq!(
+            Ok(format!("{}: 1 names, 0 problems", names_file)),
+            validate(&args, &SourceOptions::default())
+        );
+    }
+}
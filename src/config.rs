@@ -1,5 +1,14 @@
+use crate::decay::DEFAULT_DECAY_HALF_LIFE;
+use crate::export::DEFAULT_EXPORT_PAGE_SIZE;
+use crate::journal::FsyncPolicy;
+use crate::pagination::DEFAULT_MAX_RESULT_LIMIT;
+use crate::ranking::{Ranking, RankingStrategy};
+use crate::source::{CollisionPolicy, SourceFormat, SourceOptions};
+use crate::trie::{Popularity, TrieOptions, DEFAULT_MAX_FUZZY_EDITS};
 use config::ConfigError;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use std::time::Duration;
 
 #[derive(Deserialize)]
 
@@ -7,14 +16,218 @@ use serde::Deserialize;
 pub struct Config {
     pub host: String,
     pub file_name: String,
+    pub file_format: Option<SourceFormat>,
+    #[serde(default = "default_file_name_column")]
+    pub file_name_column: String,
+    #[serde(default = "default_file_popularity_column")]
+    pub file_popularity_column: String,
+    #[serde(default)]
+    pub file_default_popularity: Popularity,
+    #[serde(default)]
+    pub strict_validation: bool,
+    #[serde(default = "default_collision_policy")]
+    pub collision_policy: CollisionPolicy,
     pub port: String,
     pub suggestion_number: u8,
+    #[serde(default)]
+    pub accent_insensitive: bool,
+    #[serde(default = "default_max_fuzzy_edits")]
+    pub max_fuzzy_edits: u8,
+    pub journal_file: Option<String>,
+    #[serde(default = "default_journal_fsync")]
+    pub journal_fsync: FsyncPolicy,
+    #[serde(default = "default_journal_fsync_batch_size")]
+    pub journal_fsync_batch_size: u32,
+    pub snapshot_dir: Option<String>,
+    #[serde(default = "default_snapshot_interval_seconds")]
+    pub snapshot_interval_seconds: u64,
+    #[serde(default = "default_snapshot_retention")]
+    pub snapshot_retention: usize,
+    pub index_file: Option<String>,
+    #[serde(default)]
+    pub watch_interval_seconds: u64,
+    pub admin_token: Option<String>,
+    #[serde(default = "default_stats_cache_seconds")]
+    pub stats_cache_seconds: u64,
+    #[serde(default = "default_max_result_limit")]
+    pub max_result_limit: usize,
+    #[serde(default = "default_export_page_size")]
+    pub export_page_size: usize,
+    #[serde(default = "default_ranking")]
+    pub ranking: RankingStrategy,
+    #[serde(default = "default_exact_match_first")]
+    pub exact_match_first: bool,
+    #[serde(default = "default_decay_weight")]
+    pub decay_weight: u8,
+    #[serde(default = "default_decay_half_life_seconds")]
+    pub decay_half_life_seconds: u64,
+}
+
+// Subset of the configuration that shapes the trie, read by the commands that run without the server
+#[derive(Deserialize)]
+pub struct TrieConfig {
+    pub suggestion_number: u8,
+    #[serde(default)]
+    pub accent_insensitive: bool,
+    #[serde(default = "default_max_fuzzy_edits")]
+    pub max_fuzzy_edits: u8,
+}
+
+// Subset of the configuration that reads the source file, for the same commands
+#[derive(Deserialize)]
+pub struct SourceConfig {
+    pub file_format: Option<SourceFormat>,
+    #[serde(default = "default_file_name_column")]
+    pub file_name_column: String,
+    #[serde(default = "default_file_popularity_column")]
+    pub file_popularity_column: String,
+    #[serde(default)]
+    pub file_default_popularity: Popularity,
+    #[serde(default)]
+    pub strict_validation: bool,
+    #[serde(default = "default_collision_policy")]
+    pub collision_policy: CollisionPolicy,
+}
+
+fn default_max_fuzzy_edits() -> u8 {
+    DEFAULT_MAX_FUZZY_EDITS
+}
+
+fn default_file_name_column() -> String {
+    SourceOptions::default().name_column
+}
+
+fn default_file_popularity_column() -> String {
+    SourceOptions::default().popularity_column
+}
+
+fn default_collision_policy() -> CollisionPolicy {
+    SourceOptions::default().collision_policy
+}
+
+fn default_journal_fsync() -> FsyncPolicy {
+    FsyncPolicy::Always
+}
+
+fn default_journal_fsync_batch_size() -> u32 {
+    100
+}
+
+fn default_snapshot_interval_seconds() -> u64 {
+    300
+}
+
+fn default_snapshot_retention() -> usize {
+    2
+}
+
+fn default_stats_cache_seconds() -> u64 {
+    10
+}
+
+fn default_max_result_limit() -> usize {
+    DEFAULT_MAX_RESULT_LIMIT
+}
+
+fn default_export_page_size() -> usize {
+    DEFAULT_EXPORT_PAGE_SIZE
+}
+
+fn default_ranking() -> RankingStrategy {
+    Ranking::default().strategy
+}
+
+fn default_exact_match_first() -> bool {
+    Ranking::default().exact_match_first
+}
+
+fn default_decay_weight() -> u8 {
+    Ranking::default().decay_weight
+}
+
+fn default_decay_half_life_seconds() -> u64 {
+    DEFAULT_DECAY_HALF_LIFE.as_secs()
+}
+
+fn from_env<T: DeserializeOwned>() -> Result<T, ConfigError> {
+    let mut cfg = ::config::Config::new();
+    cfg.merge(::config::Environment::new())?;
+    cfg.try_into()
 }
 
 impl Config {
     pub fn from_env() -> Result<Self, ConfigError> {
-        let mut cfg = ::config::Config::new();
-        cfg.merge(::config::Environment::new())?;
-        cfg.try_into()
+        let config: Config = from_env()?;
+
+        //only the default ranking is cached, any other would walk every matching name on each keystroke of every user
+        if !config.ranking().is_cached() {
+            return Err(ConfigError::Message(
+                "RANKING can only be popularity with EXACT_MATCH_FIRST, other rankings can only be asked per request"
+                    .to_string(),
+            ));
+        }
+
+        Ok(config)
+    }
+
+    pub fn trie_options(&self) -> TrieOptions {
+        TrieOptions {
+            suggestion_number: self.suggestion_number,
+            accent_insensitive: self.accent_insensitive,
+            max_fuzzy_edits: self.max_fuzzy_edits,
+            decay_half_life: Duration::from_secs(self.decay_half_life_seconds),
+        }
+    }
+
+    pub fn ranking(&self) -> Ranking {
+        Ranking {
+            strategy: self.ranking,
+            exact_match_first: self.exact_match_first,
+            //a share over 100% would make a higher popularity rank lower
+            decay_weight: self.decay_weight.min(100),
+        }
+    }
+
+    pub fn source_options(&self) -> SourceOptions {
+        SourceOptions {
+            format: self.file_format,
+            name_column: self.file_name_column.clone(),
+            popularity_column: self.file_popularity_column.clone(),
+            default_popularity: self.file_default_popularity,
+            strict: self.strict_validation,
+            collision_policy: self.collision_policy,
+        }
+    }
+}
+
+impl TrieConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        from_env()
+    }
+
+    pub fn trie_options(&self) -> TrieOptions {
+        TrieOptions {
+            suggestion_number: self.suggestion_number,
+            accent_insensitive: self.accent_insensitive,
+            max_fuzzy_edits: self.max_fuzzy_edits,
+            ..TrieOptions::default()
+        }
+    }
+}
+
+impl SourceConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        from_env()
+    }
+
+    pub fn source_options(&self) -> SourceOptions {
+        SourceOptions {
+            format: self.file_format,
+            name_column: self.file_name_column.clone(),
+            popularity_column: self.file_popularity_column.clone(),
+            default_popularity: self.file_default_popularity,
+            strict: self.strict_validation,
+            collision_policy: self.collision_policy,
+        }
     }
 }
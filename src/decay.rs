@@ -0,0 +1,174 @@
+use crate::trie::Popularity;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+//a week, so last week's hits count half as much as today's.
+pub const DEFAULT_DECAY_HALF_LIFE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+// Popularity of each name that fades over time, kept aside from the lifetime counter of the trie. Every hit adds 1 to the
+// score of the name, and the score halves every half-life, so it mostly reflects the hits of the last few half-lives.
+// Names without hits since they were loaded have no entry and a score of 0.
+#[derive(Debug, Clone)]
+pub struct DecayedScores {
+    half_life: Duration,
+    scores: HashMap<String, DecayedScore>,
+}
+
+//the score as it was when the name last got a hit, it is decayed from there when read. Snapshots keep it as is, with the
+//time in unix millis like the journal.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DecayedScore {
+    score: f64,
+    #[serde(serialize_with = "serialize_unix_millis", deserialize_with = "deserialize_unix_millis")]
+    updated_at: SystemTime,
+}
+
+impl DecayedScores {
+    pub fn new(half_life: Duration) -> DecayedScores {
+        DecayedScores {
+            //a zero half-life would turn every score into NaN
+            half_life: half_life.max(Duration::from_secs(1)),
+            scores: HashMap::new(),
+        }
+    }
+
+    //hits given before the latest one of the name, e.g. replayed out of order, count as if they were given with it.
+    pub fn add(&mut self, word: &str, amount: Popularity, at: SystemTime) {
+        let half_life = self.half_life;
+
+        match self.scores.get_mut(word) {
+            Some(decayed_score) => {
+                let at = at.max(decayed_score.updated_at);
+
+                decayed_score.score = decay(decayed_score.score, decayed_score.updated_at, at, half_life) + amount as f64;
+                decayed_score.updated_at = at;
+            }
+            None => {
+                self.scores.insert(
+                    word.to_string(),
+                    DecayedScore {
+                        score: amount as f64,
+                        updated_at: at,
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn score(&self, word: &str, now: SystemTime) -> f64 {
+        self.score_of(self.scores.get(word), now)
+    }
+
+    //the score of an entry taken out of the scores earlier, decayed at the same pace as them.
+    pub fn score_of(&self, decayed_score: Option<&DecayedScore>, now: SystemTime) -> f64 {
+        decayed_score.map_or(0.0, |decayed_score| decay(decayed_score.score, decayed_score.updated_at, now, self.half_life))
+    }
+
+    pub fn get(&self, word: &str) -> Option<&DecayedScore> {
+        self.scores.get(word)
+    }
+
+    pub fn insert(&mut self, word: String, decayed_score: DecayedScore) {
+        self.scores.insert(word, decayed_score);
+    }
+
+    pub fn remove(&mut self, word: &str) -> Option<DecayedScore> {
+        self.scores.remove(word)
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.scores.retain(|word, _| keep(word));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &DecayedScore)> {
+        self.scores.iter()
+    }
+}
+
+impl Default for DecayedScores {
+    fn default() -> DecayedScores {
+        DecayedScores::new(DEFAULT_DECAY_HALF_LIFE)
+    }
+}
+
+fn decay(score: f64, updated_at: SystemTime, now: SystemTime, half_life: Duration) -> f64 {
+    let elapsed = now.duration_since(updated_at).unwrap_or_default();
+
+    score * 0.5_f64.powf(elapsed.as_secs_f64() / half_life.as_secs_f64())
+}
+
+//how hit times are written to the journal.
+pub fn to_unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+}
+
+pub fn from_unix_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+fn serialize_unix_millis<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(to_unix_millis(*time))
+}
+
+fn deserialize_unix_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+    u64::deserialize(deserializer).map(from_unix_millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    fn assert_close(expected: f64, actual: f64) {
+        assert!((expected - actual).abs() < 1e-9, "expected {} but got {}", expected, actual);
+    }
+
+    #[test]
+    fn t_score_halves_every_half_life() {
+        let start = SystemTime::now();
+        let mut decayed_scores = DecayedScores::new(HOUR);
+
+        decayed_scores.add("Ann", 8, start);
+
+        assert_close(8.0, decayed_scores.score("Ann", start));
+        assert_close(4.0, decayed_scores.score("Ann", start + HOUR));
+        assert_close(1.0, decayed_scores.score("Ann", start + 3 * HOUR));
+        assert_close(0.0, decayed_scores.score("Anna", start));
+
+        //the previous score is decayed to the time of the new hit before adding it
+        decayed_scores.add("Ann", 1, start + 2 * HOUR);
+        assert_close(3.0, decayed_scores.score("Ann", start + 2 * HOUR));
+        assert_close(1.5, decayed_scores.score("Ann", start + 3 * HOUR));
+
+        //an older hit counts as given with the latest one
+        decayed_scores.add("Ann", 1, start);
+        assert_close(4.0, decayed_scores.score("Ann", start + 2 * HOUR));
+    }
+
+    #[test]
+    fn t_insert_and_remove() {
+        let now = SystemTime::now();
+        let mut decayed_scores = DecayedScores::new(HOUR);
+        decayed_scores.add("ann", 2, now);
+        decayed_scores.add("Anna", 1, now);
+
+        let decayed_score = decayed_scores.remove("ann").unwrap();
+        decayed_scores.insert("Ann".to_string(), decayed_score);
+        decayed_scores.remove("Anna");
+
+        assert_close(2.0, decayed_scores.score("Ann", now));
+        assert_close(0.0, decayed_scores.score("ann", now));
+        assert_eq!(1, decayed_scores.scores.len());
+    }
+
+    #[test]
+    fn t_unix_millis() {
+        let time = from_unix_millis(1_700_000_000_123);
+
+        assert_eq!(1_700_000_000_123, to_unix_millis(time));
+        assert_eq!(Duration::from_secs(1), DecayedScores::new(Duration::ZERO).half_life);
+    }
+}
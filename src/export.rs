@@ -0,0 +1,310 @@
+use crate::app_error::AppError;
+use crate::metrics::{self, Metrics};
+use crate::source::SourceFormat;
+use crate::trie::{Popularity, Trie, WordData};
+use std::iter::Peekable;
+use std::sync::{Arc, RwLock};
+use std::vec;
+
+//names written to each chunk of the body.
+const EXPORT_CHUNK_SIZE: usize = 500;
+//largest page of an export when EXPORT_PAGE_SIZE is not set, also the names taken under each read lock when streaming.
+pub const DEFAULT_EXPORT_PAGE_SIZE: usize = 1000;
+
+//exports are written in the source file formats, so they load back as one. Lines only have the names.
+pub fn content_type(format: SourceFormat) -> &'static str {
+    match format {
+        SourceFormat::Json => "application/json",
+        SourceFormat::Csv => "text/csv; charset=utf-8",
+        SourceFormat::Tsv => "text/tab-separated-values; charset=utf-8",
+        SourceFormat::Ndjson => "application/x-ndjson",
+        SourceFormat::Lines => "text/plain; charset=utf-8",
+    }
+}
+
+fn header(format: SourceFormat) -> &'static str {
+    match format {
+        SourceFormat::Json => "{",
+        SourceFormat::Csv => "name,times\n",
+        SourceFormat::Tsv => "name\ttimes\n",
+        SourceFormat::Ndjson | SourceFormat::Lines => "",
+    }
+}
+
+fn footer(format: SourceFormat) -> &'static str {
+    match format {
+        SourceFormat::Json => "}",
+        _ => "",
+    }
+}
+
+fn write_entry(format: SourceFormat, chunk: &mut String, word_data: &WordData, is_first: bool) {
+    match format {
+        SourceFormat::Json => {
+            if !is_first {
+                chunk.push(',');
+            }
+            chunk.push_str(&serde_json::to_string(&word_data.word).unwrap_or_default());
+            chunk.push(':');
+            chunk.push_str(&word_data.popularity.to_string());
+        }
+        SourceFormat::Csv | SourceFormat::Tsv => {
+            let delimiter = if format == SourceFormat::Tsv { '\t' } else { ',' };
+
+            chunk.push_str(&delimited_field(&word_data.word, delimiter));
+            chunk.push(delimiter);
+            chunk.push_str(&word_data.popularity.to_string());
+            chunk.push('\n');
+        }
+        SourceFormat::Ndjson => {
+            chunk.push_str(&serde_json::to_string(word_data).unwrap_or_default());
+            chunk.push('\n');
+        }
+        SourceFormat::Lines => {
+            chunk.push_str(&word_data.word);
+            chunk.push('\n');
+        }
+    }
+}
+
+//quotes the names that would otherwise be split, the way the source file and import readers expect.
+fn delimited_field(name: &str, delimiter: char) -> String {
+    if name.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    } else {
+        name.to_string()
+    }
+}
+
+// Names of a whole export, taken from the live ones a batch at a time as the body is sent. Each batch is copied under its
+// own read lock, walking the names in key order from where the previous one stopped, so the lock is never held for all of
+// them. Names changed during the export are exported as they are when their batch is taken.
+pub struct ExportedWords {
+    shared_trie: Arc<RwLock<Trie>>,
+    metrics: Option<Arc<Metrics>>,
+    prefix: String,
+    min_popularity: Popularity,
+    batch_size: usize,
+    //key of the last names taken, the next batch starts after it.
+    last_key: Option<String>,
+    batch: vec::IntoIter<WordData>,
+    is_finished: bool,
+}
+
+impl ExportedWords {
+    pub fn new(
+        shared_trie: Arc<RwLock<Trie>>,
+        metrics: Option<Arc<Metrics>>,
+        prefix: String,
+        min_popularity: Popularity,
+        batch_size: usize,
+    ) -> ExportedWords {
+        ExportedWords {
+            shared_trie,
+            metrics,
+            prefix,
+            min_popularity,
+            batch_size,
+            last_key: None,
+            batch: Vec::new().into_iter(),
+            is_finished: false,
+        }
+    }
+
+    fn take_batch(&mut self) -> Result<(), AppError> {
+        let trie = metrics::read_trie(&self.shared_trie, self.metrics.as_deref())?;
+
+        let (batch_size, min_popularity) = (self.batch_size, self.min_popularity);
+        let mut words = Vec::new();
+        let mut last_key = self.last_key.clone();
+        let mut is_full = false;
+
+        //the names sharing a key go in the same batch, as the next one starts after that key
+        trie.visit_words_in_key_order(&self.prefix, self.last_key.as_deref(), |key, word_data| {
+            if words.len() >= batch_size && last_key.as_deref() != Some(key) {
+                is_full = true;
+                return false;
+            }
+
+            if word_data.popularity >= min_popularity {
+                words.push(word_data.clone());
+                if last_key.as_deref() != Some(key) {
+                    last_key = Some(key.to_string());
+                }
+            }
+
+            true
+        });
+
+        self.batch = words.into_iter();
+        self.last_key = last_key;
+        self.is_finished = !is_full;
+
+        Ok(())
+    }
+}
+
+impl Iterator for ExportedWords {
+    type Item = Result<WordData, AppError>;
+
+    fn next(&mut self) -> Option<Result<WordData, AppError>> {
+        loop {
+            if let Some(word_data) = self.batch.next() {
+                return Some(Ok(word_data));
+            }
+            if self.is_finished {
+                return None;
+            }
+
+            if let Err(e) = self.take_batch() {
+                self.is_finished = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+// Body of an export, written a chunk at a time as it is sent instead of all at once. The first chunk opens the
+// document and the last one closes it, so even an empty export is a valid document. When the names can't be taken,
+// the body ends with the error instead.
+pub struct ExportChunks<I: Iterator<Item = Result<WordData, AppError>>> {
+    words: Peekable<I>,
+    format: SourceFormat,
+    written_words: usize,
+    is_finished: bool,
+}
+
+impl<I: Iterator<Item = Result<WordData, AppError>>> ExportChunks<I> {
+    pub fn new(words: I, format: SourceFormat) -> ExportChunks<I> {
+        ExportChunks {
+            words: words.peekable(),
+            format,
+            written_words: 0,
+            is_finished: false,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<WordData, AppError>>> Iterator for ExportChunks<I> {
+    type Item = Result<String, AppError>;
+
+    fn next(&mut self) -> Option<Result<String, AppError>> {
+        if self.is_finished {
+            return None;
+        }
+
+        let mut chunk = String::new();
+        if self.written_words == 0 {
+            chunk.push_str(header(self.format));
+        }
+
+        for word_data in self.words.by_ref().take(EXPORT_CHUNK_SIZE) {
+            let word_data = match word_data {
+                Ok(word_data) => word_data,
+                Err(e) => {
+                    self.is_finished = true;
+                    return Some(Err(e));
+                }
+            };
+
+            write_entry(self.format, &mut chunk, &word_data, self.written_words == 0);
+            self.written_words += 1;
+        }
+
+        if self.words.peek().is_none() {
+            chunk.push_str(footer(self.format));
+            self.is_finished = true;
+        }
+
+        Some(Ok(chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{self, SourceOptions};
+    use crate::trie::{ITrie, Trie};
+
+    fn export(words: &[WordData], format: SourceFormat) -> String {
+        ExportChunks::new(words.iter().cloned().map(Ok), format).map(Result::unwrap).collect()
+    }
+
+    fn words() -> Vec<WordData> {
+        vec![
+            WordData::new("Aar".to_string(), 361),
+            WordData::new("Smith, \"Jr\"".to_string(), 5),
+            WordData::new("Zoë".to_string(), 0),
+        ]
+    }
+
+    #[test]
+    fn t_export_formats() {
+        assert_eq!(
+            "{\"Aar\":361,\"Smith, \\\"Jr\\\"\":5,\"Zoë\":0}",
+            export(&words(), SourceFormat::Json)
+        );
+        assert_eq!(
+            "name,times\nAar,361\n\"Smith, \"\"Jr\"\"\",5\nZoë,0\n",
+            export(&words(), SourceFormat::Csv)
+        );
+        assert_eq!(
+            "{\"name\":\"Aar\",\"times\":361}\n{\"name\":\"Smith, \\\"Jr\\\"\",\"times\":5}\n{\"name\":\"Zoë\",\"times\":0}\n",
+            export(&words(), SourceFormat::Ndjson)
+        );
+        assert_eq!(
+            "name\ttimes\nAar\t361\n\"Smith, \"\"Jr\"\"\"\t5\nZoë\t0\n",
+            export(&words(), SourceFormat::Tsv)
+        );
+        assert_eq!("Aar\nSmith, \"Jr\"\nZoë\n", export(&words(), SourceFormat::Lines));
+    }
+
+    #[test]
+    fn t_export_empty() {
+        assert_eq!("{}", export(&[], SourceFormat::Json));
+        assert_eq!("name,times\n", export(&[], SourceFormat::Csv));
+        assert_eq!("", export(&[], SourceFormat::Ndjson));
+        assert_eq!("name\ttimes\n", export(&[], SourceFormat::Tsv));
+        assert_eq!("", export(&[], SourceFormat::Lines));
+    }
+
+    #[test]
+    fn t_export_in_chunks() {
+        let words: Vec<WordData> = (0..EXPORT_CHUNK_SIZE * 2 + 1)
+            .map(|index| WordData::new(format!("Name{}", index), index as u64))
+            .collect();
+
+        let chunks: Vec<String> = ExportChunks::new(words.iter().cloned().map(Ok), SourceFormat::Json)
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(3, chunks.len());
+        assert!(chunks[0].starts_with("{\"Name0\":0,"));
+        assert!(chunks[1].starts_with(",\"Name500\":500,"));
+        assert_eq!(",\"Name1000\":1000}", chunks[2]);
+
+        let trie = Trie::initialize(&chunks.concat(), 10).unwrap();
+        assert_eq!(words.len(), trie.all_words().len());
+    }
+
+    #[test]
+    fn t_export_loads_back() {
+        let trie = Trie::initialize("{\"Aar\": 361, \"Smith, \\\"Jr\\\"\": 5, \"Zoë\": 0}", 10).unwrap();
+        let words: Vec<WordData> = trie.all_words().into_iter().cloned().collect();
+
+        let loaded_trie = Trie::initialize(&export(&words, SourceFormat::Json), 10).unwrap();
+        assert_eq!(trie.all_words(), loaded_trie.all_words());
+
+        for format in [SourceFormat::Csv, SourceFormat::Tsv, SourceFormat::Ndjson] {
+            let entries = source::parse(&export(&words, format), format, &SourceOptions::default()).unwrap();
+
+            assert_eq!(
+                words,
+                entries
+                    .into_iter()
+                    .map(|entry| WordData::new(entry.name, entry.popularity))
+                    .collect::<Vec<WordData>>()
+            );
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use crate::app_error::AppError;
+use crate::trie::{compare_word_data, ITrie, Node, Trie, WordData};
+use std::collections::HashMap;
+
+impl Trie {
+    //same as get_typeahead_words, but after the exact prefix matches it also suggests words whose beginning is up to max_edits
+    //insertions, deletions, substitutions or transpositions away from the prefix, closest ones first.
+    pub fn get_fuzzy_typeahead_words(
+        &self,
+        prefix: String,
+        max_edits: u8,
+    ) -> Result<Vec<WordData>, AppError> {
+        let max_edits = max_edits.min(self.max_fuzzy_edits);
+        let suggestion_number: usize = self.suggestion_number.into();
+        let mut words = self.get_typeahead_words(prefix.clone())?;
+
+        if max_edits == 0 || words.len() >= suggestion_number {
+            return Ok(words);
+        }
+
+        let prefix: Vec<char> = self.trie_key(&prefix).chars().collect();
+        let first_row: Vec<usize> = (0..=prefix.len()).collect();
+        let mut fuzzy_words: HashMap<&str, (usize, &WordData)> = HashMap::new();
+
+        for (char, child_node) in &self.root.children {
+            collect_fuzzy_words(
+                child_node,
+                *char,
+                None,
+                &prefix,
+                &first_row,
+                &[],
+                max_edits.into(),
+                &mut fuzzy_words,
+            );
+        }
+
+        let mut fuzzy_words: Vec<(usize, &WordData)> = fuzzy_words
+            .into_values()
+            .filter(|(_, word_data)| !words.contains(word_data))
+            .collect();
+
+        //order by edit distance asc, then by popularity desc and then by word asc
+        fuzzy_words.sort_by(|(distance_one, word_data_one), (distance_two, word_data_two)| {
+            distance_one
+                .cmp(distance_two)
+                .then(compare_word_data(word_data_one, word_data_two))
+        });
+
+        words.extend(
+            fuzzy_words
+                .into_iter()
+                .take(suggestion_number - words.len())
+                .map(|(_, word_data)| word_data.clone()),
+        );
+
+        Ok(words)
+    }
+}
+
+//walks the trie computing one row of the optimal string alignment distance between the prefix and the path of each node.
+//the last cell of the row is the distance between the whole prefix and the path, so when it is small enough every word below
+//the node matches. The walk stops as soon as every cell is above max_edits, as no longer path can get closer.
+#[allow(clippy::too_many_arguments)]
+fn collect_fuzzy_words<'a>(
+    node: &'a Node,
+    char: char,
+    parent_char: Option<char>,
+    prefix: &[char],
+    parent_row: &[usize],
+    grandparent_row: &[usize],
+    max_edits: usize,
+    fuzzy_words: &mut HashMap<&'a str, (usize, &'a WordData)>,
+) {
+    let mut row = Vec::with_capacity(parent_row.len());
+    row.push(parent_row[0] + 1);
+
+    for i in 1..parent_row.len() {
+        let substitution_cost = usize::from(prefix[i - 1] != char);
+        let mut distance = (parent_row[i] + 1)
+            .min(row[i - 1] + 1)
+            .min(parent_row[i - 1] + substitution_cost);
+
+        if i > 1 && parent_char == Some(prefix[i - 1]) && prefix[i - 2] == char {
+            distance = distance.min(grandparent_row[i - 2] + 1);
+        }
+
+        row.push(distance);
+    }
+
+    let distance = row[prefix.len()];
+    if distance <= max_edits {
+        for word_data in &node.top_words {
+            let entry = fuzzy_words
+                .entry(word_data.word.as_str())
+                .or_insert((distance, word_data));
+
+            if distance < entry.0 {
+                *entry = (distance, word_data);
+            }
+        }
+    }
+
+    if row.iter().all(|distance| *distance > max_edits) {
+        return;
+    }
+
+    for (child_char, child_node) in &node.children {
+        collect_fuzzy_words(
+            child_node,
+            *child_char,
+            Some(char),
+            prefix,
+            &row,
+            parent_row,
+            max_edits,
+            fuzzy_words,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trie::{ITrie, Trie, WordData};
+
+    fn get_default_trie() -> Trie {
+        let file_content =
+            "{\"Jan\": 296, \"Janel\": 955, \"Janet\": 936, \"Jana\": 120, \"Ajna\": 80, \"Jnae\": 10, \"Joan\": 400, \"Bob\": 70}";
+        Trie::initialize(file_content, 10).unwrap()
+    }
+
+    #[test]
+    fn t_get_fuzzy_typeahead_words_transposition() {
+        let trie = get_default_trie();
+
+        let words = trie.get_fuzzy_typeahead_words("jna".to_string(), 1).unwrap();
+
+        //"Jnae" matches exactly, then every "jan..." is one transposition away
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Jnae".to_string(), 10),
+            WordData::new("Janel".to_string(), 955),
+            WordData::new("Janet".to_string(), 936),
+            WordData::new("Joan".to_string(), 400),
+            WordData::new("Jan".to_string(), 296),
+            WordData::new("Jana".to_string(), 120),
+            WordData::new("Ajna".to_string(), 80),
+        ];
+
+        assert_eq!(expected_words, words);
+    }
+
+    #[test]
+    fn t_get_fuzzy_typeahead_words_substitution() {
+        let mut trie = get_default_trie();
+        trie.suggestion_number = 3;
+
+        let words = trie.get_fuzzy_typeahead_words("jen".to_string(), 1).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Janel".to_string(), 955),
+            WordData::new("Janet".to_string(), 936),
+            WordData::new("Jan".to_string(), 296),
+        ];
+
+        assert_eq!(expected_words, words);
+    }
+
+    #[test]
+    fn t_get_fuzzy_typeahead_words_exact_matches_first() {
+        let mut trie = get_default_trie();
+        trie.suggestion_number = 4;
+
+        let words = trie.get_fuzzy_typeahead_words("jo".to_string(), 1).unwrap();
+
+        //"Joan" is the only exact match, the others only need one substitution even though they are more popular
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Joan".to_string(), 400),
+            WordData::new("Janel".to_string(), 955),
+            WordData::new("Janet".to_string(), 936),
+            WordData::new("Jan".to_string(), 296),
+        ];
+
+        assert_eq!(expected_words, words);
+    }
+
+    #[test]
+    fn t_get_fuzzy_typeahead_words_closest_first() {
+        let trie = get_default_trie();
+
+        let words = trie.get_fuzzy_typeahead_words("jabe".to_string(), 2).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Janel".to_string(), 955),
+            WordData::new("Janet".to_string(), 936),
+            WordData::new("Jan".to_string(), 296),
+            WordData::new("Jana".to_string(), 120),
+            WordData::new("Jnae".to_string(), 10),
+        ];
+
+        assert_eq!(expected_words, words[..5]);
+    }
+
+    #[test]
+    fn t_get_fuzzy_typeahead_words_no_edits() {
+        let trie = get_default_trie();
+
+        let words = trie.get_fuzzy_typeahead_words("jna".to_string(), 0).unwrap();
+
+        assert_eq!(vec![WordData::new("Jnae".to_string(), 10)], words);
+    }
+
+    #[test]
+    fn t_get_fuzzy_typeahead_words_max_edits_capped() {
+        let mut trie = get_default_trie();
+        trie.max_fuzzy_edits = 1;
+
+        let words = trie.get_fuzzy_typeahead_words("bobby".to_string(), 3).unwrap();
+
+        assert_eq!(Vec::<WordData>::new(), words);
+    }
+}
@@ -1,27 +1,79 @@
 use crate::app_error::AppError;
+use crate::decay;
+use crate::export::{self, ExportChunks, ExportedWords, DEFAULT_EXPORT_PAGE_SIZE};
+use crate::import::{self, ConflictPolicy, ImportParser, ImportSummary};
+use crate::journal::{Journal, JournalRecord};
+use crate::metrics::{self, Metrics};
+use crate::pagination::{self, Cursor, PageRequest, DEFAULT_MAX_RESULT_LIMIT};
+use crate::ranking::{Ranking, RankingStrategy};
+use crate::source::SourceFormat;
+use crate::stats::StatsCache;
 use crate::trie::ITrie;
-use crate::trie::Trie;
-use actix_web::{get, post, web, HttpResponse};
+use crate::trie::{Popularity, Trie, WordData};
+use actix_web::error::{PathError, QueryPayloadError};
+use actix_web::http::header;
+use actix_web::{delete, get, patch, post, put, web, HttpMessage, HttpRequest, HttpResponse};
 use log::info;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Serialize;
+use futures_util::StreamExt;
 use serde_json::json;
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+#[derive(Deserialize)]
+struct TypeaheadQuery {
+    // Amount of typos tolerated in the prefix, none by default
+    #[serde(default)]
+    pub fuzzy: u8,
+    // Any of these returns a page of the suggestions, along with the cursor of the next one, instead of just the first SUGGESTION_NUMBER
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    pub cursor: Option<String>,
+    // Override the ranking set by RANKING, EXACT_MATCH_FIRST and DECAY_WEIGHT
+    pub ranking: Option<RankingStrategy>,
+    pub exact_match_first: Option<bool>,
+    pub decay_weight: Option<u8>,
+}
+
+// Most names a page can hold, whatever the limit asked for
+pub struct MaxResultLimit(pub usize);
+
+// Most names a page of an export can hold, also the names copied under each read lock by an export without a limit
+pub struct ExportPageSize(pub usize);
+
+// Query strings and paths that don't deserialize fail with the same error body as the handlers, which also counts them in the error metrics
+pub fn query_config() -> web::QueryConfig {
+    web::QueryConfig::default().error_handler(|e, _req| match e {
+        QueryPayloadError::Deserialize(e) => AppError::InvalidQuery { reason: e.to_string() }.into(),
+    })
+}
+
+pub fn path_config() -> web::PathConfig {
+    web::PathConfig::default().error_handler(|e, _req| match e {
+        PathError::Deserialize(e) => AppError::InvalidPath { reason: e.to_string() }.into(),
+    })
+}
 
 #[allow(clippy::unused_async)]
 #[get("/typeahead/{prefix}")]
 async fn get_words_match_prefix(
-    shared_trie: web::Data<Arc<Mutex<Trie>>>,
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    metrics: Option<web::Data<Metrics>>,
+    max_result_limit: Option<web::Data<MaxResultLimit>>,
+    ranking: Option<web::Data<Ranking>>,
     prefix: web::Path<String>,
+    query: web::Query<TypeaheadQuery>,
 ) -> Result<HttpResponse, AppError> {
     // Trace the message body received as this is the only way found to be able to log the request body and hence figure out any json issues before trying to parse it
-    info!("prefix: \n{:?}", prefix);
+    info!(
+        "prefix: \n{:?}, fuzzy: {}, limit: {:?}, offset: {:?}, cursor: {:?}",
+        prefix, query.fuzzy, query.limit, query.offset, query.cursor
+    );
 
-    let trie = shared_trie.lock().unwrap();
-    let result = trie.get_typeahead_words(prefix.into_inner())?;
-
-    Ok(HttpResponse::Ok().json(result))
+    typeahead(&shared_trie, metrics, max_result_limit, ranking, prefix.into_inner(), &query)
 }
 
 // These functions are needed according to this: https://github.com/actix/actix-web/issues/1054
@@ -29,29 +81,111 @@ async fn get_words_match_prefix(
 #[allow(clippy::unused_async)]
 #[get("/typeahead/")]
 async fn get_words_match_empty_prefix_with_last_slash(
-    shared_trie: web::Data<Arc<Mutex<Trie>>>,
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    metrics: Option<web::Data<Metrics>>,
+    max_result_limit: Option<web::Data<MaxResultLimit>>,
+    ranking: Option<web::Data<Ranking>>,
+    query: web::Query<TypeaheadQuery>,
 ) -> Result<HttpResponse, AppError> {
     // Trace the message body received as this is the only way found to be able to log the request body and hence figure out any json issues before trying to parse it
     info!("prefix is empty");
 
-    let trie = shared_trie.lock().unwrap();
-    let result = trie.get_typeahead_words(String::new())?;
-
-    Ok(HttpResponse::Ok().json(result))
+    typeahead(&shared_trie, metrics, max_result_limit, ranking, String::new(), &query)
 }
 
 #[allow(clippy::unused_async)]
 #[get("/typeahead")]
 async fn get_words_match_empty_prefix(
-    shared_trie: web::Data<Arc<Mutex<Trie>>>,
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    metrics: Option<web::Data<Metrics>>,
+    max_result_limit: Option<web::Data<MaxResultLimit>>,
+    ranking: Option<web::Data<Ranking>>,
+    query: web::Query<TypeaheadQuery>,
 ) -> Result<HttpResponse, AppError> {
     // Trace the message body received as this is the only way found to be able to log the request body and hence figure out any json issues before trying to parse it
     info!("prefix is empty");
 
-    let trie = shared_trie.lock().unwrap();
-    let result = trie.get_typeahead_words(String::new())?;
+    typeahead(&shared_trie, metrics, max_result_limit, ranking, String::new(), &query)
+}
 
-    Ok(HttpResponse::Ok().json(result))
+fn typeahead(
+    shared_trie: &RwLock<Trie>,
+    metrics: Option<web::Data<Metrics>>,
+    max_result_limit: Option<web::Data<MaxResultLimit>>,
+    ranking: Option<web::Data<Ranking>>,
+    prefix: String,
+    query: &TypeaheadQuery,
+) -> Result<HttpResponse, AppError> {
+    let metrics = metrics.as_ref().map(|metrics| metrics.get_ref());
+    let is_paged = query.limit.is_some() || query.offset.is_some() || query.cursor.is_some();
+
+    let default_ranking = ranking.map_or_else(Ranking::default, |ranking| *ranking.get_ref());
+    let ranking = Ranking {
+        strategy: query.ranking.unwrap_or(default_ranking.strategy),
+        exact_match_first: query.exact_match_first.unwrap_or(default_ranking.exact_match_first),
+        decay_weight: query.decay_weight.unwrap_or(default_ranking.decay_weight),
+    };
+
+    if query.limit == Some(0) {
+        return Err(invalid_query("limit must be at least 1"));
+    }
+    if ranking.decay_weight > 100 {
+        return Err(invalid_query("decay_weight must be from 0 to 100"));
+    }
+    // Fuzzy matches are ranked by edits on top of popularity, so there is no cursor or other ranking for them
+    if query.fuzzy > 0 && is_paged {
+        return Err(invalid_query("fuzzy matches can't be paged with limit, offset or cursor"));
+    }
+    if query.fuzzy > 0 && !ranking.is_cached() {
+        return Err(invalid_query(
+            "fuzzy matches can only be ranked by popularity with exact_match_first",
+        ));
+    }
+    let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+
+    let trie = metrics::read_trie(shared_trie, metrics)?;
+
+    if !is_paged {
+        let result = if query.fuzzy > 0 {
+            trie.get_fuzzy_typeahead_words(prefix, query.fuzzy)?
+        } else {
+            trie.get_ranked_typeahead_words(prefix, &ranking)?
+        };
+        observe_result_size(metrics, result.len());
+
+        return Ok(HttpResponse::Ok().json(result));
+    }
+
+    let max_result_limit = max_result_limit.map_or(DEFAULT_MAX_RESULT_LIMIT, |max_result_limit| max_result_limit.0);
+    let page_request = PageRequest {
+        cursor,
+        offset: query.offset.unwrap_or(0),
+        limit: query
+            .limit
+            .unwrap_or_else(|| trie.suggestion_number.into())
+            .min(max_result_limit),
+        ranking,
+    };
+
+    let page = pagination::page(&trie, prefix, &page_request)?;
+    observe_result_size(metrics, page.results.len());
+
+    Ok(HttpResponse::Ok().json(page))
+}
+
+fn observe_result_size(metrics: Option<&Metrics>, size: usize) {
+    if let Some(metrics) = metrics {
+        metrics.observe_result_size(size);
+    }
+}
+
+// Tells a body that is not UTF-8 from one that is not JSON, or that misses the expected fields
+fn parse_payload<T: DeserializeOwned>(payload: &[u8]) -> Result<T, AppError> {
+    let payload = std::str::from_utf8(payload).map_err(|e| AppError::InvalidPayloadEncoding {
+        valid_up_to: e.valid_up_to(),
+    })?;
+
+    serde_json::from_str(payload).map_err(|e| AppError::from_payload_error(&e))
 }
 
 #[derive(Deserialize, Serialize)]
@@ -62,43 +196,560 @@ struct IncreasePopularityPayload {
 #[allow(clippy::unused_async)]
 #[post("/typeahead")]
 async fn increase_popularity(
-    shared_trie: web::Data<Arc<Mutex<Trie>>>,
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    journal: Option<web::Data<Journal>>,
+    metrics: Option<web::Data<Metrics>>,
     payload: web::Bytes,
 ) -> Result<HttpResponse, AppError> {
     // Trace the message body received as this is the only way found to be able to log the request body and hence figure out any json issues before trying to parse it
     info!("PAYLOAD: \n{:?}", payload);
 
-    let payload = String::from_utf8(payload.to_vec()).map_err(|_e| AppError::BadRequest)?;
-    let deserialized_payload = serde_json::from_str::<IncreasePopularityPayload>(&payload)
-        .map_err(|_e| AppError::BadRequest)?;
-
-    let mut trie = shared_trie.lock().unwrap();
-    let result = trie.increase_popularity(deserialized_payload.name)?;
+    let deserialized_payload = parse_payload::<IncreasePopularityPayload>(&payload)?;
+    // Popularity is only bumped after the payload is parsed so the write lock, the only one that blocks readers, is held as briefly as possible
+    let result = hit_name(&shared_trie, journal, metrics, deserialized_payload.name)?;
 
     let json = json! ({ "name": result.word, "times": result.popularity });
 
     Ok(HttpResponse::Created().json(json))
 }
 
+fn hit_name(
+    shared_trie: &RwLock<Trie>,
+    journal: Option<web::Data<Journal>>,
+    metrics: Option<web::Data<Metrics>>,
+    name: String,
+) -> Result<WordData, AppError> {
+    let mut trie = metrics::write_trie(shared_trie, metrics.as_ref().map(|metrics| metrics.get_ref()))?;
+    let word = trie.word_to_hit(&name)?.word.clone();
+    let at = SystemTime::now();
+
+    // Appending while still holding the write lock keeps the journal in the same order as the changes, and appending
+    // first leaves the popularity untouched when the hit can't be journaled
+    append_to_journal(
+        journal,
+        JournalRecord::Hit {
+            name: word.clone(),
+            at: Some(decay::to_unix_millis(at)),
+        },
+    )?;
+
+    trie.hit(word, at)
+}
+
+// Secret expected as a bearer token by the admin endpoints. They reject every request when it's not configured
+pub struct AdminToken(pub String);
+
+fn authorize(req: &HttpRequest, admin_token: Option<web::Data<AdminToken>>) -> Result<(), AppError> {
+    let admin_token = admin_token.ok_or(AppError::Unauthorized)?;
+    let bearer_token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AppError::Unauthorized)?;
+
+    // Compares every byte so the time taken doesn't tell how much of the token was right
+    let expected_token = admin_token.0.as_bytes();
+    let is_valid = bearer_token.len() == expected_token.len()
+        && bearer_token
+            .bytes()
+            .zip(expected_token)
+            .fold(0, |difference, (one, two)| difference | (one ^ two))
+            == 0;
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(AppError::Unauthorized)
+    }
+}
+
+fn invalid_query(reason: &str) -> AppError {
+    AppError::InvalidQuery {
+        reason: reason.to_string(),
+    }
+}
+
+fn parse_name(name: String) -> Result<String, AppError> {
+    if name.trim().is_empty() {
+        return Err(AppError::BadRequest);
+    }
+
+    Ok(name)
+}
+
+fn append_to_journal(journal: Option<web::Data<Journal>>, record: JournalRecord) -> Result<(), AppError> {
+    match journal {
+        Some(journal) => journal.append(&record),
+        None => Ok(()),
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct InsertNamePayload {
+    pub name: String,
+    #[serde(default)]
+    pub times: Popularity,
+}
+
+#[allow(clippy::unused_async)]
+#[post("/admin/names")]
+async fn insert_name(
+    req: HttpRequest,
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    journal: Option<web::Data<Journal>>,
+    metrics: Option<web::Data<Metrics>>,
+    admin_token: Option<web::Data<AdminToken>>,
+    payload: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    authorize(&req, admin_token)?;
+    info!("PAYLOAD: \n{:?}", payload);
+
+    let deserialized_payload = parse_payload::<InsertNamePayload>(&payload)?;
+    let name = parse_name(deserialized_payload.name)?;
+
+    let mut trie = metrics::write_trie(&shared_trie, metrics.as_ref().map(|metrics| metrics.get_ref()))?;
+    if trie.find_word(&name).is_some() {
+        return Err(AppError::WordAlreadyExists);
+    }
+
+    // Like the hits, the change is only applied once it's journaled
+    append_to_journal(
+        journal,
+        JournalRecord::Insert {
+            name: name.clone(),
+            times: deserialized_payload.times,
+        },
+    )?;
+    trie.insert_word(name.clone(), deserialized_payload.times)?;
+
+    let json = json! ({ "name": name, "times": deserialized_payload.times });
+
+    Ok(HttpResponse::Created().json(json))
+}
+
+#[allow(clippy::unused_async)]
+#[delete("/admin/names/{name}")]
+async fn remove_name(
+    req: HttpRequest,
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    journal: Option<web::Data<Journal>>,
+    metrics: Option<web::Data<Metrics>>,
+    admin_token: Option<web::Data<AdminToken>>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    authorize(&req, admin_token)?;
+    info!("name: \n{:?}", name);
+
+    let mut trie = metrics::write_trie(&shared_trie, metrics.as_ref().map(|metrics| metrics.get_ref()))?;
+    let current_name = trie
+        .find_word(&name)
+        .ok_or_else(|| AppError::WordDoesNotExist { name: name.to_string() })?
+        .word
+        .clone();
+
+    append_to_journal(
+        journal,
+        JournalRecord::Remove {
+            name: current_name.clone(),
+        },
+    )?;
+    let result = trie.remove_word(current_name)?;
+
+    let json = json! ({ "name": result.word, "times": result.popularity });
+
+    Ok(HttpResponse::Ok().json(json))
+}
+
+#[derive(Deserialize, Serialize)]
+struct RenameNamePayload {
+    pub name: String,
+}
+
+#[allow(clippy::unused_async)]
+#[patch("/admin/names/{name}")]
+async fn rename_name(
+    req: HttpRequest,
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    journal: Option<web::Data<Journal>>,
+    metrics: Option<web::Data<Metrics>>,
+    admin_token: Option<web::Data<AdminToken>>,
+    name: web::Path<String>,
+    payload: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    authorize(&req, admin_token)?;
+    info!("name: \n{:?}, PAYLOAD: \n{:?}", name, payload);
+
+    let deserialized_payload = parse_payload::<RenameNamePayload>(&payload)?;
+    let new_name = parse_name(deserialized_payload.name)?;
+
+    let mut trie = metrics::write_trie(&shared_trie, metrics.as_ref().map(|metrics| metrics.get_ref()))?;
+    let current_name = trie.word_to_rename(&name, &new_name)?.word.clone();
+
+    append_to_journal(
+        journal,
+        JournalRecord::Rename {
+            name: current_name.clone(),
+            new_name: new_name.clone(),
+        },
+    )?;
+    let result = trie.rename_word(current_name, new_name)?;
+
+    let json = json! ({ "name": result.word, "times": result.popularity });
+
+    Ok(HttpResponse::Ok().json(json))
+}
+
+// The /v1/names routes treat every name as a resource, so an unknown one is a 404 instead of the 400 kept by the older routes
+fn name_not_found(e: AppError) -> AppError {
+    match e {
+        AppError::WordDoesNotExist { name } => AppError::NameNotFound { name },
+        e => e,
+    }
+}
+
+#[allow(clippy::unused_async)]
+#[get("/v1/names/{name}")]
+async fn get_name(
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    metrics: Option<web::Data<Metrics>>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    info!("name: \n{:?}", name);
+
+    let trie = metrics::read_trie(&shared_trie, metrics.as_ref().map(|metrics| metrics.get_ref()))?;
+    let word_data = trie.find_word(&name).ok_or_else(|| AppError::NameNotFound {
+        name: name.to_string(),
+    })?;
+
+    Ok(HttpResponse::Ok().json(word_data))
+}
+
+#[allow(clippy::unused_async)]
+#[post("/v1/names/{name}/hits")]
+async fn hit_name_resource(
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    journal: Option<web::Data<Journal>>,
+    metrics: Option<web::Data<Metrics>>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    info!("name: \n{:?}", name);
+
+    let result = hit_name(&shared_trie, journal, metrics, name.into_inner()).map_err(name_not_found)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+#[derive(Deserialize, Serialize)]
+struct SetPopularityPayload {
+    pub times: Popularity,
+}
+
+#[allow(clippy::unused_async)]
+#[put("/v1/names/{name}")]
+async fn put_name(
+    req: HttpRequest,
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    journal: Option<web::Data<Journal>>,
+    metrics: Option<web::Data<Metrics>>,
+    admin_token: Option<web::Data<AdminToken>>,
+    name: web::Path<String>,
+    payload: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    authorize(&req, admin_token)?;
+    info!("name: \n{:?}, PAYLOAD: \n{:?}", name, payload);
+
+    if req.content_type() != "application/json" {
+        return Err(AppError::UnsupportedMediaType);
+    }
+
+    let deserialized_payload = parse_payload::<SetPopularityPayload>(&payload)?;
+    let name = parse_name(name.into_inner())?;
+
+    let mut trie = metrics::write_trie(&shared_trie, metrics.as_ref().map(|metrics| metrics.get_ref()))?;
+    // The path is the name with its casing, so another casing of it is a different resource taking its place
+    let is_new_name = match trie.find_word(&name) {
+        None => true,
+        Some(word_data) if word_data.word == name => false,
+        Some(_) => return Err(AppError::WordAlreadyExists),
+    };
+
+    append_to_journal(
+        journal,
+        JournalRecord::Insert {
+            name: name.clone(),
+            times: deserialized_payload.times,
+        },
+    )?;
+    trie.insert_word(name.clone(), deserialized_payload.times)?;
+
+    let json = json! ({ "name": name, "times": deserialized_payload.times });
+
+    if is_new_name {
+        Ok(HttpResponse::Created().json(json))
+    } else {
+        Ok(HttpResponse::Ok().json(json))
+    }
+}
+
+#[derive(Deserialize)]
+struct ImportQuery {
+    // Taken from the content type when not given
+    pub format: Option<SourceFormat>,
+    #[serde(default = "default_conflict_policy")]
+    pub policy: ConflictPolicy,
+}
+
+fn default_conflict_policy() -> ConflictPolicy {
+    ConflictPolicy::Keep
+}
+
+fn import_format_from_content_type(req: &HttpRequest) -> SourceFormat {
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    if content_type.starts_with("application/x-ndjson") {
+        SourceFormat::Ndjson
+    } else if content_type.starts_with("text/csv") {
+        SourceFormat::Csv
+    } else if content_type.starts_with("text/tab-separated-values") {
+        SourceFormat::Tsv
+    } else if content_type.starts_with("text/plain") {
+        SourceFormat::Lines
+    } else {
+        SourceFormat::Json
+    }
+}
+
+fn apply_import(
+    shared_trie: &RwLock<Trie>,
+    journal: Option<&Journal>,
+    metrics: Option<&Metrics>,
+    entries: Vec<Result<import::ImportEntry, import::Rejection>>,
+    policy: ConflictPolicy,
+    summary: &mut ImportSummary,
+) -> Result<(), AppError> {
+    let mut trie = metrics::write_trie(shared_trie, metrics)?;
+
+    import::apply(&mut trie, entries, policy, summary, |record| match journal {
+        Some(journal) => journal.append(record),
+        None => Ok(()),
+    })
+}
+
+// Merges the body as its chunks arrive, so the write lock is never held for the whole body
+async fn merge_import(
+    shared_trie: &RwLock<Trie>,
+    journal: Option<&Journal>,
+    metrics: Option<&Metrics>,
+    payload: &mut web::Payload,
+    format: SourceFormat,
+    policy: ConflictPolicy,
+    summary: &mut ImportSummary,
+) -> Result<(), AppError> {
+    let mut parser = ImportParser::new(format);
+
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| AppError::UnreadablePayload { reason: e.to_string() })?;
+        let entries = parser.feed(&chunk)?;
+
+        if !entries.is_empty() {
+            apply_import(shared_trie, journal, metrics, entries, policy, summary)?;
+        }
+    }
+
+    apply_import(shared_trie, journal, metrics, parser.finish()?, policy, summary)
+}
+
+#[allow(clippy::unused_async)]
+#[post("/admin/import")]
+async fn import_names(
+    req: HttpRequest,
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    journal: Option<web::Data<Journal>>,
+    metrics: Option<web::Data<Metrics>>,
+    admin_token: Option<web::Data<AdminToken>>,
+    query: web::Query<ImportQuery>,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, AppError> {
+    authorize(&req, admin_token)?;
+
+    let format = query
+        .format
+        .unwrap_or_else(|| import_format_from_content_type(&req));
+    info!("import, format: {:?}, policy: {:?}", format, query.policy);
+
+    let journal = journal.as_ref().map(|journal| journal.get_ref());
+    let metrics = metrics.as_ref().map(|metrics| metrics.get_ref());
+    let mut summary = ImportSummary::default();
+
+    // What was merged before a failure stays, so the summary up to it goes along with the error
+    if let Err(e) = merge_import(&shared_trie, journal, metrics, &mut payload, format, query.policy, &mut summary).await {
+        return Err(AppError::ImportInterrupted {
+            cause: Box::new(e),
+            summary,
+        });
+    }
+
+    info!(
+        "imported: {} inserted, {} updated, {} skipped, {} rejected",
+        summary.inserted, summary.updated, summary.skipped, summary.rejected
+    );
+
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    #[serde(default = "default_export_format")]
+    pub format: SourceFormat,
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub min_popularity: Popularity,
+    #[serde(default)]
+    pub offset: usize,
+    // Never more than EXPORT_PAGE_SIZE names. When not given, all of the names are exported
+    pub limit: Option<usize>,
+}
+
+fn default_export_format() -> SourceFormat {
+    SourceFormat::Json
+}
+
+#[allow(clippy::unused_async)]
+#[get("/export")]
+async fn export_names(
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    metrics: Option<web::Data<Metrics>>,
+    export_page_size: Option<web::Data<ExportPageSize>>,
+    query: web::Query<ExportQuery>,
+) -> Result<HttpResponse, AppError> {
+    info!(
+        "export, format: {:?}, prefix: {:?}, min_popularity: {}, offset: {}, limit: {:?}",
+        query.format, query.prefix, query.min_popularity, query.offset, query.limit
+    );
+
+    if query.limit == Some(0) {
+        return Err(invalid_query("limit must be at least 1"));
+    }
+    let export_page_size = export_page_size.map_or(DEFAULT_EXPORT_PAGE_SIZE, |export_page_size| export_page_size.0);
+
+    let mut response = HttpResponse::Ok();
+    response.content_type(export::content_type(query.format));
+
+    let limit = match query.limit {
+        Some(limit) => limit.min(export_page_size),
+        None => {
+            // Without a limit all of the names are streamed, copying EXPORT_PAGE_SIZE of them under each read lock
+            let words = ExportedWords::new(
+                shared_trie.get_ref().clone(),
+                metrics.map(|metrics| metrics.into_inner()),
+                query.prefix.clone(),
+                query.min_popularity,
+                export_page_size,
+            )
+            .skip(query.offset);
+            let chunks = ExportChunks::new(words, query.format)
+                .map(|chunk| chunk.map(web::Bytes::from).map_err(actix_web::Error::from));
+
+            return Ok(response.streaming(futures_util::stream::iter(chunks)));
+        }
+    };
+
+    // Only the requested page is copied while holding the read lock, the body is written after it is released. The
+    // other names are only counted, walking them in key order without sorting or copying them
+    let (total_count, words) = {
+        let trie = metrics::read_trie(&shared_trie, metrics.as_ref().map(|metrics| metrics.get_ref()))?;
+        let mut total_count = 0;
+        let mut words: Vec<WordData> = Vec::new();
+
+        trie.visit_words_in_key_order(&query.prefix, None, |_key, word_data| {
+            if word_data.popularity >= query.min_popularity {
+                if total_count >= query.offset && words.len() < limit {
+                    words.push(word_data.clone());
+                }
+                total_count += 1;
+            }
+
+            true
+        });
+
+        (total_count, words)
+    };
+
+    response.header("X-Total-Count", total_count.to_string());
+
+    let next_offset = query.offset + words.len();
+    if next_offset < total_count {
+        response.header("X-Next-Offset", next_offset.to_string());
+    }
+
+    let chunks = ExportChunks::new(words.into_iter().map(Ok), query.format)
+        .map(|chunk| chunk.map(web::Bytes::from).map_err(actix_web::Error::from));
+
+    Ok(response.streaming(futures_util::stream::iter(chunks)))
+}
+
+#[allow(clippy::unused_async)]
+#[get("/stats")]
+async fn get_stats(
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    stats_cache: web::Data<StatsCache>,
+) -> Result<HttpResponse, AppError> {
+    info!("stats");
+
+    let stats = stats_cache.get(&shared_trie)?;
+
+    Ok(HttpResponse::Ok().json(&*stats))
+}
+
+#[allow(clippy::unused_async)]
+#[get("/metrics")]
+async fn get_metrics(
+    shared_trie: web::Data<Arc<RwLock<Trie>>>,
+    metrics: web::Data<Metrics>,
+    stats_cache: web::Data<StatsCache>,
+) -> Result<HttpResponse, AppError> {
+    // The word count comes from the cached statistics, so frequent scrapes don't walk the trie every time
+    metrics.set_words(stats_cache.get(&shared_trie)?.word_count);
+
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(metrics.encode()?))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::trie::{ITrie, Trie};
     use std::sync::Arc;
-    use std::sync::Mutex;
+    use std::sync::RwLock;
     use crate::handlers::{get_words_match_prefix, increase_popularity, get_words_match_empty_prefix,get_words_match_empty_prefix_with_last_slash};
+    use crate::handlers::{export_names, query_config, path_config, ExportPageSize, MaxResultLimit, get_metrics, get_name, get_stats, hit_name_resource, put_name, import_names, insert_name, remove_name, rename_name, AdminToken};
+    use crate::journal::tests::temp_file_path;
+    use crate::journal::{FsyncPolicy, Journal, JournalRecord};
+    use crate::metrics::Metrics;
+    use crate::ranking::{Ranking, RankingStrategy};
+    use crate::stats::StatsCache;
+    use serde_json::json;
+    use crate::trie::WordData;
+    use actix_web::web;
     use actix_web::{body::Body, test, web::Bytes, App};
     use actix_web::http::StatusCode;
 
     fn get_default_trie() -> Trie {
         let file_content =
         "{\"A-b\": 23, \"Aar\":361,\"Aari\":151,\"Aba\":608,\"Abag\":704, \"Abe\": 300, \"Ba\": 5, \"Bah\": 5, \"Be\": 50, \"Bc\": 50}";
-        Trie::initialize(&file_content, 5).unwrap()
+        Trie::initialize(file_content, 5).unwrap()
     }
 
     #[actix_rt::test]
     async fn t_get_words_match_prefix_prefix_not_included() {
         let trie = get_default_trie();
-        let shared_trie: Arc<Mutex<Trie>> = Arc::new(Mutex::new(trie));
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
         
         let app = App::new().data(shared_trie).service(get_words_match_prefix);
         let mut app = test::init_service(app).await;
@@ -119,7 +770,7 @@ mod tests {
     #[actix_rt::test]
     async fn t_get_words_match_prefix_exact_match_prefix() {
         let trie = get_default_trie();
-        let shared_trie: Arc<Mutex<Trie>> = Arc::new(Mutex::new(trie));
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
         
         let app = App::new().data(shared_trie).service(get_words_match_prefix);
         let mut app = test::init_service(app).await;
@@ -141,7 +792,7 @@ mod tests {
     async fn t_get_words_match_prefix_words_with_same_popularity() {
         let mut trie = get_default_trie();
         trie.suggestion_number = 2;
-        let shared_trie: Arc<Mutex<Trie>> = Arc::new(Mutex::new(trie));
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
         
         let app = App::new().data(shared_trie).service(get_words_match_prefix);
         let mut app = test::init_service(app).await;
@@ -163,7 +814,7 @@ mod tests {
     async fn t_get_words_match_prefix_case_insensitive() {
         let mut trie = get_default_trie();
         trie.suggestion_number = 2;
-        let shared_trie: Arc<Mutex<Trie>> = Arc::new(Mutex::new(trie));
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
         
         let app = App::new().data(shared_trie).service(get_words_match_prefix);
         let mut app = test::init_service(app).await;
@@ -185,7 +836,7 @@ mod tests {
     async fn t_get_words_match_prefix_no_words_match_prefix() {
         let mut trie = get_default_trie();
         trie.suggestion_number = 2;
-        let shared_trie: Arc<Mutex<Trie>> = Arc::new(Mutex::new(trie));
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
         
         let app = App::new().data(shared_trie).service(get_words_match_prefix);
         let mut app = test::init_service(app).await;
@@ -207,7 +858,7 @@ mod tests {
     async fn t_get_words_match_prefix_return_only_prefix_and_special_character() {
         let mut trie = get_default_trie();
         trie.suggestion_number = 2;
-        let shared_trie: Arc<Mutex<Trie>> = Arc::new(Mutex::new(trie));
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
         
         let app = App::new().data(shared_trie).service(get_words_match_prefix);
         let mut app = test::init_service(app).await;
@@ -226,14 +877,15 @@ mod tests {
     }
 
     #[actix_rt::test]
-    async fn t_get_words_match_prefix_empty_prefix() {
-        let trie = get_default_trie();
-        let shared_trie: Arc<Mutex<Trie>> = Arc::new(Mutex::new(trie));
+    async fn t_get_words_match_prefix_unicode_prefix() {
+        let trie = Trie::initialize("{\"Élodie\": 120, \"Elodie\": 80, \"Émile\": 95}", 5).unwrap();
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
         
-        let app = App::new().data(shared_trie).service(get_words_match_empty_prefix);
+        let app = App::new().data(shared_trie).service(get_words_match_prefix);
         let mut app = test::init_service(app).await;
 
-        let req = test::TestRequest::get().uri("/typeahead").to_request();
+        // "ÉL" percent-encoded
+        let req = test::TestRequest::get().uri("/typeahead/%C3%89L").to_request();
         println!("req: {:?}", req);
 
         let mut resp = test::call_service(&mut app, req).await;
@@ -242,19 +894,19 @@ mod tests {
         assert_eq!(resp.status(), StatusCode::OK);
         assert_eq!(
             resp.take_body().as_ref().unwrap(),
-            &Body::from_slice(b"[{\"name\":\"Abag\",\"times\":704},{\"name\":\"Aba\",\"times\":608},{\"name\":\"Aar\",\"times\":361},{\"name\":\"Abe\",\"times\":300},{\"name\":\"Aari\",\"times\":151}]")
+            &Body::from_slice("[{\"name\":\"Élodie\",\"times\":120}]".as_bytes())
         );
     }
 
     #[actix_rt::test]
-    async fn t_get_words_match_prefix_empty_prefix_with_last_slash() {
+    async fn t_get_words_match_prefix_fuzzy() {
         let trie = get_default_trie();
-        let shared_trie: Arc<Mutex<Trie>> = Arc::new(Mutex::new(trie));
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
         
-        let app = App::new().data(shared_trie).service(get_words_match_empty_prefix_with_last_slash);
+        let app = App::new().data(shared_trie).service(get_words_match_prefix);
         let mut app = test::init_service(app).await;
 
-        let req = test::TestRequest::get().uri("/typeahead/").to_request();
+        let req = test::TestRequest::get().uri("/typeahead/Aeb?fuzzy=1").to_request();
         println!("req: {:?}", req);
 
         let mut resp = test::call_service(&mut app, req).await;
@@ -263,69 +915,1154 @@ mod tests {
         assert_eq!(resp.status(), StatusCode::OK);
         assert_eq!(
             resp.take_body().as_ref().unwrap(),
-            &Body::from_slice(b"[{\"name\":\"Abag\",\"times\":704},{\"name\":\"Aba\",\"times\":608},{\"name\":\"Aar\",\"times\":361},{\"name\":\"Abe\",\"times\":300},{\"name\":\"Aari\",\"times\":151}]")
+            &Body::from_slice(b"[{\"name\":\"Abag\",\"times\":704},{\"name\":\"Aba\",\"times\":608},{\"name\":\"Abe\",\"times\":300},{\"name\":\"A-b\",\"times\":23}]")
         );
     }
 
     #[actix_rt::test]
-    async fn t_handler_increase_popularity_word_exists() {
+    async fn t_get_words_match_prefix_fuzzy_invalid_parameter() {
         let trie = get_default_trie();
-        let shared_trie: Arc<Mutex<Trie>> = Arc::new(Mutex::new(trie));
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
         
-        let app = App::new().data(shared_trie).service(increase_popularity);
+        let app = App::new().data(shared_trie).service(get_words_match_prefix);
         let mut app = test::init_service(app).await;
 
-        let data = Bytes::from("{\"name\": \"Aar\"}");
-        let req = test::TestRequest::post().uri("/typeahead").set_payload(data).to_request();
+        let req = test::TestRequest::get().uri("/typeahead/Aeb?fuzzy=a").to_request();
         println!("req: {:?}", req);
 
-        let mut resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&mut app, req).await;
         println!("response: {:?}", &resp);
 
-        assert_eq!(resp.status(), StatusCode::CREATED);
-        assert_eq!(
-            resp.take_body().as_ref().unwrap(),
-            &Body::from_slice(b"{\"name\":\"Aar\",\"times\":362}")
-        );
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
     }
 
     #[actix_rt::test]
-    async fn t_handler_increase_popularity_word_exists_case_sensitive() {
-        let trie = get_default_trie();
-        let shared_trie: Arc<Mutex<Trie>> = Arc::new(Mutex::new(trie));
-        
-        let app = App::new().data(shared_trie).service(increase_popularity);
+    async fn t_get_words_match_prefix_pages() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new()
+            .data(shared_trie)
+            .service(get_words_match_prefix)
+            .service(get_words_match_empty_prefix);
         let mut app = test::init_service(app).await;
 
-        let data = Bytes::from("{\"name\": \"AaR\"}");
-        let req = test::TestRequest::post().uri("/typeahead").set_payload(data).to_request();
-        println!("req: {:?}", req);
+        let req = test::TestRequest::get().uri("/typeahead/a?limit=4").to_request();
+        let page: serde_json::Value = test::read_body_json(test::call_service(&mut app, req).await).await;
 
-        let mut resp = test::call_service(&mut app, req).await;
-        println!("response: {:?}", &resp);
+        assert_eq!(
+            page["results"],
+            json!([
+                { "name": "Abag", "times": 704 },
+                { "name": "Aba", "times": 608 },
+                { "name": "Aar", "times": 361 },
+                { "name": "Abe", "times": 300 }
+            ])
+        );
+
+        let uri = format!("/typeahead/a?limit=4&cursor={}", page["next_cursor"].as_str().unwrap());
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let page: serde_json::Value = test::read_body_json(test::call_service(&mut app, req).await).await;
 
-        assert_eq!(resp.status(), StatusCode::CREATED);
         assert_eq!(
-            resp.take_body().as_ref().unwrap(),
-            &Body::from_slice(b"{\"name\":\"Aar\",\"times\":362}")
+            page,
+            json!({ "results": [{ "name": "Aari", "times": 151 }, { "name": "A-b", "times": 23 }], "next_cursor": null })
+        );
+
+        //past SUGGESTION_NUMBER, without a prefix
+        let req = test::TestRequest::get().uri("/typeahead?offset=5&limit=3").to_request();
+        let page: serde_json::Value = test::read_body_json(test::call_service(&mut app, req).await).await;
+
+        assert_eq!(
+            page["results"],
+            json!([{ "name": "Bc", "times": 50 }, { "name": "Be", "times": 50 }, { "name": "A-b", "times": 23 }])
         );
+        assert!(page["next_cursor"].is_string());
+
+        for (uri, code, reason) in [
+            ("/typeahead/a?cursor=zz", "invalid_cursor", None),
+            (
+                "/typeahead/a?fuzzy=1&limit=2",
+                "invalid_query",
+                Some("fuzzy matches can't be paged with limit, offset or cursor"),
+            ),
+            (
+                "/typeahead/a?fuzzy=1&ranking=alphabetical",
+                "invalid_query",
+                Some("fuzzy matches can only be ranked by popularity with exact_match_first"),
+            ),
+            ("/typeahead/a?limit=0", "invalid_query", Some("limit must be at least 1")),
+            ("/typeahead/a?decay_weight=101", "invalid_query", Some("decay_weight must be from 0 to 100")),
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&mut app, req).await;
+
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", uri);
+
+            let error: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(error["error"]["code"], code, "{}", uri);
+            if let Some(reason) = reason {
+                assert_eq!(error["error"]["details"]["reason"], reason, "{}", uri);
+            }
+        }
     }
 
     #[actix_rt::test]
-    async fn t_handler_increase_popularity_word_does_not_exists() {
-        let trie = get_default_trie();
-        let shared_trie: Arc<Mutex<Trie>> = Arc::new(Mutex::new(trie));
-        
-        let app = App::new().data(shared_trie).service(increase_popularity);
+    async fn t_get_words_match_prefix_ranking() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+        let ranking = Ranking {
+            strategy: RankingStrategy::ShortestFirst,
+            exact_match_first: true,
+            ..Ranking::default()
+        };
+
+        let app = App::new()
+            .data(shared_trie)
+            .app_data(web::Data::new(ranking))
+            .service(get_words_match_prefix);
         let mut app = test::init_service(app).await;
 
-        let data = Bytes::from("{\"name\": \"Abcd\"}");
-        let req = test::TestRequest::post().uri("/typeahead").set_payload(data).to_request();
-        println!("req: {:?}", req);
+        let cases = [
+            ("/typeahead/ab", json!(["Aba", "Abe", "Abag"])),
+            ("/typeahead/ab?ranking=alphabetical", json!(["Aba", "Abag", "Abe"])),
+            ("/typeahead/ab?ranking=popularity", json!(["Abag", "Aba", "Abe"])),
+            ("/typeahead/aba?ranking=popularity&exact_match_first=false", json!(["Abag", "Aba"])),
+            ("/typeahead/b?ranking=alphabetical&limit=2", json!({ "results": ["Ba", "Bah"] })),
+        ];
 
-        let resp = test::call_service(&mut app, req).await;
-        println!("response: {:?}", &resp);
+        for (uri, expected_names) in cases {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let result: serde_json::Value = test::read_body_json(test::call_service(&mut app, req).await).await;
 
-        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            let names = |words: &serde_json::Value| -> serde_json::Value {
+                words.as_array().unwrap().iter().map(|word_data| word_data["name"].clone()).collect()
+            };
+            match expected_names.get("results") {
+                Some(expected_names) => assert_eq!(&names(&result["results"]), expected_names, "{}", uri),
+                None => assert_eq!(names(&result), expected_names, "{}", uri),
+            }
+        }
+
+        for uri in ["/typeahead/ab?ranking=random", "/typeahead/ab?ranking=alphabetical&fuzzy=1"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&mut app, req).await;
+
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn t_get_words_match_prefix_decayed_ranking() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+        shared_trie.write().unwrap().increase_popularity("Abe".to_string()).unwrap();
+
+        let app = App::new().data(shared_trie).service(get_words_match_prefix);
+        let mut app = test::init_service(app).await;
+
+        //"Abe" is the only name with a recent hit
+        let req = test::TestRequest::get().uri("/typeahead/ab?ranking=decayed").to_request();
+        let result: serde_json::Value = test::read_body_json(test::call_service(&mut app, req).await).await;
+        assert_eq!("Abe", result[0]["name"]);
+
+        let req = test::TestRequest::get().uri("/typeahead/ab?ranking=blended&decay_weight=100").to_request();
+        let result: serde_json::Value = test::read_body_json(test::call_service(&mut app, req).await).await;
+        assert_eq!("Abe", result[0]["name"]);
+
+        let req = test::TestRequest::get().uri("/typeahead/ab?ranking=blended&decay_weight=101").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn t_get_words_match_prefix_max_result_limit() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new()
+            .data(shared_trie)
+            .app_data(web::Data::new(MaxResultLimit(2)))
+            .service(get_words_match_prefix);
+        let mut app = test::init_service(app).await;
+
+        let req = test::TestRequest::get().uri("/typeahead/a?limit=50").to_request();
+        let page: serde_json::Value = test::read_body_json(test::call_service(&mut app, req).await).await;
+
+        assert_eq!(
+            page["results"],
+            json!([{ "name": "Abag", "times": 704 }, { "name": "Aba", "times": 608 }])
+        );
+    }
+
+    #[actix_rt::test]
+    async fn t_get_words_match_prefix_empty_prefix() {
+        let trie = get_default_trie();
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
+        
+        let app = App::new().data(shared_trie).service(get_words_match_empty_prefix);
+        let mut app = test::init_service(app).await;
+
+        let req = test::TestRequest::get().uri("/typeahead").to_request();
+        println!("req: {:?}", req);
+
+        let mut resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.take_body().as_ref().unwrap(),
+            &Body::from_slice(b"[{\"name\":\"Abag\",\"times\":704},{\"name\":\"Aba\",\"times\":608},{\"name\":\"Aar\",\"times\":361},{\"name\":\"Abe\",\"times\":300},{\"name\":\"Aari\",\"times\":151}]")
+        );
+    }
+
+    #[actix_rt::test]
+    async fn t_get_words_match_prefix_empty_prefix_with_last_slash() {
+        let trie = get_default_trie();
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
+        
+        let app = App::new().data(shared_trie).service(get_words_match_empty_prefix_with_last_slash);
+        let mut app = test::init_service(app).await;
+
+        let req = test::TestRequest::get().uri("/typeahead/").to_request();
+        println!("req: {:?}", req);
+
+        let mut resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.take_body().as_ref().unwrap(),
+            &Body::from_slice(b"[{\"name\":\"Abag\",\"times\":704},{\"name\":\"Aba\",\"times\":608},{\"name\":\"Aar\",\"times\":361},{\"name\":\"Abe\",\"times\":300},{\"name\":\"Aari\",\"times\":151}]")
+        );
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_increase_popularity_word_exists() {
+        let trie = get_default_trie();
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
+        
+        let app = App::new().data(shared_trie).service(increase_popularity);
+        let mut app = test::init_service(app).await;
+
+        let data = Bytes::from("{\"name\": \"Aar\"}");
+        let req = test::TestRequest::post().uri("/typeahead").set_payload(data).to_request();
+        println!("req: {:?}", req);
+
+        let mut resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(
+            resp.take_body().as_ref().unwrap(),
+            &Body::from_slice(b"{\"name\":\"Aar\",\"times\":362}")
+        );
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_increase_popularity_word_exists_case_sensitive() {
+        let trie = get_default_trie();
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
+        
+        let app = App::new().data(shared_trie).service(increase_popularity);
+        let mut app = test::init_service(app).await;
+
+        let data = Bytes::from("{\"name\": \"AaR\"}");
+        let req = test::TestRequest::post().uri("/typeahead").set_payload(data).to_request();
+        println!("req: {:?}", req);
+
+        let mut resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(
+            resp.take_body().as_ref().unwrap(),
+            &Body::from_slice(b"{\"name\":\"Aar\",\"times\":362}")
+        );
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_increase_popularity_word_does_not_exists() {
+        let trie = get_default_trie();
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
+        
+        let app = App::new().data(shared_trie).service(increase_popularity);
+        let mut app = test::init_service(app).await;
+
+        let data = Bytes::from("{\"name\": \"Abcd\"}");
+        let req = test::TestRequest::post().uri("/typeahead").set_payload(data).to_request();
+        println!("req: {:?}", req);
+
+        let resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let error: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(
+            error,
+            json!({ "error": { "code": "word_does_not_exist", "message": "Word does not exist.", "details": { "name": "Abcd" } } })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_increase_popularity_invalid_payloads() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie).service(increase_popularity);
+        let mut app = test::init_service(app).await;
+
+        let cases: [(&[u8], serde_json::Value); 4] = [
+            (
+                b"{\"name\": \"Ab\xff\"}",
+                json!({ "code": "invalid_payload_encoding", "message": "Payload is not valid UTF-8.", "details": { "valid_up_to": 12 } }),
+            ),
+            (
+                b"{\"name\": \"Aba\"",
+                json!({ "code": "malformed_payload", "message": "Payload is not valid JSON: EOF while parsing an object", "details": { "line": 1, "column": 14 } }),
+            ),
+            (
+                b"{\n  \"names\": \"Aba\"\n}",
+                json!({ "code": "invalid_payload", "message": "Payload does not have the expected fields: missing field `name`", "details": { "line": 3, "column": 1 } }),
+            ),
+            (
+                b"{\"name\": 3}",
+                json!({ "code": "invalid_payload", "message": "Payload does not have the expected fields: invalid type: integer `3`, expected a string", "details": { "line": 1, "column": 10 } }),
+            ),
+        ];
+
+        for (payload, expected_error) in cases {
+            let req = test::TestRequest::post().uri("/typeahead").set_payload(payload).to_request();
+            let resp = test::call_service(&mut app, req).await;
+
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+            let error: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(error, json!({ "error": expected_error }));
+        }
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_increase_popularity_overflow() {
+        let trie = Trie::initialize("{\"Aar\": 18446744073709551615}", 5).unwrap();
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
+        
+        let app = App::new().data(shared_trie).service(increase_popularity);
+        let mut app = test::init_service(app).await;
+
+        let data = Bytes::from("{\"name\": \"Aar\"}");
+        let req = test::TestRequest::post().uri("/typeahead").set_payload(data).to_request();
+        println!("req: {:?}", req);
+
+        let resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+    }
+
+    const ADMIN_TOKEN: &str = "Bearer s3cr3t";
+
+    fn admin_token() -> web::Data<AdminToken> {
+        web::Data::new(AdminToken("s3cr3t".to_string()))
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_insert_name() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie.clone()).app_data(admin_token()).service(insert_name);
+        let mut app = test::init_service(app).await;
+
+        let data = Bytes::from("{\"name\": \"Abel\", \"times\": 400}");
+        let req = test::TestRequest::post().uri("/admin/names").header("Authorization", ADMIN_TOKEN).set_payload(data).to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(
+            resp.take_body().as_ref().unwrap(),
+            &Body::from_slice(b"{\"name\":\"Abel\",\"times\":400}")
+        );
+        assert_eq!(
+            WordData::new("Abel".to_string(), 400),
+            shared_trie.read().unwrap().get_typeahead_words("abel".to_string()).unwrap()[0]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_insert_name_already_exists() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie).app_data(admin_token()).service(insert_name);
+        let mut app = test::init_service(app).await;
+
+        let data = Bytes::from("{\"name\": \"ABE\", \"times\": 1}");
+        let req = test::TestRequest::post().uri("/admin/names").header("Authorization", ADMIN_TOKEN).set_payload(data).to_request();
+
+        let resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_insert_name_invalid_payload() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie).app_data(admin_token()).service(insert_name);
+        let mut app = test::init_service(app).await;
+
+        for payload in ["{\"name\": \"  \", \"times\": 1}", "{\"times\": 1}", "{\"name\": \"Abel\", \"times\": -1}"] {
+            let req = test::TestRequest::post().uri("/admin/names").header("Authorization", ADMIN_TOKEN).set_payload(Bytes::from(payload)).to_request();
+
+            let resp = test::call_service(&mut app, req).await;
+            println!("response: {:?}", &resp);
+
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_admin_unauthorized() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie.clone()).app_data(admin_token()).service(insert_name).service(remove_name).service(rename_name);
+        let mut app = test::init_service(app).await;
+
+        for authorization in [None, Some("s3cr3t"), Some("Bearer s3cr3"), Some("Bearer s3cr3t4")] {
+            let requests = vec![
+                test::TestRequest::post().uri("/admin/names").set_payload(Bytes::from("{\"name\": \"Abel\"}")),
+                test::TestRequest::delete().uri("/admin/names/Abe"),
+                test::TestRequest::patch().uri("/admin/names/Abe").set_payload(Bytes::from("{\"name\": \"Abel\"}")),
+            ];
+
+            for req in requests {
+                let req = match authorization {
+                    Some(authorization) => req.header("Authorization", authorization),
+                    None => req,
+                };
+
+                let resp = test::call_service(&mut app, req.to_request()).await;
+                println!("response: {:?}", &resp);
+
+                assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+            }
+        }
+
+        assert!(shared_trie.read().unwrap().find_word("Abe").is_some());
+        assert!(shared_trie.read().unwrap().find_word("Abel").is_none());
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_admin_without_configured_token() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie).service(remove_name);
+        let mut app = test::init_service(app).await;
+
+        let req = test::TestRequest::delete().uri("/admin/names/Abe").header("Authorization", "Bearer ").to_request();
+
+        let resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_remove_name() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie.clone()).app_data(admin_token()).service(remove_name);
+        let mut app = test::init_service(app).await;
+
+        let req = test::TestRequest::delete().uri("/admin/names/abag").header("Authorization", ADMIN_TOKEN).to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.take_body().as_ref().unwrap(),
+            &Body::from_slice(b"{\"name\":\"Abag\",\"times\":704}")
+        );
+        assert!(shared_trie.read().unwrap().find_word("Abag").is_none());
+
+        let req = test::TestRequest::delete().uri("/admin/names/abag").header("Authorization", ADMIN_TOKEN).to_request();
+
+        let resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_rename_name() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie.clone()).app_data(admin_token()).service(rename_name);
+        let mut app = test::init_service(app).await;
+
+        let data = Bytes::from("{\"name\": \"A-B\"}");
+        let req = test::TestRequest::patch().uri("/admin/names/a-b").header("Authorization", ADMIN_TOKEN).set_payload(data).to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.take_body().as_ref().unwrap(),
+            &Body::from_slice(b"{\"name\":\"A-B\",\"times\":23}")
+        );
+        assert_eq!(Some(&WordData::new("A-B".to_string(), 23)), shared_trie.read().unwrap().find_word("a-b"));
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_rename_name_errors() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie).app_data(admin_token()).service(rename_name);
+        let mut app = test::init_service(app).await;
+
+        let cases = [
+            ("/admin/names/Aba", "{\"name\": \"abag\"}", StatusCode::CONFLICT),
+            ("/admin/names/Abc", "{\"name\": \"Abcd\"}", StatusCode::BAD_REQUEST),
+            ("/admin/names/Aba", "{\"name\": \"\"}", StatusCode::BAD_REQUEST),
+        ];
+
+        for (uri, payload, status) in cases {
+            let req = test::TestRequest::patch().uri(uri).header("Authorization", ADMIN_TOKEN).set_payload(Bytes::from(payload)).to_request();
+
+            let resp = test::call_service(&mut app, req).await;
+            println!("response: {:?}", &resp);
+
+            assert_eq!(resp.status(), status);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_admin_appends_to_journal() {
+        let path = temp_file_path("handlers-admin-journal");
+        let (journal, _) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie).app_data(web::Data::new(journal)).app_data(admin_token()).service(insert_name).service(remove_name).service(rename_name);
+        let mut app = test::init_service(app).await;
+
+        let requests = vec![
+            test::TestRequest::post().uri("/admin/names").set_payload(Bytes::from("{\"name\": \"Abel\", \"times\": 7}")),
+            test::TestRequest::patch().uri("/admin/names/abel").set_payload(Bytes::from("{\"name\": \"Abelard\"}")),
+            test::TestRequest::delete().uri("/admin/names/BE"),
+        ];
+
+        for req in requests {
+            let resp = test::call_service(&mut app, req.header("Authorization", ADMIN_TOKEN).to_request()).await;
+            println!("response: {:?}", &resp);
+
+            assert!(resp.status().is_success());
+        }
+
+        let (_journal, records) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+        let expected_records = vec![
+            JournalRecord::Insert { name: "Abel".to_string(), times: 7 },
+            JournalRecord::Rename { name: "Abel".to_string(), new_name: "Abelard".to_string() },
+            JournalRecord::Remove { name: "Be".to_string() },
+        ];
+
+        assert_eq!(expected_records, records);
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_admin_journal_failure() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+        let words: Vec<WordData> = get_default_trie().all_words().into_iter().cloned().collect();
+
+        let app = App::new()
+            .data(shared_trie.clone())
+            .app_data(web::Data::new(crate::journal::tests::failing_journal()))
+            .app_data(admin_token())
+            .service(insert_name)
+            .service(remove_name)
+            .service(rename_name)
+            .service(put_name);
+        let mut app = test::init_service(app).await;
+
+        let requests = vec![
+            test::TestRequest::post().uri("/admin/names").set_payload(Bytes::from("{\"name\": \"Abel\", \"times\": 7}")),
+            test::TestRequest::patch().uri("/admin/names/aba").set_payload(Bytes::from("{\"name\": \"Abelard\"}")),
+            test::TestRequest::delete().uri("/admin/names/BE"),
+            test::TestRequest::put().uri("/v1/names/Be").header("Content-Type", "application/json").set_payload(Bytes::from("{\"times\": 1}")),
+        ];
+
+        //the changes that can't be journaled aren't applied either
+        for req in requests {
+            let resp = test::call_service(&mut app, req.header("Authorization", ADMIN_TOKEN).to_request()).await;
+
+            assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        }
+
+        let trie = shared_trie.read().unwrap();
+        assert_eq!(words, trie.all_words().into_iter().cloned().collect::<Vec<WordData>>());
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_get_name() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie).service(get_name);
+        let mut app = test::init_service(app).await;
+
+        let req = test::TestRequest::get().uri("/v1/names/aBA").to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.take_body().as_ref().unwrap(),
+            &Body::from_slice(b"{\"name\":\"Aba\",\"times\":608}")
+        );
+
+        let req = test::TestRequest::get().uri("/v1/names/Ab").to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let error: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(
+            error,
+            json!({ "error": { "code": "name_not_found", "message": "Name not found.", "details": { "name": "Ab" } } })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_names_accent_insensitive() {
+        let options = crate::trie::TrieOptions {
+            accent_insensitive: true,
+            ..5.into()
+        };
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(
+            Trie::initialize("{\"José\": 10, \"Zoe\": 9, \"Zoë\": 5}", options).unwrap(),
+        ));
+
+        let app = App::new().data(shared_trie).service(get_name).service(hit_name_resource);
+        let mut app = test::init_service(app).await;
+
+        //lookups and hits find the same name
+        let cases = [
+            (test::TestRequest::get().uri("/v1/names/jose"), "{\"name\":\"José\",\"times\":10}"),
+            (test::TestRequest::post().uri("/v1/names/jose/hits"), "{\"name\":\"José\",\"times\":11}"),
+            (test::TestRequest::get().uri("/v1/names/JOS%C3%89"), "{\"name\":\"José\",\"times\":11}"),
+            (test::TestRequest::get().uri("/v1/names/zo%C3%AB"), "{\"name\":\"Zoë\",\"times\":5}"),
+            (test::TestRequest::post().uri("/v1/names/ZOE/hits"), "{\"name\":\"Zoe\",\"times\":10}"),
+        ];
+
+        for (req, body) in cases {
+            let resp = test::call_service(&mut app, req.to_request()).await;
+
+            assert_eq!(resp.status(), StatusCode::OK, "{}", body);
+            assert_eq!(test::read_body(resp).await, Bytes::from(body), "{}", body);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_hit_name_resource() {
+        let path = temp_file_path("handlers-hit-name");
+        let (journal, _) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new()
+            .data(shared_trie)
+            .app_data(web::Data::new(journal))
+            .service(hit_name_resource)
+            .service(increase_popularity);
+        let mut app = test::init_service(app).await;
+
+        let req = test::TestRequest::post().uri("/v1/names/BE/hits").to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.take_body().as_ref().unwrap(),
+            &Body::from_slice(b"{\"name\":\"Be\",\"times\":51}")
+        );
+
+        let req = test::TestRequest::post().uri("/v1/names/Abcd/hits").to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        //the older route keeps its status code
+        let req = test::TestRequest::post().uri("/typeahead").set_payload("{\"name\": \"Abcd\"}").to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let (_journal, records) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+        assert!(matches!(&records[..], [JournalRecord::Hit { name, at: Some(_) }] if name == "Be"));
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_hit_name_journal_failure() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new()
+            .data(shared_trie.clone())
+            .app_data(web::Data::new(crate::journal::tests::failing_journal()))
+            .service(hit_name_resource);
+        let mut app = test::init_service(app).await;
+
+        let req = test::TestRequest::post().uri("/v1/names/Be/hits").to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        //a hit that can't be journaled isn't applied either
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(50, shared_trie.read().unwrap().find_word("Be").unwrap().popularity);
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_put_name() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie.clone()).app_data(admin_token()).service(put_name);
+        let mut app = test::init_service(app).await;
+
+        let cases = [
+            ("/v1/names/Abel", "{\"times\": 400}", StatusCode::CREATED),
+            ("/v1/names/Abel", "{\"times\": 3}", StatusCode::OK),
+            //another casing of an existing name
+            ("/v1/names/aba", "{\"times\": 3}", StatusCode::CONFLICT),
+            ("/v1/names/Aba", "{\"name\": \"Aba\"}", StatusCode::BAD_REQUEST),
+            ("/v1/names/%20", "{\"times\": 3}", StatusCode::BAD_REQUEST),
+        ];
+
+        for (uri, payload, status) in cases {
+            let req = test::TestRequest::put()
+                .uri(uri)
+                .header("Authorization", ADMIN_TOKEN)
+                .header("Content-Type", "application/json")
+                .set_payload(payload)
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+
+            assert_eq!(resp.status(), status, "{} {}", uri, payload);
+        }
+
+        assert_eq!(Some(&WordData::new("Abel".to_string(), 3)), shared_trie.read().unwrap().find_word("abel"));
+        assert_eq!(Some(&WordData::new("Aba".to_string(), 608)), shared_trie.read().unwrap().find_word("Aba"));
+
+        let req = test::TestRequest::put()
+            .uri("/v1/names/Abel")
+            .header("Authorization", ADMIN_TOKEN)
+            .header("Content-Type", "text/plain")
+            .set_payload("{\"times\": 5}")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let req = test::TestRequest::put()
+            .uri("/v1/names/Abel")
+            .header("Content-Type", "application/json")
+            .set_payload("{\"times\": 5}")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_import_names_csv() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie.clone()).app_data(admin_token()).service(import_names);
+        let mut app = test::init_service(app).await;
+
+        let data = Bytes::from("name,times\nAAR,9\nAbel,5\nAbelard,x\nBa,1\n");
+        let req = test::TestRequest::post().uri("/admin/import?format=csv&policy=add").header("Authorization", ADMIN_TOKEN).set_payload(data).to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.take_body().as_ref().unwrap(),
+            &Body::from_slice(b"{\"inserted\":1,\"updated\":2,\"skipped\":0,\"rejected\":1,\"rejections\":[{\"line\":4,\"name\":\"Abelard\",\"reason\":\"Popularity x of \\\"Abelard\\\" is not a whole number from 0 to 18446744073709551615\"}]}")
+        );
+
+        let trie = shared_trie.read().unwrap();
+        assert_eq!(Some(&WordData::new("Aar".to_string(), 370)), trie.find_word("Aar"));
+        assert_eq!(Some(&WordData::new("Abel".to_string(), 5)), trie.find_word("Abel"));
+        assert_eq!(Some(&WordData::new("Ba".to_string(), 6)), trie.find_word("Ba"));
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_import_names_format_from_content_type() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie.clone()).app_data(admin_token()).service(import_names);
+        let mut app = test::init_service(app).await;
+
+        let cases = [
+            ("application/json", "{\"Aar\": 1, \"Abel\": 5}", "{\"inserted\":1,\"updated\":0,\"skipped\":1,\"rejected\":0,\"rejections\":[]}"),
+            ("application/x-ndjson", "{\"name\": \"Aar\", \"times\": 2}\n{\"name\": \"Abelard\", \"times\": 3}", "{\"inserted\":1,\"updated\":0,\"skipped\":1,\"rejected\":0,\"rejections\":[]}"),
+            ("text/csv; charset=utf-8", "Aar,3\nAbelino,4", "{\"inserted\":1,\"updated\":0,\"skipped\":1,\"rejected\":0,\"rejections\":[]}"),
+        ];
+
+        for (content_type, payload, summary) in cases {
+            let req = test::TestRequest::post().uri("/admin/import").header("Authorization", ADMIN_TOKEN).header("Content-Type", content_type).set_payload(Bytes::from(payload)).to_request();
+
+            let mut resp = test::call_service(&mut app, req).await;
+            println!("response: {:?}", &resp);
+
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.take_body().as_ref().unwrap(), &Body::from_slice(summary.as_bytes()));
+        }
+
+        //"keep" by default
+        assert_eq!(Some(&WordData::new("Aar".to_string(), 361)), shared_trie.read().unwrap().find_word("Aar"));
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_import_names_errors() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie).app_data(admin_token()).app_data(query_config()).service(import_names);
+        let mut app = test::init_service(app).await;
+
+        let formats = "expected one of `json`, `csv`, `tsv`, `ndjson`, `lines`";
+        let cases = [
+            (
+                "/admin/import",
+                Some(ADMIN_TOKEN),
+                "[\"Aar\"]",
+                StatusCode::BAD_REQUEST,
+                json!({ "code": "import_interrupted", "message": "Import stopped before the end of the body: Payload does not have the expected fields: invalid type: sequence, expected an object with names and popularities", "details": {
+                    "cause": { "code": "invalid_payload", "message": "Payload does not have the expected fields: invalid type: sequence, expected an object with names and popularities", "details": {
+                        "line": 1, "column": 0
+                    } },
+                    "summary": { "inserted": 0, "updated": 0, "skipped": 0, "rejected": 0, "rejections": [] }
+                } }),
+            ),
+            (
+                "/admin/import?policy=replace",
+                Some(ADMIN_TOKEN),
+                "{}",
+                StatusCode::BAD_REQUEST,
+                json!({ "code": "invalid_query", "message": "Query string is invalid: unknown variant `replace`, expected one of `overwrite`, `add`, `keep`", "details": {
+                    "reason": "unknown variant `replace`, expected one of `overwrite`, `add`, `keep`"
+                } }),
+            ),
+            (
+                "/admin/import?format=xml",
+                Some(ADMIN_TOKEN),
+                "{}",
+                StatusCode::BAD_REQUEST,
+                json!({ "code": "invalid_query", "message": format!("Query string is invalid: unknown variant `xml`, {}", formats), "details": {
+                    "reason": format!("unknown variant `xml`, {}", formats)
+                } }),
+            ),
+            (
+                "/admin/import",
+                None,
+                "{}",
+                StatusCode::UNAUTHORIZED,
+                json!({ "code": "unauthorized", "message": "Missing or invalid admin token." }),
+            ),
+        ];
+
+        for (uri, authorization, payload, status, error) in cases {
+            let req = test::TestRequest::post().uri(uri).set_payload(Bytes::from(payload));
+            let req = match authorization {
+                Some(authorization) => req.header("Authorization", authorization),
+                None => req,
+            };
+
+            let resp = test::call_service(&mut app, req.to_request()).await;
+
+            assert_eq!(resp.status(), status, "{}", uri);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(json!({ "error": error }), body, "{}", uri);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_import_names_appends_to_journal() {
+        let path = temp_file_path("handlers-import-journal");
+        let (journal, _) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie).app_data(web::Data::new(journal)).app_data(admin_token()).service(import_names);
+        let mut app = test::init_service(app).await;
+
+        let data = Bytes::from("{\"name\": \"aar\", \"times\": 1}\n{\"name\": \"Abel\", \"times\": 5}\n{\"name\": \"\", \"times\": 5}\n");
+        let req = test::TestRequest::post().uri("/admin/import?format=ndjson&policy=overwrite").header("Authorization", ADMIN_TOKEN).set_payload(data).to_request();
+
+        let resp = test::call_service(&mut app, req).await;
+        println!("response: {:?}", &resp);
+
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let (_journal, records) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+        let expected_records = vec![
+            JournalRecord::Insert { name: "Aar".to_string(), times: 1 },
+            JournalRecord::Insert { name: "Abel".to_string(), times: 5 },
+        ];
+
+        assert_eq!(expected_records, records);
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_import_names_journal_failure() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new()
+            .data(shared_trie.clone())
+            .app_data(web::Data::new(crate::journal::tests::failing_journal()))
+            .app_data(admin_token())
+            .service(import_names);
+        let mut app = test::init_service(app).await;
+
+        let data = Bytes::from("{\"name\": \"\", \"times\": 5}\n{\"name\": \"Abel\", \"times\": 5}\n{\"name\": \"Ada\", \"times\": 1}\n");
+        let req = test::TestRequest::post().uri("/admin/import?format=ndjson").header("Authorization", ADMIN_TOKEN).set_payload(data).to_request();
+
+        let resp = test::call_service(&mut app, req).await;
+
+        //the import stops at the first entry that can't be journaled, returning what was done until then
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let error: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(
+            error,
+            json!({ "error": {
+                "code": "import_interrupted",
+                "message": "Import stopped before the end of the body: Unexpected internal error.",
+                "details": {
+                    "cause": { "code": "unexpected_error", "message": "Unexpected internal error." },
+                    "summary": { "inserted": 0, "updated": 0, "skipped": 0, "rejected": 1, "rejections": [{ "line": 1, "name": "", "reason": "Empty name" }] }
+                }
+            } })
+        );
+        assert_eq!(None, shared_trie.read().unwrap().find_word("Abel"));
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_export_names() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+        shared_trie.write().unwrap().increase_popularity("Aari".to_string()).unwrap();
+
+        let app = App::new().data(shared_trie).service(export_names);
+        let mut app = test::init_service(app).await;
+
+        let req = test::TestRequest::get().uri("/export?prefix=aa").to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("Content-Type").unwrap(), "application/json");
+        assert!(resp.headers().get("X-Total-Count").is_none());
+        assert!(resp.headers().get("X-Next-Offset").is_none());
+        assert_eq!(test::read_body(resp).await, Bytes::from_static(b"{\"Aar\":361,\"Aari\":152}"));
+
+        let req = test::TestRequest::get().uri("/export?format=csv&min_popularity=50").to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(
+            test::read_body(resp).await,
+            Bytes::from_static(b"name,times\nAar,361\nAari,152\nAba,608\nAbag,704\nAbe,300\nBc,50\nBe,50\n")
+        );
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_export_names_pages() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+
+        let app = App::new().data(shared_trie).service(export_names);
+        let mut app = test::init_service(app).await;
+
+        let req = test::TestRequest::get().uri("/export?format=ndjson&prefix=a&offset=1&limit=2").to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(resp.headers().get("Content-Type").unwrap(), "application/x-ndjson");
+        assert_eq!(resp.headers().get("X-Total-Count").unwrap(), "6");
+        assert_eq!(resp.headers().get("X-Next-Offset").unwrap(), "3");
+        assert_eq!(
+            test::read_body(resp).await,
+            Bytes::from_static(b"{\"name\":\"Aar\",\"times\":361}\n{\"name\":\"Aari\",\"times\":151}\n")
+        );
+
+        //pages hold at most EXPORT_PAGE_SIZE names
+        let app = App::new()
+            .data(Arc::new(RwLock::new(get_default_trie())))
+            .app_data(web::Data::new(ExportPageSize(3)))
+            .app_data(query_config())
+            .service(export_names);
+        let mut app = test::init_service(app).await;
+
+        let req = test::TestRequest::get().uri("/export?format=lines&limit=4").to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(resp.headers().get("X-Total-Count").unwrap(), "10");
+        assert_eq!(resp.headers().get("X-Next-Offset").unwrap(), "3");
+        assert_eq!(test::read_body(resp).await, Bytes::from_static(b"A-b\nAar\nAari\n"));
+
+        //without a limit, all of the names from the offset are streamed, EXPORT_PAGE_SIZE at a time
+        let req = test::TestRequest::get().uri("/export?format=lines&offset=2").to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        assert!(resp.headers().get("X-Total-Count").is_none());
+        assert!(resp.headers().get("X-Next-Offset").is_none());
+        assert_eq!(
+            test::read_body(resp).await,
+            Bytes::from_static(b"Aari\nAba\nAbag\nAbe\nBa\nBah\nBc\nBe\n")
+        );
+
+        let cases = [
+            (
+                "/export?limit=0",
+                json!({ "code": "invalid_query", "message": "Query string is invalid: limit must be at least 1", "details": {
+                    "reason": "limit must be at least 1"
+                } }),
+            ),
+            (
+                "/export?format=xml",
+                json!({ "code": "invalid_query", "message": "Query string is invalid: unknown variant `xml`, expected one of `json`, `csv`, `tsv`, `ndjson`, `lines`", "details": {
+                    "reason": "unknown variant `xml`, expected one of `json`, `csv`, `tsv`, `ndjson`, `lines`"
+                } }),
+            ),
+            (
+                "/export?min_popularity=-1",
+                json!({ "code": "invalid_query", "message": "Query string is invalid: invalid digit found in string", "details": {
+                    "reason": "invalid digit found in string"
+                } }),
+            ),
+        ];
+
+        for (uri, error) in cases {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&mut app, req).await;
+
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(json!({ "error": error }), body, "{}", uri);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_export_names_loads_back() {
+        let file_content = std::fs::read_to_string("names.json").unwrap();
+        let trie = Trie::initialize(&file_content, 10).unwrap();
+        let words: Vec<WordData> = trie.all_words().into_iter().cloned().collect();
+
+        let app = App::new()
+            .data(Arc::new(RwLock::new(trie)))
+            .app_data(web::Data::new(ExportPageSize(100)))
+            .service(export_names);
+        let mut app = test::init_service(app).await;
+
+        let req = test::TestRequest::get().uri("/export").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let body = test::read_body(resp).await;
+
+        let loaded_trie = Trie::initialize(std::str::from_utf8(&body).unwrap(), 10).unwrap();
+        let loaded_words: Vec<WordData> = loaded_trie.all_words().into_iter().cloned().collect();
+        assert_eq!(words, loaded_words);
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_get_stats() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+        let stats_cache = web::Data::new(StatsCache::new(std::time::Duration::from_secs(60)));
+
+        let app = App::new().data(shared_trie).app_data(stats_cache).service(get_stats);
+        let mut app = test::init_service(app).await;
+
+        let req = test::TestRequest::get().uri("/stats").to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let stats: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(stats["word_count"], 10);
+        assert_eq!(stats["max_depth"], 4);
+        assert_eq!(stats["total_popularity"], 2257);
+        assert_eq!(stats["max_popularity"], 704);
+        assert_eq!(stats["popularity_percentiles"]["p50"], 50);
+        assert_eq!(stats["source"], serde_json::Value::Null);
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_get_metrics() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+        let metrics = web::Data::new(Metrics::new().unwrap());
+        let stats_cache = web::Data::new(StatsCache::new(std::time::Duration::from_secs(60)));
+
+        let app = App::new()
+            .data(shared_trie)
+            .app_data(metrics)
+            .app_data(stats_cache)
+            .service(get_words_match_prefix)
+            .service(increase_popularity)
+            .service(get_metrics);
+        let mut app = test::init_service(app).await;
+
+        for uri in ["/typeahead/a", "/typeahead/zz"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            test::call_service(&mut app, req).await;
+        }
+
+        let req = test::TestRequest::post()
+            .uri("/typeahead")
+            .set_payload("{\"name\": \"Aba\"}")
+            .to_request();
+        test::call_service(&mut app, req).await;
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("Content-Type").unwrap(), prometheus::TEXT_FORMAT);
+
+        let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        assert!(body.contains("typeahead_result_size_count 2\n"));
+        assert!(body.contains("typeahead_result_size_sum 5\n"));
+        assert!(body.contains("typeahead_empty_results_total 1\n"));
+        assert!(body.contains("typeahead_words 10\n"));
+        assert!(body.contains("typeahead_lock_wait_seconds_count{mode=\"read\"} 2\n"));
+        assert!(body.contains("typeahead_lock_wait_seconds_count{mode=\"write\"} 1\n"));
+    }
+
+    #[actix_rt::test]
+    async fn t_handler_invalid_query_and_path() {
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(get_default_trie()));
+        let metrics = Metrics::new().unwrap();
+
+        //the name routes take any text, so a route with a number in the path stands in for a path that doesn't deserialize
+        let app = App::new()
+            .data(shared_trie)
+            .app_data(query_config())
+            .app_data(path_config())
+            .service(get_words_match_prefix)
+            .route("/numbers/{number}", web::get().to(|number: web::Path<u8>| async move { number.to_string() }));
+        let mut app = test::init_service(app).await;
+
+        let rankings = "expected one of `popularity`, `alphabetical`, `shortest_first`, `log_popularity`, `decayed`, `blended`";
+        let cases = [
+            ("/typeahead/a?limit=abc", "invalid_query", "Query string is invalid: ", "invalid digit found in string".to_string()),
+            ("/typeahead/a?ranking=bogus", "invalid_query", "Query string is invalid: ", format!("unknown variant `bogus`, {}", rankings)),
+            ("/numbers/300", "invalid_path", "Path is invalid: ", "can not parse \"300\" to a u8".to_string()),
+        ];
+
+        for (uri, code, message, reason) in cases {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&mut app, req).await;
+            metrics.observe_response(&resp, std::time::Duration::from_millis(1));
+
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(
+                json!({ "error": { "code": code, "message": format!("{}{}", message, reason), "details": { "reason": reason } } }),
+                body,
+                "{}",
+                uri
+            );
+        }
+
+        let encoded = metrics.encode().unwrap();
+        assert!(encoded.contains("typeahead_errors_total{error=\"invalid_query\"} 2\n"));
+        assert!(encoded.contains("typeahead_errors_total{error=\"invalid_path\"} 1\n"));
     }
 }
@@ -0,0 +1,564 @@
+use crate::app_error::AppError;
+use crate::journal::JournalRecord;
+use crate::source::{self, split_csv_line, LineParser, SourceEntry, SourceFormat, SourceOptions};
+use crate::trie::{ITrie, Popularity, Trie};
+use crate::validation::{Issue, Problem};
+use serde::{Deserialize, Serialize};
+
+//only the first rejections are listed in the summary, the count includes all of them.
+const MAX_LISTED_REJECTIONS: usize = 100;
+//a JSON body is only read once it's complete, so it's kept whole in memory up to this size.
+pub const MAX_JSON_BODY_SIZE: usize = 32 * 1024 * 1024;
+//line based bodies are read line by line, a line is kept in memory until its end arrives up to this size.
+pub const MAX_LINE_SIZE: usize = 64 * 1024;
+
+//what happens to a name that already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    Overwrite,
+    Add,
+    Keep,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportEntry {
+    pub name: String,
+    pub times: Popularity,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Rejection {
+    //line of the body, for the line based formats.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ImportSummary {
+    pub inserted: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub rejected: usize,
+    pub rejections: Vec<Rejection>,
+}
+
+impl ImportSummary {
+    fn reject(&mut self, rejection: Rejection) {
+        self.rejected += 1;
+
+        if self.rejections.len() < MAX_LISTED_REJECTIONS {
+            self.rejections.push(rejection);
+        }
+    }
+}
+
+// Turns the body into entries as its chunks arrive, reading it like a source file with the default columns. Line based
+// formats give their entries as soon as each line is complete, while a JSON object can only be read once the whole body
+// is there. Unlike in a source file, the csv and tsv header is optional.
+pub struct ImportParser {
+    format: SourceFormat,
+    options: SourceOptions,
+    line_parser: LineParser,
+    buffer: Vec<u8>,
+    line_number: usize,
+    max_json_body_size: usize,
+    max_line_size: usize,
+}
+
+impl ImportParser {
+    pub fn new(format: SourceFormat) -> ImportParser {
+        ImportParser::with_limits(format, MAX_JSON_BODY_SIZE, MAX_LINE_SIZE)
+    }
+
+    fn with_limits(format: SourceFormat, max_json_body_size: usize, max_line_size: usize) -> ImportParser {
+        let options = SourceOptions::default();
+
+        ImportParser {
+            format,
+            line_parser: LineParser::new(format, options.clone()),
+            options,
+            buffer: Vec::new(),
+            line_number: 0,
+            max_json_body_size,
+            max_line_size,
+        }
+    }
+
+    //fails when what has to be kept until more of the body arrives grows past the limits.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<Result<ImportEntry, Rejection>>, AppError> {
+        if self.format == SourceFormat::Json {
+            if self.buffer.len() + chunk.len() > self.max_json_body_size {
+                return Err(AppError::PayloadTooLarge {
+                    limit: self.max_json_body_size,
+                });
+            }
+
+            self.buffer.extend_from_slice(chunk);
+            return Ok(Vec::new());
+        }
+
+        self.buffer.extend_from_slice(chunk);
+
+        let entries = match self.buffer.iter().rposition(|byte| *byte == b'\n') {
+            Some(position) => {
+                let lines: Vec<u8> = self.buffer.drain(..position + 1).collect();
+                self.parse_lines(&lines)
+            }
+            None => Vec::new(),
+        };
+
+        if self.buffer.len() > self.max_line_size {
+            return Err(AppError::PayloadLineTooLong {
+                line: self.line_number + 1,
+                limit: self.max_line_size,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    //parses what is left once the body is over. Fails only when a JSON body is not a JSON object.
+    pub fn finish(mut self) -> Result<Vec<Result<ImportEntry, Rejection>>, AppError> {
+        let rest = std::mem::take(&mut self.buffer);
+
+        match self.format {
+            SourceFormat::Json => {
+                let content = std::str::from_utf8(&rest).map_err(|e| AppError::InvalidPayloadEncoding {
+                    valid_up_to: e.valid_up_to(),
+                })?;
+                let entries = source::parse_json_entries(content.trim_start_matches('\u{feff}'))
+                    .map_err(|e| AppError::from_payload_error(&e))?;
+
+                Ok(entries.into_iter().map(import_entry).collect())
+            }
+            _ => Ok(self.parse_lines(&rest)),
+        }
+    }
+
+    fn parse_lines(&mut self, content: &[u8]) -> Vec<Result<ImportEntry, Rejection>> {
+        let mut entries = Vec::new();
+
+        let mut lines: Vec<&[u8]> = content.split(|byte| *byte == b'\n').collect();
+        //nothing follows the last line break
+        if content.is_empty() || content.ends_with(b"\n") {
+            lines.pop();
+        }
+
+        for line in lines {
+            self.line_number += 1;
+
+            let line = match std::str::from_utf8(line) {
+                Ok(line) => line.trim_end_matches('\r'),
+                Err(_) => {
+                    entries.push(Err(Rejection {
+                        line: Some(self.line_number),
+                        name: None,
+                        reason: "Line is not valid UTF-8".to_string(),
+                    }));
+                    continue;
+                }
+            };
+
+            //lines of a body without header are read with the default columns
+            if self.line_number == 1 && self.is_headless(line) {
+                let header = format!("{}{}{}", self.options.name_column, self.delimiter(), self.options.popularity_column);
+                let _ = self.line_parser.parse_line(0, &header);
+            }
+
+            match self.line_parser.parse_line(self.line_number, line) {
+                Ok(Some(entry)) => entries.push(import_entry(entry)),
+                Ok(None) => {}
+                Err(issue) => entries.push(Err(rejection(issue))),
+            }
+        }
+
+        entries
+    }
+
+    fn delimiter(&self) -> char {
+        match self.format {
+            SourceFormat::Tsv => '\t',
+            _ => ',',
+        }
+    }
+
+    //a csv or tsv first line without the name column.
+    fn is_headless(&self, line: &str) -> bool {
+        if !matches!(self.format, SourceFormat::Csv | SourceFormat::Tsv) {
+            return false;
+        }
+
+        match split_csv_line(line, self.delimiter()) {
+            Ok(fields) => !fields
+                .iter()
+                .any(|field| field.trim().eq_ignore_ascii_case(&self.options.name_column)),
+            Err(_) => true,
+        }
+    }
+}
+
+//names read from the body are still checked for being empty, as a source file only warns about them.
+fn import_entry(entry: Result<SourceEntry, Issue>) -> Result<ImportEntry, Rejection> {
+    let entry = entry.map_err(rejection)?;
+
+    if entry.name.trim().is_empty() {
+        return Err(Rejection {
+            line: Some(entry.line),
+            name: Some(entry.name),
+            reason: Problem::EmptyName.to_string(),
+        });
+    }
+
+    Ok(ImportEntry {
+        name: entry.name,
+        times: entry.popularity,
+    })
+}
+
+fn rejection(issue: Issue) -> Rejection {
+    let name = match &issue.problem {
+        Problem::InvalidPopularity { name, .. } => Some(name.clone()),
+        _ => None,
+    };
+
+    Rejection {
+        line: Some(issue.line),
+        name,
+        reason: issue.problem.to_string(),
+    }
+}
+
+//merges the entries into the trie, journaling each change before it is applied. Stops at the first change that can't be
+//journaled, leaving in the summary the entries merged until then.
+pub fn apply(
+    trie: &mut Trie,
+    entries: Vec<Result<ImportEntry, Rejection>>,
+    policy: ConflictPolicy,
+    summary: &mut ImportSummary,
+    mut journal: impl FnMut(&JournalRecord) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(rejection) => {
+                summary.reject(rejection);
+                continue;
+            }
+        };
+
+        let existing_word_data = trie.find_word(&entry.name).cloned();
+
+        let (name, times) = match (existing_word_data, policy) {
+            (None, _) => (entry.name, entry.times),
+            (Some(_), ConflictPolicy::Keep) => {
+                summary.skipped += 1;
+                continue;
+            }
+            (Some(word_data), ConflictPolicy::Overwrite) => (word_data.word, entry.times),
+            (Some(word_data), ConflictPolicy::Add) => match word_data.popularity.checked_add(entry.times) {
+                Some(times) => (word_data.word, times),
+                None => {
+                    summary.reject(Rejection {
+                        line: None,
+                        name: Some(word_data.word),
+                        reason: AppError::PopularityOverflow.to_string(),
+                    });
+                    continue;
+                }
+            },
+        };
+
+        let is_new_name = trie.find_word(&name).is_none();
+        journal(&JournalRecord::Insert {
+            name: name.clone(),
+            times,
+        })?;
+
+        if let Err(e) = trie.insert_word(name.clone(), times) {
+            summary.reject(Rejection {
+                line: None,
+                name: Some(name),
+                reason: e.to_string(),
+            });
+            continue;
+        }
+
+        if is_new_name {
+            summary.inserted += 1;
+        } else {
+            summary.updated += 1;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trie::WordData;
+
+    fn parse(format: SourceFormat, chunks: &[&str]) -> Vec<Result<ImportEntry, Rejection>> {
+        let mut parser = ImportParser::new(format);
+        let mut entries = Vec::new();
+
+        for chunk in chunks {
+            entries.extend(parser.feed(chunk.as_bytes()).unwrap());
+        }
+        entries.extend(parser.finish().unwrap());
+
+        entries
+    }
+
+    fn import_entry(name: &str, times: Popularity) -> Result<ImportEntry, Rejection> {
+        Ok(ImportEntry {
+            name: name.to_string(),
+            times,
+        })
+    }
+
+    fn rejection(line: Option<usize>, name: Option<&str>, reason: &str) -> Result<ImportEntry, Rejection> {
+        Err(Rejection {
+            line,
+            name: name.map(str::to_string),
+            reason: reason.to_string(),
+        })
+    }
+
+    //applies the entries, returning the records journaled along the way.
+    fn apply_recording(
+        trie: &mut Trie,
+        entries: Vec<Result<ImportEntry, Rejection>>,
+        policy: ConflictPolicy,
+        summary: &mut ImportSummary,
+    ) -> Vec<JournalRecord> {
+        let mut records = Vec::new();
+
+        apply(trie, entries, policy, summary, |record| {
+            records.push(record.clone());
+            Ok(())
+        })
+        .unwrap();
+
+        records
+    }
+
+    #[test]
+    fn t_parse_json() {
+        let entries = parse(SourceFormat::Json, &["{\"Aar\": 36", "1, \"Abe\": -1, \"\": 3, \"Abel\": \"4\"}"]);
+
+        assert_eq!(
+            vec![
+                import_entry("Aar", 361),
+                rejection(Some(1), Some("Abe"), "Popularity -1 of \"Abe\" is not a whole number from 0 to 18446744073709551615"),
+                rejection(Some(1), Some(""), "Empty name"),
+                rejection(Some(1), Some("Abel"), "Popularity \"4\" of \"Abel\" is not a whole number from 0 to 18446744073709551615"),
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn t_parse_json_errors() {
+        let mut parser = ImportParser::new(SourceFormat::Json);
+        parser.feed(b"[\"Aar\"]").unwrap();
+
+        assert_eq!(
+            Err(AppError::InvalidPayload {
+                reason: "invalid type: sequence, expected an object with names and popularities".to_string(),
+                line: 1,
+                column: 0,
+            }),
+            parser.finish().map(|_| ())
+        );
+
+        let mut parser = ImportParser::new(SourceFormat::Json);
+        parser.feed(b"{\"Aar\": 361,").unwrap();
+
+        assert_eq!(
+            Err(AppError::MalformedPayload {
+                reason: "EOF while parsing a value".to_string(),
+                line: 1,
+                column: 12,
+            }),
+            parser.finish().map(|_| ())
+        );
+
+        let mut parser = ImportParser::new(SourceFormat::Json);
+        parser.feed(b"{\"Aar\xff\": 361}").unwrap();
+
+        assert_eq!(Err(AppError::InvalidPayloadEncoding { valid_up_to: 5 }), parser.finish().map(|_| ()));
+    }
+
+    #[test]
+    fn t_parse_over_limits() {
+        let mut parser = ImportParser::with_limits(SourceFormat::Json, 16, 8);
+        parser.feed(b"{\"Aar\": 361, ").unwrap();
+
+        assert_eq!(Err(AppError::PayloadTooLarge { limit: 16 }), parser.feed(b"\"Abe\": 300}").map(|_| ()));
+
+        //complete lines are parsed whatever the size of the chunk, only the line waiting for its end is bounded
+        let mut parser = ImportParser::with_limits(SourceFormat::Lines, 16, 8);
+        let entries = parser.feed(b"Aar\nAbe\nAbel\nAbr").unwrap();
+
+        assert_eq!(vec![import_entry("Aar", 0), import_entry("Abe", 0), import_entry("Abel", 0)], entries);
+        assert_eq!(
+            Err(AppError::PayloadLineTooLong { line: 4, limit: 8 }),
+            parser.feed(b"aham Lincoln").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn t_parse_ndjson_across_chunks() {
+        let entries = parse(
+            SourceFormat::Ndjson,
+            &[
+                "{\"name\": \"Aar\", \"times\": 361}\n{\"name\": \"Ab",
+                "e\", \"times\": 300}\r\n\nnot json\n",
+                "{\"name\": \"Abel\", \"times\": 1.5}\n{\"name\": \"Élodie\", \"times\": 7}",
+            ],
+        );
+
+        assert_eq!(
+            vec![
+                import_entry("Aar", 361),
+                import_entry("Abe", 300),
+                rejection(Some(4), None, "Invalid JSON: expected ident at line 1 column 2"),
+                rejection(Some(5), Some("Abel"), "Popularity 1.5 of \"Abel\" is not a whole number from 0 to 18446744073709551615"),
+                import_entry("Élodie", 7),
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn t_parse_csv() {
+        let entries = parse(
+            SourceFormat::Csv,
+            &["name,times\nAar,361\n\"Smith, Jr\",5\n\"Jo \"\"Jo\"\"\", 3\nAbe\nAbel,many\n\"Abe,3\n ,2\n"],
+        );
+
+        assert_eq!(
+            vec![
+                import_entry("Aar", 361),
+                import_entry("Smith, Jr", 5),
+                import_entry("Jo \"Jo\"", 3),
+                rejection(Some(5), None, "Expected 2 fields, found 1"),
+                rejection(Some(6), Some("Abel"), "Popularity many of \"Abel\" is not a whole number from 0 to 18446744073709551615"),
+                rejection(Some(7), None, "Unclosed quote"),
+                rejection(Some(8), Some(" "), "Empty name"),
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn t_parse_csv_without_header() {
+        let entries = parse(SourceFormat::Csv, &["Aar,361\nAbe,300"]);
+
+        assert_eq!(vec![import_entry("Aar", 361), import_entry("Abe", 300)], entries);
+    }
+
+    #[test]
+    fn t_parse_tsv_and_lines() {
+        let entries = parse(SourceFormat::Tsv, &["name\ttimes\nSmith, Jr\t5\nAb", "e\t3\n"]);
+
+        assert_eq!(vec![import_entry("Smith, Jr", 5), import_entry("Abe", 3)], entries);
+
+        let entries = parse(SourceFormat::Lines, &["Name\nAar\n\nAb", "e"]);
+
+        assert_eq!(vec![import_entry("Aar", 0), import_entry("Abe", 0)], entries);
+    }
+
+    #[test]
+    fn t_apply_policies() {
+        let file_content = "{\"Aar\": 361, \"Abe\": 300, \"Aba\": 18446744073709551615}";
+        let entries = vec![
+            import_entry("AAR", 10),
+            import_entry("Abel", 5),
+            import_entry("Aba", 1),
+            rejection(Some(4), None, "Empty name"),
+        ];
+
+        let cases = [
+            (ConflictPolicy::Overwrite, 10, 0, 2),
+            (ConflictPolicy::Add, 371, 0, 1),
+            (ConflictPolicy::Keep, 361, 2, 0),
+        ];
+
+        for (policy, aar_popularity, skipped, updated) in cases {
+            let mut trie = Trie::initialize(file_content, 10).unwrap();
+            let mut summary = ImportSummary::default();
+
+            let records = apply_recording(&mut trie, entries.clone(), policy, &mut summary);
+
+            assert_eq!(1, summary.inserted);
+            assert_eq!(updated, summary.updated);
+            assert_eq!(skipped, summary.skipped);
+            assert_eq!(summary.rejected, summary.rejections.len());
+            assert_eq!(1 + updated, records.len());
+            assert_eq!(Some(&WordData::new("Aar".to_string(), aar_popularity)), trie.find_word("aar"));
+            assert_eq!(Some(&WordData::new("Abel".to_string(), 5)), trie.find_word("Abel"));
+        }
+    }
+
+    #[test]
+    fn t_apply_add_overflow() {
+        let mut trie = Trie::initialize("{\"Aba\": 18446744073709551615}", 10).unwrap();
+        let mut summary = ImportSummary::default();
+
+        let records = apply_recording(&mut trie, vec![import_entry("aba", 1)], ConflictPolicy::Add, &mut summary);
+
+        assert!(records.is_empty());
+        assert_eq!(
+            vec![Rejection {
+                line: None,
+                name: Some("Aba".to_string()),
+                reason: "Word popularity can not grow any further.".to_string()
+            }],
+            summary.rejections
+        );
+        assert_eq!(Some(&WordData::new("Aba".to_string(), u64::MAX)), trie.find_word("Aba"));
+    }
+
+    #[test]
+    fn t_apply_stops_when_journaling_fails() {
+        let mut trie = Trie::initialize("{\"Aar\": 361}", 10).unwrap();
+        let mut summary = ImportSummary::default();
+        let entries = vec![import_entry("Abe", 1), rejection(Some(2), None, "Empty name"), import_entry("Abel", 2), import_entry("Ada", 3)];
+
+        let mut journaled_records = 0;
+        let result = apply(&mut trie, entries, ConflictPolicy::Keep, &mut summary, |_| {
+            journaled_records += 1;
+            match journaled_records {
+                1 => Ok(()),
+                _ => Err(AppError::UnexpectedError),
+            }
+        });
+
+        assert_eq!(Err(AppError::UnexpectedError), result);
+        assert_eq!(1, summary.inserted);
+        assert_eq!(1, summary.rejected);
+        assert_eq!(Some(&WordData::new("Abe".to_string(), 1)), trie.find_word("Abe"));
+        assert_eq!(None, trie.find_word("Abel"));
+        assert_eq!(None, trie.find_word("Ada"));
+    }
+
+    #[test]
+    fn t_apply_lists_first_rejections_only() {
+        let mut trie = Trie::initialize("{}", 10).unwrap();
+        let mut summary = ImportSummary::default();
+        let entries = (0..MAX_LISTED_REJECTIONS + 5)
+            .map(|line| rejection(Some(line), None, "Empty name"))
+            .collect();
+
+        apply_recording(&mut trie, entries, ConflictPolicy::Keep, &mut summary);
+
+        assert_eq!(MAX_LISTED_REJECTIONS + 5, summary.rejected);
+        assert_eq!(MAX_LISTED_REJECTIONS, summary.rejections.len());
+    }
+}
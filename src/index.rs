@@ -0,0 +1,219 @@
+use crate::app_error::AppError;
+use crate::trie::{Node, Trie, TrieOptions};
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+const INDEX_MAGIC: &[u8; 8] = b"TAHINDEX";
+//must be bumped whenever the layout of the payload changes, e.g. a new field in Node or WordData.
+pub const INDEX_FORMAT_VERSION: u32 = 1;
+//magic, format version and payload checksum.
+const HEADER_LEN: usize = 16;
+
+// Binary copy of an already built trie, so the server can start without parsing the names and building the cached
+// suggestions again. Only the options that shape the trie are stored, the others are taken from the configuration.
+#[derive(Serialize, Deserialize)]
+struct IndexPayload<'a> {
+    suggestion_number: u8,
+    accent_insensitive: bool,
+    root: Cow<'a, Node>,
+}
+
+//encodes the trie, header included.
+pub fn encode(trie: &Trie) -> Result<Vec<u8>, AppError> {
+    let payload = IndexPayload {
+        suggestion_number: trie.suggestion_number,
+        accent_insensitive: trie.accent_insensitive,
+        root: Cow::Borrowed(&trie.root),
+    };
+
+    let payload = bincode::serialize(&payload).map_err(|_e| AppError::UnexpectedError)?;
+
+    let mut content = Vec::with_capacity(HEADER_LEN + payload.len());
+    content.extend_from_slice(INDEX_MAGIC);
+    content.extend_from_slice(&INDEX_FORMAT_VERSION.to_le_bytes());
+    content.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    content.extend_from_slice(&payload);
+
+    Ok(content)
+}
+
+//decodes a trie encoded with the same format version, checking it against its checksum and the configured options.
+pub fn decode(content: &[u8], options: TrieOptions) -> Result<Trie, AppError> {
+    if content.len() < HEADER_LEN || &content[..INDEX_MAGIC.len()] != INDEX_MAGIC {
+        return Err(AppError::InvalidIndexFile);
+    }
+
+    let version = read_u32(&content[8..12]);
+    if version != INDEX_FORMAT_VERSION {
+        return Err(AppError::IndexVersionMismatch {
+            found: version,
+            expected: INDEX_FORMAT_VERSION,
+        });
+    }
+
+    let payload = &content[HEADER_LEN..];
+    if crc32fast::hash(payload) != read_u32(&content[12..16]) {
+        return Err(AppError::InvalidIndexFile);
+    }
+
+    let payload: IndexPayload =
+        bincode::deserialize(payload).map_err(|_e| AppError::InvalidIndexFile)?;
+
+    if payload.suggestion_number != options.suggestion_number
+        || payload.accent_insensitive != options.accent_insensitive
+    {
+        error!(
+            "Index was built with SUGGESTION_NUMBER={} and ACCENT_INSENSITIVE={}, but the server runs with {} and {}",
+            payload.suggestion_number,
+            payload.accent_insensitive,
+            options.suggestion_number,
+            options.accent_insensitive
+        );
+        return Err(AppError::IndexOptionsMismatch);
+    }
+
+    let mut trie = Trie::with_options(options);
+    trie.root = Box::new(payload.root.into_owned());
+
+    Ok(trie)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buffer = [0; 4];
+    buffer.copy_from_slice(bytes);
+
+    u32::from_le_bytes(buffer)
+}
+
+//writes the index next to its final path first, so the server never reads a half written file.
+pub fn write(trie: &Trie, path: &str) -> Result<(), AppError> {
+    let content = encode(trie)?;
+    let temp_path = format!("{}.tmp", path);
+
+    File::create(&temp_path)
+        .and_then(|mut file| file.write_all(&content).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&temp_path, Path::new(path)))
+        .map_err(|e| {
+            error!("Could not write index {}: {}", path, e);
+            AppError::UnexpectedError
+        })
+}
+
+//the whole trie is decoded to the heap anyway, so the file is read at once rather than mapped.
+pub fn load(path: &str, options: TrieOptions) -> Result<Trie, AppError> {
+    let content = fs::read(path).map_err(|e| {
+        error!("Could not read index {}: {}", path, e);
+        AppError::InvalidIndexFile
+    })?;
+
+    decode(&content, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::tests::temp_file_path;
+    use crate::trie::{ITrie, WordData};
+
+    const FILE_CONTENT: &str = "{\"Aar\": 361, \"Aari\": 151, \"Rose-Marie\": 40, \"Zoë\": 7, \"Zoe\": 9}";
+
+    #[test]
+    fn t_write_and_load() {
+        let path = temp_file_path("index-load");
+        let trie = Trie::initialize(FILE_CONTENT, 10).unwrap();
+
+        write(&trie, &path).unwrap();
+        let loaded_trie = load(&path, 10.into()).unwrap();
+
+        assert_eq!(trie.all_words(), loaded_trie.all_words());
+        assert_eq!(
+            trie.get_typeahead_words("ma".to_string()).unwrap(),
+            loaded_trie.get_typeahead_words("ma".to_string()).unwrap()
+        );
+        assert_eq!(
+            vec![
+                WordData::new("Aar".to_string(), 361),
+                WordData::new("Aari".to_string(), 151)
+            ],
+            loaded_trie.get_typeahead_words("a".to_string()).unwrap()
+        );
+    }
+
+    #[test]
+    fn t_decode_keeps_runtime_options() {
+        let trie = Trie::initialize(FILE_CONTENT, 10).unwrap();
+        let options = TrieOptions {
+            max_fuzzy_edits: 1,
+            ..TrieOptions::from(10)
+        };
+
+        let loaded_trie = decode(&encode(&trie).unwrap(), options).unwrap();
+
+        assert_eq!(1, loaded_trie.max_fuzzy_edits);
+    }
+
+    #[test]
+    fn t_decode_version_mismatch() {
+        let trie = Trie::initialize(FILE_CONTENT, 10).unwrap();
+        let mut content = encode(&trie).unwrap();
+        content[8..12].copy_from_slice(&(INDEX_FORMAT_VERSION + 1).to_le_bytes());
+
+        let error = decode(&content, 10.into()).unwrap_err();
+
+        assert_eq!(
+            AppError::IndexVersionMismatch {
+                found: INDEX_FORMAT_VERSION + 1,
+                expected: INDEX_FORMAT_VERSION
+            },
+            error
+        );
+        assert_eq!(
+            "Index file format version 2 is not supported, expected version 1. Build the index again.",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn t_decode_options_mismatch() {
+        let trie = Trie::initialize(FILE_CONTENT, 10).unwrap();
+        let content = encode(&trie).unwrap();
+
+        let options = TrieOptions {
+            accent_insensitive: true,
+            ..TrieOptions::from(10)
+        };
+
+        assert_eq!(Err(AppError::IndexOptionsMismatch), decode(&content, 5.into()).map(|_| ()));
+        assert_eq!(Err(AppError::IndexOptionsMismatch), decode(&content, options).map(|_| ()));
+    }
+
+    #[test]
+    fn t_decode_corrupted_content() {
+        let trie = Trie::initialize(FILE_CONTENT, 10).unwrap();
+        let content = encode(&trie).unwrap();
+
+        let mut flipped_content = content.clone();
+        let last = flipped_content.len() - 1;
+        flipped_content[last] ^= 1;
+
+        let mut wrong_magic = content.clone();
+        wrong_magic[0] = b'X';
+
+        assert_eq!(Err(AppError::InvalidIndexFile), decode(&flipped_content, 10.into()).map(|_| ()));
+        assert_eq!(Err(AppError::InvalidIndexFile), decode(&content[..content.len() - 1], 10.into()).map(|_| ()));
+        assert_eq!(Err(AppError::InvalidIndexFile), decode(&wrong_magic, 10.into()).map(|_| ()));
+        assert_eq!(Err(AppError::InvalidIndexFile), decode(&content[..4], 10.into()).map(|_| ()));
+        assert_eq!(Err(AppError::InvalidIndexFile), decode(FILE_CONTENT.as_bytes(), 10.into()).map(|_| ()));
+    }
+
+    #[test]
+    fn t_load_missing_file() {
+        let path = temp_file_path("index-missing");
+
+        assert_eq!(Err(AppError::InvalidIndexFile), load(&path, 10.into()).map(|_| ()));
+    }
+}
@@ -0,0 +1,569 @@
+use crate::app_error::AppError;
+use crate::decay;
+use crate::trie::{ITrie, Popularity, Trie};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::sync::Mutex;
+
+//how often appended records are flushed to the disk. Records that were not synced yet can be lost on a power failure,
+//but not on a crash of the process alone, as they are already handed to the operating system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    //after every record.
+    Always,
+    //after every JOURNAL_FSYNC_BATCH_SIZE records.
+    Batch,
+    //whenever the operating system decides to.
+    Never,
+}
+
+//one line of the journal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JournalRecord {
+    //`at` is when the hit was given, in milliseconds since the Unix epoch. Journals written before it was added don't have it.
+    Hit {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        at: Option<u64>,
+    },
+    Insert { name: String, times: Popularity },
+    Remove { name: String },
+    Rename { name: String, new_name: String },
+    //first record after a snapshot: the records that follow it are the changes made after that snapshot.
+    Snapshot { id: u64 },
+}
+
+// Append-only file with every change made to the names after they were loaded, one JSON record per line, so they can be
+// replayed on top of the source file after a restart.
+#[derive(Debug)]
+pub struct Journal {
+    writer: Mutex<JournalWriter>,
+    fsync_policy: FsyncPolicy,
+    fsync_batch_size: u32,
+}
+
+#[derive(Debug)]
+struct JournalWriter {
+    file: File,
+    //length of the file up to the last record that was fully appended.
+    len: u64,
+    unsynced_records: u32,
+}
+
+impl Journal {
+    //opens the journal, creating it if needed, and returns the records it already holds. A final record without its line
+    //break was cut by a crash while being written: it is dropped and removed from the file so new records start on a clean line.
+    pub fn open(
+        path: &str,
+        fsync_policy: FsyncPolicy,
+        fsync_batch_size: u32,
+    ) -> Result<(Journal, Vec<JournalRecord>), AppError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| {
+                error!("Could not open journal {}: {}", path, e);
+                AppError::UnexpectedError
+            })?;
+
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)
+            .map_err(|_e| AppError::InvalidJournalContent)?;
+
+        let complete_len = content
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |position| position + 1);
+
+        let records = parse_records(&content[..complete_len])?;
+
+        if complete_len < content.len() {
+            warn!(
+                "Dropping truncated record at the end of journal {}: {:?}",
+                path,
+                String::from_utf8_lossy(&content[complete_len..])
+            );
+            file.set_len(complete_len as u64)
+                .and_then(|_| file.sync_data())
+                .map_err(|_e| AppError::UnexpectedError)?;
+        }
+
+        let journal = Journal {
+            writer: Mutex::new(JournalWriter {
+                file,
+                len: complete_len as u64,
+                unsynced_records: 0,
+            }),
+            fsync_policy,
+            fsync_batch_size: fsync_batch_size.max(1),
+        };
+
+        Ok((journal, records))
+    }
+
+    //the changes are journaled before they are applied, so when this fails the change must not be applied either.
+    pub fn append(&self, record: &JournalRecord) -> Result<(), AppError> {
+        let mut line = serde_json::to_vec(record).map_err(|_e| AppError::UnexpectedError)?;
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().map_err(|_e| AppError::UnexpectedError)?;
+
+        //the whole line goes in a single write, so only a crash in the middle of it can leave a record without its line break
+        if let Err(e) = writer.file.write_all(&line) {
+            error!("Could not append to journal: {}", e);
+            return Err(writer.discard_partial_record());
+        }
+        writer.unsynced_records += 1;
+
+        let should_sync = match self.fsync_policy {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::Batch => writer.unsynced_records >= self.fsync_batch_size,
+            FsyncPolicy::Never => false,
+        };
+
+        if should_sync {
+            if let Err(e) = writer.file.sync_data() {
+                error!("Could not sync journal: {}", e);
+                return Err(writer.discard_partial_record());
+            }
+            writer.unsynced_records = 0;
+        }
+
+        writer.len += line.len() as u64;
+
+        Ok(())
+    }
+
+    //empties the journal once everything in it made it to the snapshot. Must be called while no record can be appended.
+    pub fn reset(&self, snapshot_id: u64) -> Result<(), AppError> {
+        let mut line = serde_json::to_vec(&JournalRecord::Snapshot { id: snapshot_id })
+            .map_err(|_e| AppError::UnexpectedError)?;
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().map_err(|_e| AppError::UnexpectedError)?;
+
+        writer
+            .file
+            .set_len(0)
+            .and_then(|_| writer.file.write_all(&line))
+            .and_then(|_| writer.file.sync_data())
+            .map_err(|e| {
+                error!("Could not reset journal: {}", e);
+                AppError::UnexpectedError
+            })?;
+        writer.len = line.len() as u64;
+        writer.unsynced_records = 0;
+
+        Ok(())
+    }
+}
+
+impl JournalWriter {
+    //cuts what a failed append may have left, so the record that is reported as failed is not replayed after a restart.
+    fn discard_partial_record(&mut self) -> AppError {
+        if let Err(e) = self.file.set_len(self.len) {
+            error!("Could not discard the failed journal record: {}", e);
+        }
+
+        AppError::UnexpectedError
+    }
+}
+
+//records that still have to be replayed on top of the given snapshot (0 when the names came from the source file).
+pub fn records_since_snapshot(records: &[JournalRecord], snapshot_id: u64) -> &[JournalRecord] {
+    let (journal_snapshot_id, first_record) = records
+        .iter()
+        .rposition(|record| matches!(record, JournalRecord::Snapshot { .. }))
+        .map_or((0, 0), |position| match records[position] {
+            JournalRecord::Snapshot { id } => (id, position + 1),
+            _ => (0, 0),
+        });
+
+    if snapshot_id > journal_snapshot_id {
+        //the process stopped between writing the snapshot and resetting the journal, the snapshot already has these records
+        info!(
+            "Journal records predate snapshot {}, they are already applied",
+            snapshot_id
+        );
+        return &[];
+    }
+
+    if snapshot_id < journal_snapshot_id {
+        warn!(
+            "Journal starts after snapshot {}, but snapshot {} was loaded. Changes made in between are lost",
+            journal_snapshot_id, snapshot_id
+        );
+    }
+
+    &records[first_record..]
+}
+
+fn parse_records(content: &[u8]) -> Result<Vec<JournalRecord>, AppError> {
+    content
+        .split(|byte| *byte == b'\n')
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(index, line)| {
+            serde_json::from_slice(line).map_err(|e| {
+                error!("Invalid journal record at line {}: {}", index + 1, e);
+                AppError::InvalidJournalContent
+            })
+        })
+        .collect()
+}
+
+//applies the records on top of the freshly loaded trie. Changes to names that are gone from the source file are skipped.
+//Hits without their time are counted in the decayed scores as if they were given now.
+pub fn replay(records: &[JournalRecord], trie: &mut Trie) {
+    let mut skipped_records = 0;
+
+    for record in records {
+        let result = match record {
+            JournalRecord::Hit { name, at: Some(at) } => {
+                trie.hit(name.clone(), decay::from_unix_millis(*at)).map(|_| ())
+            }
+            JournalRecord::Hit { name, at: None } => trie.increase_popularity(name.clone()).map(|_| ()),
+            JournalRecord::Insert { name, times } => trie.insert_word(name.clone(), *times),
+            JournalRecord::Remove { name } => trie.remove_word(name.clone()).map(|_| ()),
+            JournalRecord::Rename { name, new_name } => {
+                trie.rename_word(name.clone(), new_name.clone()).map(|_| ())
+            }
+            JournalRecord::Snapshot { .. } => Ok(()),
+        };
+
+        if let Err(e) = result {
+            warn!("Skipping journal record {:?}: {}", record, e);
+            skipped_records += 1;
+        }
+    }
+
+    info!(
+        "Replayed {} journal records, {} skipped",
+        records.len() - skipped_records,
+        skipped_records
+    );
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::decay::DEFAULT_DECAY_HALF_LIFE;
+    use crate::trie::WordData;
+    use std::env;
+    use std::fs;
+    use std::process::{Command, Stdio};
+    use std::thread;
+    use std::time::{Duration, Instant, SystemTime};
+
+    //path of an empty file in the temp dir, unique for each test. What the test leaves there, a file or a directory, is
+    //removed when it's dropped.
+    pub fn temp_file_path(name: &str) -> TempPath {
+        let path = env::temp_dir().join(format!("type-ahead-api-{}-{}", std::process::id(), name));
+        let path = TempPath(path.to_str().unwrap().to_string());
+        path.remove();
+
+        path
+    }
+
+    pub struct TempPath(String);
+
+    impl TempPath {
+        fn remove(&self) {
+            let _ = fs::remove_file(&self.0);
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            self.remove();
+        }
+    }
+
+    impl std::ops::Deref for TempPath {
+        type Target = String;
+
+        fn deref(&self) -> &String {
+            &self.0
+        }
+    }
+
+    impl AsRef<std::path::Path> for TempPath {
+        fn as_ref(&self) -> &std::path::Path {
+            self.0.as_ref()
+        }
+    }
+
+    impl std::fmt::Display for TempPath {
+        fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            self.0.fmt(formatter)
+        }
+    }
+
+    fn hit(name: &str) -> JournalRecord {
+        JournalRecord::Hit {
+            name: name.to_string(),
+            at: None,
+        }
+    }
+
+    //a journal whose appends always fail, like on a full disk.
+    pub fn failing_journal() -> Journal {
+        Journal {
+            writer: Mutex::new(JournalWriter {
+                file: File::open("/dev/null").unwrap(),
+                len: 0,
+                unsynced_records: 0,
+            }),
+            fsync_policy: FsyncPolicy::Always,
+            fsync_batch_size: 1,
+        }
+    }
+
+    #[test]
+    fn t_open_append_and_reopen() {
+        let path = temp_file_path("journal-reopen");
+
+        let (journal, records) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+        assert!(records.is_empty());
+
+        journal.append(&hit("Aar")).unwrap();
+        journal.append(&hit("Abe")).unwrap();
+        drop(journal);
+
+        let (_journal, records) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+        assert_eq!(vec![hit("Aar"), hit("Abe")], records);
+        assert_eq!(
+            "{\"op\":\"hit\",\"name\":\"Aar\"}\n{\"op\":\"hit\",\"name\":\"Abe\"}\n",
+            fs::read_to_string(&path).unwrap()
+        );
+    }
+
+    #[test]
+    fn t_open_drops_truncated_record() {
+        let path = temp_file_path("journal-truncated");
+        fs::write(&path, "{\"op\":\"hit\",\"name\":\"Aar\"}\n{\"op\":\"hit\",\"na").unwrap();
+
+        let (journal, records) = Journal::open(&path, FsyncPolicy::Batch, 10).unwrap();
+        assert_eq!(vec![hit("Aar")], records);
+
+        //the next record starts on its own line
+        journal.append(&hit("Abe")).unwrap();
+        drop(journal);
+
+        let (_journal, records) = Journal::open(&path, FsyncPolicy::Never, 1).unwrap();
+        assert_eq!(vec![hit("Aar"), hit("Abe")], records);
+    }
+
+    #[test]
+    fn t_open_recovers_from_crash_at_any_byte() {
+        let path = temp_file_path("journal-crash");
+        let records = vec![hit("Aar"), hit("Élodie"), hit("Rose-Marie")];
+
+        let (journal, _) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+        for record in &records {
+            journal.append(record).unwrap();
+        }
+        drop(journal);
+        let content = fs::read(&path).unwrap();
+
+        //the process could have been killed after any byte of the last write made it to the file
+        for written_len in 0..=content.len() {
+            fs::write(&path, &content[..written_len]).unwrap();
+
+            let (_journal, recovered_records) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+            let complete_lines = content[..written_len].iter().filter(|byte| **byte == b'\n').count();
+
+            assert_eq!(records[..complete_lines], recovered_records[..]);
+        }
+    }
+
+    //set for the copy of the tests started by t_open_after_killed_append, which appends until it is killed.
+    const KILLED_APPEND_PATH: &str = "TYPE_AHEAD_API_KILLED_APPEND_PATH";
+
+    #[test]
+    fn t_open_after_killed_append() {
+        if let Ok(path) = env::var(KILLED_APPEND_PATH) {
+            let (journal, _) = Journal::open(&path, FsyncPolicy::Never, 1).unwrap();
+            //long records, so the kill often lands in the middle of one
+            for index in 0.. {
+                journal.append(&hit(&format!("{}{}", index, "a".repeat(256 * 1024)))).unwrap();
+            }
+        }
+
+        let path = temp_file_path("journal-killed");
+        let mut child = Command::new(env::current_exe().unwrap())
+            .args(["journal::tests::t_open_after_killed_append", "--exact", "--test-threads=1"])
+            .env(KILLED_APPEND_PATH, path.as_str())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        //the child only stops when killed, so it exiting on its own means it failed before writing enough
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while fs::metadata(&path).map_or(0, |metadata| metadata.len()) < 4 * 1024 * 1024 {
+            if let Some(status) = child.try_wait().unwrap() {
+                panic!("appending child exited with {}", status);
+            }
+            if Instant::now() > deadline {
+                child.kill().unwrap();
+                child.wait().unwrap();
+                panic!("appending child did not write 4 MiB in time");
+            }
+
+            thread::sleep(Duration::from_millis(1));
+        }
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let (journal, records) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+        assert!(!records.is_empty());
+        for (index, record) in records.iter().enumerate() {
+            assert!(matches!(record, JournalRecord::Hit { name, .. } if name.starts_with(&format!("{}a", index))));
+        }
+
+        //appending goes on after the last complete record
+        journal.append(&hit("Aar")).unwrap();
+        drop(journal);
+
+        let (_journal, reopened_records) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+        assert_eq!(records.len() + 1, reopened_records.len());
+        assert_eq!(Some(&hit("Aar")), reopened_records.last());
+    }
+
+    #[test]
+    fn t_append_failure() {
+        let journal = failing_journal();
+
+        assert_eq!(Err(AppError::UnexpectedError), journal.append(&hit("Aar")));
+    }
+
+    #[test]
+    fn t_open_invalid_record() {
+        let path = temp_file_path("journal-invalid");
+        fs::write(&path, "{\"op\":\"hit\",\"name\":\"Aar\"}\nnot a record\n{\"op\":\"hit\",\"name\":\"Aar\"}\n").unwrap();
+
+        let error = Journal::open(&path, FsyncPolicy::Always, 1).unwrap_err();
+
+        assert_eq!(error, AppError::InvalidJournalContent);
+    }
+
+    #[test]
+    fn t_reset() {
+        let path = temp_file_path("journal-reset");
+
+        let (journal, _) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+        journal.append(&hit("Aar")).unwrap();
+        journal.reset(3).unwrap();
+        journal.append(&hit("Abe")).unwrap();
+        drop(journal);
+
+        let (_journal, records) = Journal::open(&path, FsyncPolicy::Always, 1).unwrap();
+        assert_eq!(vec![JournalRecord::Snapshot { id: 3 }, hit("Abe")], records);
+    }
+
+    #[test]
+    fn t_records_since_snapshot() {
+        let records = vec![JournalRecord::Snapshot { id: 3 }, hit("Aar"), hit("Abe")];
+
+        assert_eq!(records[1..], *records_since_snapshot(&records, 3));
+        //the snapshot after 3 was loaded, but the journal was not reset yet
+        assert!(records_since_snapshot(&records, 4).is_empty());
+        //snapshot 3 could not be read, the journal is all that is left
+        assert_eq!(records[1..], *records_since_snapshot(&records, 2));
+    }
+
+    #[test]
+    fn t_records_since_snapshot_without_snapshots() {
+        let records = vec![hit("Aar"), hit("Abe")];
+
+        assert_eq!(records[..], *records_since_snapshot(&records, 0));
+        assert!(records_since_snapshot(&records, 1).is_empty());
+    }
+
+    #[test]
+    fn t_replay() {
+        let mut trie = Trie::initialize("{\"Aar\": 361, \"Aari\": 151}", 10).unwrap();
+        let records = vec![hit("Aari"), hit("AARI"), hit("Brazil"), hit("Aar")];
+
+        replay(&records, &mut trie);
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aar".to_string(), 362),
+            WordData::new("Aari".to_string(), 153),
+        ];
+
+        assert_eq!(expected_words, trie.get_typeahead_words("aa".to_string()).unwrap());
+    }
+
+    #[test]
+    fn t_replay_hit_times() {
+        let mut trie = Trie::initialize("{\"Aar\": 361, \"Aari\": 151}", 10).unwrap();
+        let now = SystemTime::now();
+        let half_life_ago = now - DEFAULT_DECAY_HALF_LIFE;
+        let records = vec![
+            JournalRecord::Hit {
+                name: "Aar".to_string(),
+                at: Some(decay::to_unix_millis(half_life_ago)),
+            },
+            JournalRecord::Hit {
+                name: "Aar".to_string(),
+                at: Some(decay::to_unix_millis(half_life_ago)),
+            },
+            hit("Aari"),
+        ];
+
+        replay(&records, &mut trie);
+
+        //the two hits of Aar were given a half-life ago, the one of Aari counts as given now
+        assert!((trie.decayed_scores.score("Aar", now) - 1.0).abs() < 1e-6);
+        assert!(trie.decayed_scores.score("Aari", now) > 0.99);
+        assert_eq!(Some(&WordData::new("Aar".to_string(), 363)), trie.find_word("aar"));
+
+        let line = serde_json::to_string(&records[0]).unwrap();
+        assert_eq!(
+            format!("{{\"op\":\"hit\",\"name\":\"Aar\",\"at\":{}}}", decay::to_unix_millis(half_life_ago)),
+            line
+        );
+        assert_eq!("{\"op\":\"hit\",\"name\":\"Aari\"}", serde_json::to_string(&records[2]).unwrap());
+    }
+
+    #[test]
+    fn t_replay_admin_records() {
+        let mut trie = Trie::initialize("{\"Aar\": 361, \"Aari\": 151, \"rose-marie\": 10}", 10).unwrap();
+        let records = vec![
+            JournalRecord::Insert {
+                name: "Abe".to_string(),
+                times: 300,
+            },
+            JournalRecord::Remove {
+                name: "Aari".to_string(),
+            },
+            JournalRecord::Rename {
+                name: "rose-marie".to_string(),
+                new_name: "Rose-Marie".to_string(),
+            },
+            hit("Rose-Marie"),
+        ];
+
+        replay(&records, &mut trie);
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aar".to_string(), 361),
+            WordData::new("Abe".to_string(), 300),
+        ];
+
+        assert_eq!(expected_words, trie.get_typeahead_words("a".to_string()).unwrap());
+        assert_eq!(
+            vec![WordData::new("Rose-Marie".to_string(), 11)],
+            trie.get_typeahead_words("marie".to_string()).unwrap()
+        );
+    }
+}
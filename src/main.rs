@@ -1,17 +1,52 @@
-use crate::trie::{ITrie, Trie};
+use crate::handlers::{AdminToken, ExportPageSize, MaxResultLimit};
+use crate::journal::Journal;
+use crate::metrics::Metrics;
+use crate::snapshot::SnapshotStore;
+use crate::stats::StatsCache;
+use crate::reload::NamesSource;
+use crate::trie::Trie;
+use actix_web::dev::Service;
 use actix_web::{get, middleware, App, HttpResponse, HttpServer, web};
+use futures_util::FutureExt;
 use log::info;
-use std::fs;
+use std::env;
+use std::process;
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::sync::RwLock;
+use std::thread;
+use std::time::{Duration, Instant};
 
 mod app_error;
+mod cli;
 mod config;
+mod decay;
+mod export;
+mod fuzzy;
 mod handlers;
+mod import;
+mod index;
+mod journal;
+mod metrics;
+mod normalization;
+mod pagination;
+//experimental layout, only compared with the trie by the tests
+#[cfg(test)]
+mod radix_trie;
+mod ranking;
+mod reload;
+mod snapshot;
+mod source;
+mod stats;
 mod trie;
+mod validation;
 
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        process::exit(cli::run(&args));
+    }
+
     log4rs::init_file("log4rs.yml", log4rs::config::Deserializers::default()).unwrap();
 
     info!("Starting service...");
@@ -19,10 +54,75 @@ async fn main() -> std::io::Result<()> {
     let config =
         config::Config::from_env().expect("Could not load configuration from environment!");
 
-    let file_content = fs::read_to_string(config.file_name).expect("JSON file not found");
+    let trie_options = config.trie_options();
+
+    let names_source = match &config.index_file {
+        Some(index_file) => NamesSource::Index(index_file.clone()),
+        None => NamesSource::File(config.file_name.clone(), config.source_options()),
+    };
+
+    let snapshot_store = config.snapshot_dir.as_ref().map(|snapshot_dir| {
+        Arc::new(
+            SnapshotStore::open(snapshot_dir, config.snapshot_retention)
+                .expect("Could not open snapshot directory"),
+        )
+    });
+
+    //the newest snapshot takes the place of the source file, or of its index. Id 0 stands for the source file itself
+    let loaded_snapshot = match &snapshot_store {
+        Some(snapshot_store) => snapshot_store
+            .load_newest(trie_options)
+            .expect("Could not read snapshot directory"),
+        None => None,
+    };
+
+    let (snapshot_id, mut trie) = match loaded_snapshot {
+        Some(loaded_snapshot) => loaded_snapshot,
+        None => (
+            0,
+            names_source
+                .load(trie_options)
+                .unwrap_or_else(|e| panic!("Could not load names from {}: {}", names_source.path(), e)),
+        ),
+    };
+
+    let journal = match &config.journal_file {
+        Some(journal_file) => {
+            let (journal, records) = Journal::open(
+                journal_file,
+                config.journal_fsync,
+                config.journal_fsync_batch_size,
+            )
+            .expect("Could not open journal");
+            journal::replay(journal::records_since_snapshot(&records, snapshot_id), &mut trie);
+
+            Some(web::Data::new(journal))
+        }
+        None => None,
+    };
 
-    let trie = Trie::initialize(&file_content, config.suggestion_number).unwrap();
-    let shared_trie: Arc<Mutex<Trie>> = Arc::new(Mutex::new(trie));
+    info!("Names loaded, using around {} bytes", trie.root.heap_size());
+    let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
+
+    if let Some(snapshot_store) = &snapshot_store {
+        if config.snapshot_interval_seconds > 0 {
+            let snapshot_store = snapshot_store.clone();
+            let shared_trie = shared_trie.clone();
+            let journal = journal.clone();
+            let interval = Duration::from_secs(config.snapshot_interval_seconds);
+
+            thread::spawn(move || loop {
+                thread::sleep(interval);
+                snapshot_store.take_shared(&shared_trie, journal.as_ref().map(|journal| journal.get_ref()));
+            });
+        }
+    }
+
+    let watch_interval = match config.watch_interval_seconds {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds)),
+    };
+    reload::watch(names_source, trie_options, shared_trie.clone(), watch_interval)?;
 
     // let bind_address: SocketAddr = format!("{}:{}", config.host, config.port)
     //     .parse()
@@ -31,20 +131,80 @@ async fn main() -> std::io::Result<()> {
     let server_address = format!("{}:{}", config.host, config.port);
     info!("Starting server at {}", server_address);
 
+    let server_trie = shared_trie.clone();
+    let server_journal = journal.clone();
+    let admin_token = config
+        .admin_token
+        .clone()
+        .map(|admin_token| web::Data::new(AdminToken(admin_token)));
+    let stats_cache = web::Data::new(StatsCache::new(Duration::from_secs(config.stats_cache_seconds)));
+    let metrics = web::Data::new(Metrics::new().expect("Could not register metrics"));
+    let max_result_limit = web::Data::new(MaxResultLimit(config.max_result_limit));
+    //a streamed export takes at least one name per read lock
+    let export_page_size = web::Data::new(ExportPageSize(config.export_page_size.max(1)));
+    let ranking = web::Data::new(config.ranking());
+
     HttpServer::new(move || {
         App::new()
-            .app_data(web::Data::new(shared_trie.clone()))
+            .app_data(web::Data::new(server_trie.clone()))
+            .app_data(stats_cache.clone())
+            .app_data(metrics.clone())
+            .app_data(max_result_limit.clone())
+            .app_data(export_page_size.clone())
+            .app_data(ranking.clone())
+            .app_data(handlers::query_config())
+            .app_data(handlers::path_config())
+            .configure(|cfg| {
+                if let Some(journal) = &server_journal {
+                    cfg.app_data(journal.clone());
+                }
+                if let Some(admin_token) = &admin_token {
+                    cfg.app_data(admin_token.clone());
+                }
+            })
             .wrap(middleware::Logger::default())            
+            .wrap_fn({
+                let metrics = metrics.clone();
+                move |req, srv| {
+                    let started_at = Instant::now();
+                    let metrics = metrics.clone();
+
+                    srv.call(req).map(move |response| {
+                        if let Ok(response) = &response {
+                            metrics.observe_response(response, started_at.elapsed());
+                        }
+                        response
+                    })
+                }
+            })
             .service(handlers::get_words_match_prefix)
             .service(handlers::get_words_match_empty_prefix)
             .service(handlers::get_words_match_empty_prefix_with_last_slash)
             .service(handlers::increase_popularity)
+            .service(handlers::insert_name)
+            .service(handlers::remove_name)
+            .service(handlers::rename_name)
+            .service(handlers::get_name)
+            .service(handlers::hit_name_resource)
+            .service(handlers::put_name)
+            .service(handlers::import_names)
+            .service(handlers::export_names)
+            .service(handlers::get_stats)
+            .service(handlers::get_metrics)
             .service(health_check)
         // .service(whatsapp_hook)
     })
     .bind(server_address)?
     .run()
-    .await
+    .await?;
+
+    //the server stops gracefully on SIGINT and SIGTERM, leaving the names untouched from now on
+    if let Some(snapshot_store) = &snapshot_store {
+        info!("Taking a last snapshot before shutting down");
+        snapshot_store.take_shared(&shared_trie, journal.as_ref().map(|journal| journal.get_ref()));
+    }
+
+    Ok(())
 }
 
 #[allow(clippy::unused_async)]
@@ -0,0 +1,217 @@
+use crate::app_error::AppError;
+use crate::trie::Trie;
+use actix_web::dev::ServiceResponse;
+use log::error;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
+
+//route label of the requests that match no handler.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+const RESULT_SIZE_BUCKETS: [f64; 8] = [0.0, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0];
+const LOCK_WAIT_BUCKETS: [f64; 10] = [
+    0.000_001, 0.000_01, 0.000_1, 0.000_5, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5,
+];
+
+// Counters and histograms exposed on /metrics, in the text format scraped by Prometheus. Requests are labelled by
+// route pattern and method, as handlers sharing a path (like the ones on /typeahead) can only be told apart by both.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    errors: IntCounterVec,
+    result_size: Histogram,
+    empty_results: IntCounter,
+    words: IntGauge,
+    lock_wait: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Metrics, prometheus::Error> {
+        let metrics = Metrics {
+            registry: Registry::new(),
+            requests: IntCounterVec::new(
+                Opts::new("typeahead_requests_total", "Requests served, by route and status"),
+                &["route", "method", "status"],
+            )?,
+            request_duration: HistogramVec::new(
+                HistogramOpts::new("typeahead_request_duration_seconds", "Time taken to serve a request, by route"),
+                &["route", "method"],
+            )?,
+            errors: IntCounterVec::new(
+                Opts::new("typeahead_errors_total", "Requests that failed, by error"),
+                &["error"],
+            )?,
+            result_size: Histogram::with_opts(
+                HistogramOpts::new("typeahead_result_size", "Names returned by a typeahead query")
+                    .buckets(RESULT_SIZE_BUCKETS.to_vec()),
+            )?,
+            empty_results: IntCounter::new(
+                "typeahead_empty_results_total",
+                "Typeahead queries that returned no name",
+            )?,
+            words: IntGauge::new("typeahead_words", "Names in the trie")?,
+            lock_wait: HistogramVec::new(
+                HistogramOpts::new("typeahead_lock_wait_seconds", "Time spent waiting for the trie's lock, by mode")
+                    .buckets(LOCK_WAIT_BUCKETS.to_vec()),
+                &["mode"],
+            )?,
+        };
+
+        metrics.registry.register(Box::new(metrics.requests.clone()))?;
+        metrics.registry.register(Box::new(metrics.request_duration.clone()))?;
+        metrics.registry.register(Box::new(metrics.errors.clone()))?;
+        metrics.registry.register(Box::new(metrics.result_size.clone()))?;
+        metrics.registry.register(Box::new(metrics.empty_results.clone()))?;
+        metrics.registry.register(Box::new(metrics.words.clone()))?;
+        metrics.registry.register(Box::new(metrics.lock_wait.clone()))?;
+
+        Ok(metrics)
+    }
+
+    //counts a served request, along with its error if it failed with one of ours.
+    pub fn observe_response<B>(&self, response: &ServiceResponse<B>, duration: Duration) {
+        let request = response.request();
+        let route = request.match_pattern();
+        let route = route.as_deref().unwrap_or(UNMATCHED_ROUTE);
+        let method = request.method().as_str();
+
+        self.requests
+            .with_label_values(&[route, method, response.status().as_str()])
+            .inc();
+        self.request_duration
+            .with_label_values(&[route, method])
+            .observe(duration.as_secs_f64());
+
+        if let Some(app_error) = response.response().error().and_then(|e| e.as_error::<AppError>()) {
+            self.errors.with_label_values(&[app_error.code()]).inc();
+        }
+    }
+
+    pub fn observe_result_size(&self, size: usize) {
+        self.result_size.observe(size as f64);
+
+        if size == 0 {
+            self.empty_results.inc();
+        }
+    }
+
+    pub fn set_words(&self, words: usize) {
+        self.words.set(i64::try_from(words).unwrap_or(i64::MAX));
+    }
+
+    pub fn encode(&self) -> Result<String, AppError> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| {
+                error!("Could not encode metrics: {}", e);
+                AppError::UnexpectedError
+            })?;
+
+        String::from_utf8(buffer).map_err(|_e| AppError::UnexpectedError)
+    }
+}
+
+//locks the trie for reading, timing the wait when there are metrics.
+pub fn read_trie<'a>(
+    shared_trie: &'a RwLock<Trie>,
+    metrics: Option<&Metrics>,
+) -> Result<RwLockReadGuard<'a, Trie>, AppError> {
+    let started_at = Instant::now();
+    let trie = shared_trie.read().map_err(|_e| AppError::UnexpectedError)?;
+
+    if let Some(metrics) = metrics {
+        metrics
+            .lock_wait
+            .with_label_values(&["read"])
+            .observe(started_at.elapsed().as_secs_f64());
+    }
+
+    Ok(trie)
+}
+
+//locks the trie for writing, timing the wait when there are metrics.
+pub fn write_trie<'a>(
+    shared_trie: &'a RwLock<Trie>,
+    metrics: Option<&Metrics>,
+) -> Result<RwLockWriteGuard<'a, Trie>, AppError> {
+    let started_at = Instant::now();
+    let trie = shared_trie.write().map_err(|_e| AppError::UnexpectedError)?;
+
+    if let Some(metrics) = metrics {
+        metrics
+            .lock_wait
+            .with_label_values(&["write"])
+            .observe(started_at.elapsed().as_secs_f64());
+    }
+
+    Ok(trie)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trie::ITrie;
+    use actix_web::{test, HttpResponse};
+
+    #[test]
+    fn t_observe_response() {
+        let metrics = Metrics::new().unwrap();
+
+        let ok_response = test::TestRequest::get().to_srv_request().into_response(HttpResponse::Ok().finish());
+        metrics.observe_response(&ok_response, Duration::from_millis(3));
+
+        let error_response: ServiceResponse = test::TestRequest::post()
+            .to_srv_request()
+            .error_response(AppError::WordDoesNotExist {
+                name: "Aar".to_string(),
+            });
+        metrics.observe_response(&error_response, Duration::from_millis(1));
+
+        let encoded = metrics.encode().unwrap();
+
+        assert!(encoded.contains("typeahead_requests_total{method=\"GET\",route=\"unmatched\",status=\"200\"} 1\n"));
+        assert!(encoded.contains("typeahead_requests_total{method=\"POST\",route=\"unmatched\",status=\"400\"} 1\n"));
+        assert!(encoded.contains("typeahead_request_duration_seconds_count{method=\"GET\",route=\"unmatched\"} 1\n"));
+        assert!(encoded.contains("typeahead_errors_total{error=\"word_does_not_exist\"} 1\n"));
+    }
+
+    #[test]
+    fn t_observe_result_size() {
+        let metrics = Metrics::new().unwrap();
+
+        metrics.observe_result_size(3);
+        metrics.observe_result_size(0);
+        metrics.set_words(42);
+
+        let encoded = metrics.encode().unwrap();
+
+        assert!(encoded.contains("typeahead_result_size_count 2\n"));
+        assert!(encoded.contains("typeahead_result_size_bucket{le=\"0\"} 1\n"));
+        assert!(encoded.contains("typeahead_empty_results_total 1\n"));
+        assert!(encoded.contains("typeahead_words 42\n"));
+    }
+
+    #[test]
+    fn t_lock_wait() {
+        let metrics = Metrics::new().unwrap();
+        let shared_trie = RwLock::new(Trie::initialize("{\"Aar\": 361}", 10).unwrap());
+
+        drop(read_trie(&shared_trie, Some(&metrics)).unwrap());
+        drop(read_trie(&shared_trie, None).unwrap());
+        write_trie(&shared_trie, Some(&metrics))
+            .unwrap()
+            .increase_popularity("Aar".to_string())
+            .unwrap();
+
+        let encoded = metrics.encode().unwrap();
+
+        assert!(encoded.contains("typeahead_lock_wait_seconds_count{mode=\"read\"} 1\n"));
+        assert!(encoded.contains("typeahead_lock_wait_seconds_count{mode=\"write\"} 1\n"));
+    }
+}
@@ -0,0 +1,56 @@
+use caseless::Caseless;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+//key used to index names in the trie. The name is decomposed before case folding so accents are folded the same way whether
+//they come precomposed or not, and composed back afterwards. E.g., "ÉLODIE", "Élodie" and "E\u{301}lodie" all become "élodie".
+pub fn normalize_key(word: &str) -> String {
+    word.nfd().default_case_fold().nfc().collect()
+}
+
+//removes the diacritics of an already normalized key, e.g. "josé" becomes "jose" and "zoë" becomes "zoe".
+//letters that are not composed with a combining mark, such as "ø" or "ł", are kept as they are.
+pub fn strip_accents(key: &str) -> String {
+    key.nfd().filter(|char| !is_combining_mark(*char)).nfc().collect()
+}
+
+//key used to place the word in the trie, according to whether accents are taken into account or not.
+pub fn index_key(word: &str, accent_insensitive: bool) -> String {
+    if accent_insensitive {
+        strip_accents(&normalize_key(word))
+    } else {
+        normalize_key(word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_normalize_key_case_folding() {
+        assert_eq!("élodie", normalize_key("ÉLODIE"));
+        assert_eq!("strasse", normalize_key("STRAßE"));
+        assert_eq!("σοφία", normalize_key("ΣΟΦΊΑ"));
+    }
+
+    #[test]
+    fn t_normalize_key_precomposed_and_decomposed() {
+        assert_eq!(normalize_key("\u{e9}lodie"), normalize_key("e\u{301}lodie"));
+        assert_eq!("\u{e9}lodie", normalize_key("E\u{301}LODIE"));
+    }
+
+    #[test]
+    fn t_normalize_key_ascii() {
+        assert_eq!("rose-marie", normalize_key("Rose-Marie"));
+        assert_eq!("mary ann", normalize_key("Mary Ann"));
+    }
+
+    #[test]
+    fn t_strip_accents() {
+        assert_eq!("jose", strip_accents("josé"));
+        assert_eq!("zoe", strip_accents("zoë"));
+        assert_eq!("elodie", strip_accents("e\u{301}lodie"));
+        assert_eq!("søren", strip_accents("søren"));
+    }
+}
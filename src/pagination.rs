@@ -0,0 +1,311 @@
+use crate::app_error::AppError;
+use crate::decay::DecayedScore;
+use crate::ranking::{Ranker, Ranking};
+use crate::trie::{MatchKind, Popularity, Trie, WordData};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::time::SystemTime;
+
+//largest page a client can ask for when MAX_RESULT_LIMIT is not set.
+pub const DEFAULT_MAX_RESULT_LIMIT: usize = 100;
+
+// Last name of a page along with where it ranked, handed to the client as an opaque string. The next page starts right
+// after that rank instead of at a position, so names moving around because of popularity changes are neither returned
+// twice nor skipped, except for the ones that moved across the cursor. The rank only means something for the prefix and
+// the ranking of the page, so they go along with it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    prefix_key: String,
+    ranking: Ranking,
+    match_kind: MatchKind,
+    popularity: Popularity,
+    name: String,
+    //the decayed score of the name as of its last hit before the page, None without hits.
+    decayed_score: Option<DecayedScore>,
+}
+
+impl Cursor {
+    fn new(trie: &Trie, prefix_key: String, ranking: Ranking, match_kind: MatchKind, word_data: &WordData) -> Cursor {
+        Cursor {
+            prefix_key,
+            ranking,
+            match_kind,
+            popularity: word_data.popularity,
+            name: word_data.word.clone(),
+            decayed_score: trie.decayed_scores.get(&word_data.word).copied(),
+        }
+    }
+
+    //every ranking only looks at the name, its popularity and its decayed score, so the cursor can be compared as if it was
+    //the name itself. Its decayed score is the one kept in the cursor, decayed as of now like the others, so hits on the
+    //name after the page don't move the cursor.
+    fn compare(&self, ranking: &Ranking, ranker: &dyn Ranker, match_kind: MatchKind, word_data: &WordData) -> Ordering {
+        let cursor_word_data = WordData::new(self.name.clone(), self.popularity);
+
+        ranking.compare_with_decayed_score(
+            ranker,
+            (self.match_kind, &cursor_word_data, self.decayed_score.as_ref()),
+            (match_kind, word_data),
+        )
+    }
+
+    //hex of the JSON, so clients don't come to rely on what is inside.
+    pub fn encode(&self) -> String {
+        serde_json::to_vec(self)
+            .unwrap_or_default()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    pub fn decode(cursor: &str) -> Result<Cursor, AppError> {
+        if !cursor.is_ascii() || !cursor.len().is_multiple_of(2) {
+            return Err(AppError::InvalidCursor);
+        }
+
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&cursor[index..index + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_e| AppError::InvalidCursor)?;
+
+        serde_json::from_slice(&bytes).map_err(|_e| AppError::InvalidCursor)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageRequest {
+    pub cursor: Option<Cursor>,
+    //names skipped after the cursor, or from the start without one.
+    pub offset: usize,
+    pub limit: usize,
+    pub ranking: Ranking,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Page {
+    pub results: Vec<WordData>,
+    //None on the last page.
+    pub next_cursor: Option<String>,
+}
+
+//a page of the suggestions for the prefix, ranked the same way as them.
+pub fn page(trie: &Trie, prefix: String, request: &PageRequest) -> Result<Page, AppError> {
+    let end = request.offset.saturating_add(request.limit);
+    let prefix_key = trie.trie_key(&prefix);
+
+    //a cursor of another prefix or ranking would start the page at a rank that doesn't mean anything for this one
+    if let Some(cursor) = &request.cursor {
+        if cursor.prefix_key != prefix_key || cursor.ranking != request.ranking {
+            return Err(AppError::InvalidCursor);
+        }
+    }
+
+    //the cached suggestions are enough for the first pages, as long as they hold one more name telling if there are others
+    if request.cursor.is_none() && request.ranking.is_cached() && end < usize::from(trie.suggestion_number) {
+        let words = trie.get_ranked_typeahead_words(prefix.clone(), &request.ranking)?;
+        let has_more = words.len() > end;
+        let results: Vec<WordData> = words.into_iter().skip(request.offset).take(request.limit).collect();
+
+        let next_cursor = match results.last() {
+            Some(word_data) if has_more => {
+                let match_kind = match_kind(trie, &prefix_key, word_data);
+                Some(Cursor::new(trie, prefix_key, request.ranking, match_kind, word_data).encode())
+            }
+            _ => None,
+        };
+
+        return Ok(Page { results, next_cursor });
+    }
+
+    let now = SystemTime::now();
+    let ranker = request.ranking.ranker(&trie.decayed_scores, now);
+    let mut ranked_words = trie.words_matching_prefix(&prefix);
+
+    //only the names after the cursor and up to the end of the page are sorted, not every name matching the prefix
+    if let Some(cursor) = &request.cursor {
+        ranked_words.retain(|(match_kind, word_data)| {
+            cursor.compare(&request.ranking, ranker.as_ref(), *match_kind, word_data) == Ordering::Less
+        });
+    }
+    let has_more = end < ranked_words.len();
+    request.ranking.keep_first(ranker.as_ref(), &mut ranked_words, end);
+
+    let page_words: Vec<(MatchKind, &WordData)> = ranked_words.into_iter().skip(request.offset).collect();
+
+    let next_cursor = match page_words.last() {
+        Some((match_kind, word_data)) if has_more => {
+            Some(Cursor::new(trie, prefix_key, request.ranking, *match_kind, word_data).encode())
+        }
+        _ => None,
+    };
+
+    Ok(Page {
+        results: page_words.into_iter().map(|(_, word_data)| word_data.clone()).collect(),
+        next_cursor,
+    })
+}
+
+fn match_kind(trie: &Trie, prefix_key: &str, word_data: &WordData) -> MatchKind {
+    let key = trie.trie_key(&word_data.word);
+
+    if key == prefix_key {
+        MatchKind::Exact
+    } else if key.starts_with(prefix_key) {
+        MatchKind::Prefix
+    } else {
+        MatchKind::InnerWord
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranking::RankingStrategy;
+    use crate::trie::ITrie;
+
+    fn get_trie() -> Trie {
+        Trie::initialize(
+            "{\"Ann\": 10, \"Anna\": 50, \"Annie\": 50, \"Anne\": 20, \"Mary Ann\": 70, \"Ann-Marie\": 5, \"Rose-Anna\": 1}",
+            3,
+        )
+        .unwrap()
+    }
+
+    fn names(page: &Page) -> Vec<&str> {
+        page.results.iter().map(|word_data| word_data.word.as_str()).collect()
+    }
+
+    fn request(cursor: Option<&str>, offset: usize, limit: usize) -> PageRequest {
+        PageRequest {
+            cursor: cursor.map(|cursor| Cursor::decode(cursor).unwrap()),
+            offset,
+            limit,
+            ranking: Ranking::default(),
+        }
+    }
+
+    #[test]
+    fn t_ranked_words_with_prefix() {
+        let trie = get_trie();
+
+        let ranked_words: Vec<(MatchKind, &str)> = trie
+            .ranked_words_with_prefix("ANN", &Ranking::default(), SystemTime::now(), usize::MAX)
+            .into_iter()
+            .map(|(match_kind, word_data)| (match_kind, word_data.word.as_str()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (MatchKind::Exact, "Ann"),
+                (MatchKind::Prefix, "Anna"),
+                (MatchKind::Prefix, "Annie"),
+                (MatchKind::Prefix, "Anne"),
+                (MatchKind::Prefix, "Ann-Marie"),
+                (MatchKind::InnerWord, "Mary Ann"),
+                (MatchKind::InnerWord, "Rose-Anna"),
+            ],
+            ranked_words
+        );
+        assert!(trie.ranked_words_with_prefix("x", &Ranking::default(), SystemTime::now(), usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn t_page_with_cursor() {
+        let trie = get_trie();
+
+        let first_page = page(&trie, "ann".to_string(), &request(None, 0, 3)).unwrap();
+        assert_eq!(vec!["Ann", "Anna", "Annie"], names(&first_page));
+
+        let second_page = page(&trie, "ann".to_string(), &request(first_page.next_cursor.as_deref(), 0, 3)).unwrap();
+        assert_eq!(vec!["Anne", "Ann-Marie", "Mary Ann"], names(&second_page));
+
+        let last_page = page(&trie, "ann".to_string(), &request(second_page.next_cursor.as_deref(), 0, 3)).unwrap();
+        assert_eq!(vec!["Rose-Anna"], names(&last_page));
+        assert_eq!(None, last_page.next_cursor);
+
+        //the cached suggestions give the same first pages
+        let cached_page = page(&trie, "ann".to_string(), &request(None, 1, 1)).unwrap();
+        assert_eq!(vec!["Anna"], names(&cached_page));
+
+        let next_page = page(&trie, "ann".to_string(), &request(cached_page.next_cursor.as_deref(), 1, 2)).unwrap();
+        assert_eq!(vec!["Anne", "Ann-Marie"], names(&next_page));
+    }
+
+    #[test]
+    fn t_page_stays_stable_while_popularity_changes() {
+        let mut trie = get_trie();
+
+        let first_page = page(&trie, "ann".to_string(), &request(None, 0, 3)).unwrap();
+
+        //"Anna" was already returned, so it is not again after going first
+        trie.add_popularity("Anna".to_string(), 100).unwrap();
+        //"Ann-Marie" goes over "Anne", both still after the cursor
+        trie.add_popularity("Ann-Marie".to_string(), 20).unwrap();
+
+        let second_page = page(&trie, "ann".to_string(), &request(first_page.next_cursor.as_deref(), 0, 10)).unwrap();
+        assert_eq!(vec!["Ann-Marie", "Anne", "Mary Ann", "Rose-Anna"], names(&second_page));
+    }
+
+    #[test]
+    fn t_page_with_decayed_cursor_stays_stable_while_it_gets_hits() {
+        let mut trie = get_trie();
+        let now = SystemTime::now();
+        for name in ["Anne", "Anne", "Anne", "Annie", "Annie", "Anna"] {
+            trie.hit(name.to_string(), now).unwrap();
+        }
+
+        for strategy in [RankingStrategy::Decayed, RankingStrategy::Blended] {
+            let ranking = Ranking { strategy, decay_weight: 100, ..Ranking::default() };
+            let first_page = page(&trie, "ann".to_string(), &PageRequest { ranking, ..request(None, 0, 3) }).unwrap();
+            assert_eq!(vec!["Ann", "Anne", "Annie"], names(&first_page), "{:?}", strategy);
+
+            //"Annie" going over "Anne" doesn't bring "Anne" back, as the cursor keeps the score "Annie" had
+            let mut trie = trie.clone();
+            for _ in 0..5 {
+                trie.hit("Annie".to_string(), SystemTime::now()).unwrap();
+            }
+
+            let second_page =
+                page(&trie, "ann".to_string(), &PageRequest { ranking, ..request(first_page.next_cursor.as_deref(), 0, 3) })
+                    .unwrap();
+            assert_eq!(vec!["Anna", "Ann-Marie", "Mary Ann"], names(&second_page), "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn t_page_with_cursor_of_other_request() {
+        let trie = get_trie();
+
+        let first_page = page(&trie, "ann".to_string(), &request(None, 0, 3)).unwrap();
+
+        //the same prefix in other casing is the same request
+        let second_page = page(&trie, "ANN".to_string(), &request(first_page.next_cursor.as_deref(), 0, 3)).unwrap();
+        assert_eq!(vec!["Anne", "Ann-Marie", "Mary Ann"], names(&second_page));
+
+        let other_prefix = page(&trie, "anna".to_string(), &request(first_page.next_cursor.as_deref(), 0, 3));
+        assert_eq!(Err(AppError::InvalidCursor), other_prefix);
+
+        let other_rankings = [
+            Ranking { strategy: RankingStrategy::Alphabetical, ..Ranking::default() },
+            Ranking { exact_match_first: false, ..Ranking::default() },
+            Ranking { decay_weight: 10, ..Ranking::default() },
+        ];
+        for ranking in other_rankings {
+            let other_request = PageRequest { ranking, ..request(first_page.next_cursor.as_deref(), 0, 3) };
+
+            assert_eq!(Err(AppError::InvalidCursor), page(&trie, "ann".to_string(), &other_request), "{:?}", ranking);
+        }
+    }
+
+    #[test]
+    fn t_decode_invalid_cursor() {
+        let trie = get_trie();
+        let cursor = Cursor::new(&trie, "zoe".to_string(), Ranking::default(), MatchKind::Prefix, &WordData::new("Zoë".to_string(), 3));
+        assert_eq!(Ok(cursor.clone()), Cursor::decode(&cursor.encode()));
+
+        for invalid_cursor in ["", "7", "zz", "7b7d", "éé"] {
+            assert_eq!(Err(AppError::InvalidCursor), Cursor::decode(invalid_cursor), "{}", invalid_cursor);
+        }
+    }
+}
@@ -0,0 +1,720 @@
+// Path compressed layout of the trie: every chain of nodes with a single child and no words is merged into one node labelled with
+// the whole chain, and children live inline in a vector sorted by their first letter instead of being boxed in a hash map.
+// It implements the same ITrie operations as Trie and answers exactly like it, see t_layout_comparison_names_json for how both
+// layouts compare on names.json. Experimental: the server can't be set to use it, so it is only built for the tests.
+
+use crate::app_error::AppError;
+use crate::normalization::{index_key, normalize_key};
+use crate::ranking::PopularityRanker;
+use crate::trie::{
+    inner_word_keys, merge_suggestions, promote_word, rank_top_words, word_does_not_exist, words_heap_size,
+    ITrie, OrderedEntries, Popularity, TrieOptions, WordData,
+};
+
+#[derive(Debug, Clone)]
+pub struct RadixTrie {
+    pub root: RadixNode,
+    pub suggestion_number: u8,
+    pub accent_insensitive: bool,
+}
+
+impl RadixTrie {
+    fn with_options(options: TrieOptions) -> RadixTrie {
+        RadixTrie {
+            root: RadixNode::new(String::new()),
+            suggestion_number: options.suggestion_number,
+            accent_insensitive: options.accent_insensitive,
+        }
+    }
+
+    fn trie_key(&self, word: &str) -> String {
+        index_key(word, self.accent_insensitive)
+    }
+
+    pub fn rebuild_top_words(&mut self) {
+        RadixTrie::build_top_words(&mut self.root, self.suggestion_number.into());
+    }
+
+    fn build_top_words(node: &mut RadixNode, suggestion_number: usize) {
+        for child_node in &mut node.children {
+            RadixTrie::build_top_words(child_node, suggestion_number);
+        }
+
+        node.refresh_top_words(suggestion_number);
+    }
+
+    //inserts the word, and its inner words, without touching the cached suggestions.
+    fn put_word(&mut self, word: String, popularity: Popularity) {
+        let key = self.trie_key(&word);
+        let word_data = WordData::new(word, popularity);
+
+        for inner_word_key in inner_word_keys(&key) {
+            RadixTrie::get_or_insert_node(&mut self.root, inner_word_key)
+                .set_inner_word(word_data.clone());
+        }
+
+        RadixTrie::get_or_insert_node(&mut self.root, &key).set_word(word_data);
+    }
+
+    fn get_or_insert_node<'a>(mut node: &'a mut RadixNode, mut key: &str) -> &'a mut RadixNode {
+        while !key.is_empty() {
+            let (index, matched_len) = node.child_for_insertion(key);
+            key = &key[matched_len..];
+            node = &mut node.children[index];
+        }
+
+        node
+    }
+
+    fn insert_into_node(
+        node: &mut RadixNode,
+        key: &str,
+        word_data: WordData,
+        suggestion_number: usize,
+        set_word: fn(&mut RadixNode, WordData),
+    ) {
+        if key.is_empty() {
+            set_word(node, word_data);
+        } else {
+            let (index, matched_len) = node.child_for_insertion(key);
+
+            RadixTrie::insert_into_node(
+                &mut node.children[index],
+                &key[matched_len..],
+                word_data,
+                suggestion_number,
+                set_word,
+            );
+        }
+
+        node.refresh_top_words(suggestion_number);
+    }
+
+    fn remove_from_node(
+        node: &mut RadixNode,
+        key: &str,
+        word: &str,
+        suggestion_number: usize,
+        take_word: fn(&mut RadixNode, &str) -> Option<WordData>,
+    ) -> Option<WordData> {
+        let removed_word_data = if key.is_empty() {
+            take_word(node, word)
+        } else {
+            let index = node.child_index(key).ok()?;
+            let child_node = &mut node.children[index];
+
+            if !key.starts_with(&child_node.label) {
+                return None;
+            }
+
+            let remaining_key = &key[child_node.label.len()..];
+            let removed_word_data =
+                RadixTrie::remove_from_node(child_node, remaining_key, word, suggestion_number, take_word);
+
+            //keeps the path compressed: empty nodes go away and a node left with a single child and no words absorbs it
+            if child_node.is_empty() {
+                node.children.remove(index);
+            } else {
+                child_node.merge_single_child();
+            }
+
+            removed_word_data
+        };
+
+        if removed_word_data.is_some() {
+            node.refresh_top_words(suggestion_number);
+        }
+
+        removed_word_data
+    }
+
+    fn increase_popularity_in_node(
+        node: &mut RadixNode,
+        key: &str,
+        word: &str,
+        suggestion_number: usize,
+    ) -> Result<WordData, AppError> {
+        let updated_word_data = if key.is_empty() {
+            let word_data = node.find_word_mut(word).ok_or_else(|| word_does_not_exist(word))?;
+            word_data.popularity = word_data
+                .popularity
+                .checked_add(1)
+                .ok_or(AppError::PopularityOverflow)?;
+            word_data.clone()
+        } else {
+            let child_node = node
+                .child_mut(key)
+                .filter(|child_node| key.starts_with(&child_node.label))
+                .ok_or_else(|| word_does_not_exist(word))?;
+            let remaining_key = &key[child_node.label.len()..];
+
+            RadixTrie::increase_popularity_in_node(child_node, remaining_key, word, suggestion_number)?
+        };
+
+        promote_word(&mut node.top_words, &updated_word_data, suggestion_number);
+
+        Ok(updated_word_data)
+    }
+
+    fn promote_inner_word_in_node(
+        node: &mut RadixNode,
+        key: &str,
+        word_data: &WordData,
+        suggestion_number: usize,
+    ) {
+        if key.is_empty() {
+            if let Some(inner_word) = node
+                .inner_words
+                .iter_mut()
+                .find(|inner_word| inner_word.word == word_data.word)
+            {
+                *inner_word = word_data.clone();
+            }
+        } else if let Some(child_node) = node
+            .child_mut(key)
+            .filter(|child_node| key.starts_with(&child_node.label))
+        {
+            let remaining_key = &key[child_node.label.len()..];
+
+            RadixTrie::promote_inner_word_in_node(child_node, remaining_key, word_data, suggestion_number);
+        }
+
+        promote_word(&mut node.top_inner_words, word_data, suggestion_number);
+    }
+
+    //same as Trie::from_entries, a later name replaces an earlier one with the same key.
+    pub fn from_entries(entries: impl IntoIterator<Item = (String, Popularity)>, options: TrieOptions) -> RadixTrie {
+        let mut trie = RadixTrie::with_options(options);
+
+        for (word, popularity) in entries {
+            trie.put_word(word, popularity);
+        }
+
+        trie.rebuild_top_words();
+
+        trie
+    }
+}
+
+impl ITrie for RadixTrie {
+    fn initialize(file_content: &str, options: impl Into<TrieOptions>) -> Result<RadixTrie, AppError> {
+        //read in the order of the file, so that names sharing a node always end up in the same order
+        let OrderedEntries(entries) = serde_json::from_str(file_content).map_err(|_e| AppError::InvalidFileContent)?;
+
+        Ok(RadixTrie::from_entries(entries, options.into()))
+    }
+
+    fn insert_word(&mut self, word: String, popularity: Popularity) -> Result<(), AppError> {
+        let key = self.trie_key(&word);
+        let suggestion_number = self.suggestion_number.into();
+        let word_data = WordData::new(word, popularity);
+
+        for inner_word_key in inner_word_keys(&key) {
+            RadixTrie::insert_into_node(
+                &mut self.root,
+                inner_word_key,
+                word_data.clone(),
+                suggestion_number,
+                RadixNode::set_inner_word,
+            );
+        }
+
+        RadixTrie::insert_into_node(
+            &mut self.root,
+            &key,
+            word_data,
+            suggestion_number,
+            RadixNode::set_word,
+        );
+
+        Ok(())
+    }
+
+    fn remove_word(&mut self, word: String) -> Result<WordData, AppError> {
+        let key = self.trie_key(&word);
+        let suggestion_number = self.suggestion_number.into();
+
+        let removed_word_data =
+            RadixTrie::remove_from_node(&mut self.root, &key, &word, suggestion_number, RadixNode::take_word)
+                .ok_or_else(|| word_does_not_exist(&word))?;
+
+        for inner_word_key in inner_word_keys(&self.trie_key(&removed_word_data.word)) {
+            RadixTrie::remove_from_node(
+                &mut self.root,
+                inner_word_key,
+                &removed_word_data.word,
+                suggestion_number,
+                RadixNode::take_inner_word,
+            );
+        }
+
+        Ok(removed_word_data)
+    }
+
+    fn increase_popularity(&mut self, word: String) -> Result<WordData, AppError> {
+        let key = self.trie_key(&word);
+        let suggestion_number = self.suggestion_number.into();
+
+        let updated_word_data =
+            RadixTrie::increase_popularity_in_node(&mut self.root, &key, &word, suggestion_number)?;
+
+        for inner_word_key in inner_word_keys(&self.trie_key(&updated_word_data.word)) {
+            RadixTrie::promote_inner_word_in_node(
+                &mut self.root,
+                inner_word_key,
+                &updated_word_data,
+                suggestion_number,
+            );
+        }
+
+        Ok(updated_word_data)
+    }
+
+    fn get_typeahead_words(&self, prefix: String) -> Result<Vec<WordData>, AppError> {
+        let prefix = self.trie_key(&prefix);
+        let mut node = &self.root;
+        let mut remaining_prefix = prefix.as_str();
+
+        //the prefix either ends exactly at a node or in the middle of a node's label, and then no word matches it exactly.
+        let is_exact_match = loop {
+            if remaining_prefix.is_empty() {
+                break true;
+            }
+
+            match node.child(remaining_prefix) {
+                Some(child_node) if remaining_prefix.starts_with(&child_node.label) => {
+                    remaining_prefix = &remaining_prefix[child_node.label.len()..];
+                    node = child_node;
+                }
+                Some(child_node) if child_node.label.starts_with(remaining_prefix) => {
+                    node = child_node;
+                    break false;
+                }
+                //if there is not a single word that starts with the prefix.
+                _ => return Ok(Vec::new()),
+            }
+        };
+
+        let exact_words: &[WordData] = if is_exact_match { &node.words } else { &[] };
+
+        Ok(merge_suggestions(
+            &PopularityRanker,
+            exact_words.iter(),
+            &node.top_words,
+            &node.top_inner_words,
+            self.suggestion_number.into(),
+        ))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RadixNode {
+    //letters from the parent to this node, never empty except on the root.
+    pub label: String,
+    //sorted by the first letter of their labels, which is unique among siblings.
+    pub children: Vec<RadixNode>,
+    //names whose key ends at this node. More than one only when several names share the same key.
+    pub words: Vec<WordData>,
+    pub inner_words: Vec<WordData>,
+    pub top_words: Vec<WordData>,
+    pub top_inner_words: Vec<WordData>,
+}
+
+impl RadixNode {
+    fn new(label: String) -> RadixNode {
+        RadixNode {
+            label,
+            children: Vec::new(),
+            words: Vec::new(),
+            inner_words: Vec::new(),
+            top_words: Vec::new(),
+            top_inner_words: Vec::new(),
+        }
+    }
+
+    fn first_char(&self) -> Option<char> {
+        self.label.chars().next()
+    }
+
+    fn child_index(&self, key: &str) -> Result<usize, usize> {
+        let first_char = key.chars().next();
+
+        self.children
+            .binary_search_by(|child_node| child_node.first_char().cmp(&first_char))
+    }
+
+    //child whose label starts with the same letter as the key.
+    fn child(&self, key: &str) -> Option<&RadixNode> {
+        self.child_index(key).ok().map(|index| &self.children[index])
+    }
+
+    fn child_mut(&mut self, key: &str) -> Option<&mut RadixNode> {
+        self.child_index(key).ok().map(move |index| &mut self.children[index])
+    }
+
+    //finds or creates the child the key goes through, splitting its label when the key leaves it halfway.
+    //returns the index of the child and how many bytes of the key its label matched.
+    fn child_for_insertion(&mut self, key: &str) -> (usize, usize) {
+        match self.child_index(key) {
+            Ok(index) => {
+                let child_node = &mut self.children[index];
+                let matched_len = common_prefix_len(&child_node.label, key);
+
+                if matched_len < child_node.label.len() {
+                    child_node.split(matched_len);
+                }
+
+                (index, matched_len)
+            }
+            Err(index) => {
+                self.children.insert(index, RadixNode::new(key.to_string()));
+
+                (index, key.len())
+            }
+        }
+    }
+
+    //moves everything after the first `at` bytes of the label to a new single child.
+    fn split(&mut self, at: usize) {
+        let mut lower_node = RadixNode::new(self.label.split_off(at));
+        lower_node.children = std::mem::take(&mut self.children);
+        lower_node.words = std::mem::take(&mut self.words);
+        lower_node.inner_words = std::mem::take(&mut self.inner_words);
+        lower_node.top_words = self.top_words.clone();
+        lower_node.top_inner_words = self.top_inner_words.clone();
+
+        self.label.shrink_to_fit();
+        self.children.push(lower_node);
+    }
+
+    fn set_word(&mut self, word_data: WordData) {
+        set_word_by_key(&mut self.words, word_data);
+    }
+
+    fn set_inner_word(&mut self, word_data: WordData) {
+        set_word_by_key(&mut self.inner_words, word_data);
+    }
+
+    fn take_word(&mut self, word: &str) -> Option<WordData> {
+        let key = normalize_key(word);
+        let index = self
+            .words
+            .iter()
+            .position(|word_data| normalize_key(&word_data.word) == key)?;
+
+        Some(self.words.remove(index))
+    }
+
+    fn take_inner_word(&mut self, word: &str) -> Option<WordData> {
+        let index = self
+            .inner_words
+            .iter()
+            .position(|inner_word| inner_word.word == word)?;
+
+        Some(self.inner_words.remove(index))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.children.is_empty() && self.words.is_empty() && self.inner_words.is_empty()
+    }
+
+    //the opposite of split, for a node that was only kept for the words it no longer has.
+    fn merge_single_child(&mut self) {
+        if self.children.len() != 1 || !self.words.is_empty() || !self.inner_words.is_empty() {
+            return;
+        }
+
+        if let Some(child_node) = self.children.pop() {
+            self.label.push_str(&child_node.label);
+            self.children = child_node.children;
+            self.words = child_node.words;
+            self.inner_words = child_node.inner_words;
+            self.top_words = child_node.top_words;
+            self.top_inner_words = child_node.top_inner_words;
+        }
+    }
+
+    fn find_word_mut(&mut self, word: &str) -> Option<&mut WordData> {
+        if self.words.len() == 1 {
+            return self.words.first_mut();
+        }
+
+        let key = normalize_key(word);
+
+        self.words
+            .iter_mut()
+            .find(|word_data| normalize_key(&word_data.word) == key)
+    }
+
+    fn refresh_top_words(&mut self, suggestion_number: usize) {
+        self.top_words = rank_top_words(
+            self.words
+                .iter()
+                .chain(self.children.iter().flat_map(|child_node| &child_node.top_words)),
+            suggestion_number,
+        );
+        self.top_inner_words = rank_top_words(
+            self.inner_words
+                .iter()
+                .chain(self.children.iter().flat_map(|child_node| &child_node.top_inner_words)),
+            suggestion_number,
+        );
+    }
+
+    //approximate amount of heap memory held by the node and its subtree.
+    pub fn heap_size(&self) -> usize {
+        self.label.capacity()
+            + self.children.capacity() * size_of::<RadixNode>()
+            + self.children.iter().map(RadixNode::heap_size).sum::<usize>()
+            + words_heap_size(&self.words)
+            + words_heap_size(&self.inner_words)
+            + words_heap_size(&self.top_words)
+            + words_heap_size(&self.top_inner_words)
+    }
+
+    pub fn node_count(&self) -> usize {
+        1 + self.children.iter().map(RadixNode::node_count).sum::<usize>()
+    }
+}
+
+//replaces the name with the same casing insensitive key, or adds it.
+fn set_word_by_key(words: &mut Vec<WordData>, word_data: WordData) {
+    let key = normalize_key(&word_data.word);
+
+    match words
+        .iter_mut()
+        .find(|current| normalize_key(&current.word) == key)
+    {
+        Some(current) => *current = word_data,
+        None => words.push(word_data),
+    }
+}
+
+//length in bytes of the longest common prefix of both strings.
+fn common_prefix_len(one: &str, two: &str) -> usize {
+    one.chars()
+        .zip(two.chars())
+        .take_while(|(char_one, char_two)| char_one == char_two)
+        .map(|(char_one, _)| char_one.len_utf8())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trie::{Node, Trie};
+    use std::fs;
+    use std::time::Instant;
+
+    fn get_default_trie() -> RadixTrie {
+        let file_content =
+            "{\"A-b\": 23, \"Aar\":361,\"Aari\":151,\"Aba\":608,\"Abag\":704, \"Abe\": 300, \"Ba\": 5, \"Bah\": 5, \"Be\": 50, \"Bc\": 50}";
+        RadixTrie::initialize(file_content, 10).unwrap()
+    }
+
+    fn trie_node_count(node: &Node) -> usize {
+        1 + node.children.values().map(|child_node| trie_node_count(child_node)).sum::<usize>()
+    }
+
+    fn labels(node: &RadixNode) -> Vec<&str> {
+        node.children.iter().map(|child_node| child_node.label.as_str()).collect()
+    }
+
+    #[test]
+    fn t_initialize_compresses_paths() {
+        let trie = get_default_trie();
+
+        assert_eq!(vec!["a", "b"], labels(&trie.root));
+        assert_eq!(vec!["-b", "ar", "b"], labels(&trie.root.children[0]));
+        assert_eq!(vec!["a", "e"], labels(&trie.root.children[0].children[2]));
+        assert_eq!(vec!["g"], labels(&trie.root.children[0].children[2].children[0]));
+        assert_eq!(vec!["a", "c", "e"], labels(&trie.root.children[1]));
+    }
+
+    #[test]
+    fn t_insert_word_splits_label() {
+        let mut trie = get_default_trie();
+        trie.insert_word("Aaron".to_string(), 900).unwrap();
+        trie.insert_word("Aa".to_string(), 1).unwrap();
+
+        let a_node = &trie.root.children[0];
+        assert_eq!(vec!["-b", "a", "b"], labels(a_node));
+        assert_eq!(vec![WordData::new("Aa".to_string(), 1)], a_node.children[1].words);
+        assert_eq!(vec!["r"], labels(&a_node.children[1]));
+        assert_eq!(vec!["i", "on"], labels(&a_node.children[1].children[0]));
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aa".to_string(), 1),
+            WordData::new("Aaron".to_string(), 900),
+            WordData::new("Aar".to_string(), 361),
+            WordData::new("Aari".to_string(), 151),
+        ];
+
+        assert_eq!(expected_words, trie.get_typeahead_words("aa".to_string()).unwrap());
+    }
+
+    #[test]
+    fn t_get_typeahead_words_prefix_ends_inside_label() {
+        let trie = get_default_trie();
+
+        //"aa" is the middle of the "ar" label, so "Aar" is not an exact match of "aa"
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aar".to_string(), 361),
+            WordData::new("Aari".to_string(), 151),
+        ];
+
+        assert_eq!(expected_words, trie.get_typeahead_words("AA".to_string()).unwrap());
+        assert_eq!(
+            vec![WordData::new("A-b".to_string(), 23)],
+            trie.get_typeahead_words("A-".to_string()).unwrap()
+        );
+        assert_eq!(Vec::<WordData>::new(), trie.get_typeahead_words("Aab".to_string()).unwrap());
+    }
+
+    #[test]
+    fn t_increase_popularity() {
+        let mut trie = get_default_trie();
+        trie.suggestion_number = 2;
+
+        assert_eq!(
+            WordData::new("Be".to_string(), 51),
+            trie.increase_popularity("BE".to_string()).unwrap()
+        );
+        assert_eq!(
+            word_does_not_exist("Aa"),
+            trie.increase_popularity("Aa".to_string()).unwrap_err()
+        );
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Be".to_string(), 51),
+            WordData::new("Bc".to_string(), 50),
+        ];
+
+        assert_eq!(expected_words, trie.get_typeahead_words("b".to_string()).unwrap());
+    }
+
+    #[test]
+    fn t_increase_popularity_overflow() {
+        let mut trie = RadixTrie::initialize("{\"Aar\": 18446744073709551615}", 10).unwrap();
+
+        assert_eq!(
+            AppError::PopularityOverflow,
+            trie.increase_popularity("Aar".to_string()).unwrap_err()
+        );
+        assert_eq!(
+            vec![WordData::new("Aar".to_string(), u64::MAX)],
+            trie.get_typeahead_words("a".to_string()).unwrap()
+        );
+    }
+
+    #[test]
+    fn t_remove_word_merges_labels() {
+        let mut trie = get_default_trie();
+        trie.insert_word("Aaron".to_string(), 900).unwrap();
+
+        //"Aar" and "Aari" gone, "Aaron" is left alone below "a"
+        trie.remove_word("Aar".to_string()).unwrap();
+        trie.remove_word("AARI".to_string()).unwrap();
+
+        assert_eq!(vec!["-b", "aron", "b"], labels(&trie.root.children[0]));
+
+        //"Bah" was the only child of "Ba"
+        trie.remove_word("Ba".to_string()).unwrap();
+        assert_eq!(vec!["ah", "c", "e"], labels(&trie.root.children[1]));
+
+        trie.remove_word("Bah".to_string()).unwrap();
+        trie.remove_word("Bc".to_string()).unwrap();
+        trie.remove_word("Be".to_string()).unwrap();
+        //"b" is still the inner word of "A-b"
+        assert_eq!(vec!["a", "b"], labels(&trie.root));
+        assert!(trie.root.children[1].children.is_empty());
+
+        assert_eq!(
+            word_does_not_exist("Aa"),
+            trie.remove_word("Aa".to_string()).unwrap_err()
+        );
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aaron".to_string(), 900),
+            WordData::new("Abag".to_string(), 704),
+            WordData::new("Aba".to_string(), 608),
+            WordData::new("Abe".to_string(), 300),
+            WordData::new("A-b".to_string(), 23),
+        ];
+
+        assert_eq!(expected_words, trie.get_typeahead_words("a".to_string()).unwrap());
+    }
+
+    #[test]
+    fn t_layout_comparison_names_json() {
+        let file_content = fs::read_to_string("names.json").unwrap();
+        let mut trie = Trie::initialize(&file_content, 10).unwrap();
+        let mut radix_trie = RadixTrie::initialize(&file_content, 10).unwrap();
+
+        let trie_size = size_of::<Node>() + trie.root.heap_size();
+        let radix_trie_size = size_of::<RadixNode>() + radix_trie.root.heap_size();
+        println!(
+            "trie: {} nodes, ~{} bytes. radix trie: {} nodes, ~{} bytes",
+            trie_node_count(&trie.root),
+            trie_size,
+            radix_trie.root.node_count(),
+            radix_trie_size
+        );
+
+        assert!(radix_trie.root.node_count() < trie_node_count(&trie.root));
+        assert!(radix_trie_size < trie_size);
+
+        //every prefix of every name up to 4 letters, plus some that match nothing
+        let mut prefixes: Vec<String> = trie
+            .all_words()
+            .into_iter()
+            .map(|word_data| &word_data.word)
+            .flat_map(|word| (0..=4).map(move |len| word.chars().take(len).collect::<String>()))
+            .collect();
+        prefixes.extend(["zzz", "Brazil", "ann", "MARIE"].iter().map(|prefix| prefix.to_string()));
+        prefixes.sort();
+        prefixes.dedup();
+
+        for prefix in &prefixes {
+            assert_eq!(
+                trie.get_typeahead_words(prefix.clone()).unwrap(),
+                radix_trie.get_typeahead_words(prefix.clone()).unwrap(),
+                "prefix: {}",
+                prefix
+            );
+        }
+
+        let start = Instant::now();
+        for prefix in &prefixes {
+            trie.get_typeahead_words(prefix.clone()).unwrap();
+        }
+        let trie_lookup_time = start.elapsed();
+
+        let start = Instant::now();
+        for prefix in &prefixes {
+            radix_trie.get_typeahead_words(prefix.clone()).unwrap();
+        }
+        let radix_trie_lookup_time = start.elapsed();
+
+        println!(
+            "{} lookups. trie: {:?}, radix trie: {:?}",
+            prefixes.len(),
+            trie_lookup_time,
+            radix_trie_lookup_time
+        );
+
+        for word in ["Abagael", "anne-marie", "ZSA ZSA", "Brazil"] {
+            assert_eq!(
+                trie.increase_popularity(word.to_string()),
+                radix_trie.increase_popularity(word.to_string())
+            );
+        }
+
+        for prefix in ["ab", "anne", "marie", "zsa"] {
+            assert_eq!(
+                trie.get_typeahead_words(prefix.to_string()).unwrap(),
+                radix_trie.get_typeahead_words(prefix.to_string()).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,565 @@
+use crate::decay::{DecayedScore, DecayedScores};
+use crate::normalization::normalize_key;
+use crate::trie::{compare_word_data, MatchKind, WordData};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::time::SystemTime;
+
+// Orders the names matching the same prefix. Names are first grouped by how they match it, see `Ranking`, so a ranker only
+// decides the order inside each group.
+pub trait Ranker {
+    fn compare(&self, word_data_one: &WordData, word_data_two: &WordData) -> Ordering;
+
+    //whether the ranker orders by the normalized names, which are then worked out once per name before sorting many of
+    //them, instead of twice on every comparison.
+    fn uses_keys(&self) -> bool {
+        false
+    }
+
+    //same as compare, given the normalized names.
+    fn compare_with_keys(&self, one: (&WordData, &str), two: (&WordData, &str)) -> Ordering {
+        self.compare(one.0, two.0)
+    }
+
+    //whether the ranker orders by a score worked out from the decayed scores, which is then worked out once per name before
+    //sorting many of them, instead of twice on every comparison.
+    fn uses_scores(&self) -> bool {
+        false
+    }
+
+    //the score compare_with_scores is given for the name.
+    fn score(&self, _word_data: &WordData) -> f64 {
+        0.0
+    }
+
+    //same as compare, given the scores of the names.
+    fn compare_with_scores(&self, one: (&WordData, f64), two: (&WordData, f64)) -> Ordering {
+        self.compare(one.0, two.0)
+    }
+
+    //same as compare, with the decayed score of the first name given instead of the current one. Only the rankers looking
+    //at the decayed scores need to override it.
+    fn compare_with_decayed_score(&self, one: (&WordData, Option<&DecayedScore>), two: &WordData) -> Ordering {
+        self.compare(one.0, two)
+    }
+}
+
+//popularity desc and then word asc, the order of the cached suggestions.
+pub struct PopularityRanker;
+
+impl Ranker for PopularityRanker {
+    fn compare(&self, word_data_one: &WordData, word_data_two: &WordData) -> Ordering {
+        compare_word_data(word_data_one, word_data_two)
+    }
+}
+
+//word asc, ignoring casing.
+pub struct AlphabeticalRanker;
+
+impl Ranker for AlphabeticalRanker {
+    fn compare(&self, word_data_one: &WordData, word_data_two: &WordData) -> Ordering {
+        self.compare_with_keys(
+            (word_data_one, &normalize_key(&word_data_one.word)),
+            (word_data_two, &normalize_key(&word_data_two.word)),
+        )
+    }
+
+    fn uses_keys(&self) -> bool {
+        true
+    }
+
+    fn compare_with_keys(&self, one: (&WordData, &str), two: (&WordData, &str)) -> Ordering {
+        one.1.cmp(two.1).then_with(|| one.0.word.cmp(&two.0.word))
+    }
+}
+
+//fewest chars first, so the closest completions of the prefix come first, and then by popularity.
+pub struct ShortestFirstRanker;
+
+impl Ranker for ShortestFirstRanker {
+    fn compare(&self, word_data_one: &WordData, word_data_two: &WordData) -> Ordering {
+        word_data_one
+            .word
+            .chars()
+            .count()
+            .cmp(&word_data_two.word.chars().count())
+            .then_with(|| compare_word_data(word_data_one, word_data_two))
+    }
+}
+
+//popularity desc by order of magnitude, so a name is only ranked over another one by being several times more popular.
+//Names of the same magnitude are ordered alphabetically.
+pub struct LogPopularityRanker;
+
+impl LogPopularityRanker {
+    fn magnitude(word_data: &WordData) -> u32 {
+        word_data.popularity.checked_ilog10().unwrap_or(0)
+    }
+}
+
+impl Ranker for LogPopularityRanker {
+    fn compare(&self, word_data_one: &WordData, word_data_two: &WordData) -> Ordering {
+        LogPopularityRanker::magnitude(word_data_two)
+            .cmp(&LogPopularityRanker::magnitude(word_data_one))
+            .then_with(|| AlphabeticalRanker.compare(word_data_one, word_data_two))
+    }
+
+    fn uses_keys(&self) -> bool {
+        true
+    }
+
+    fn compare_with_keys(&self, one: (&WordData, &str), two: (&WordData, &str)) -> Ordering {
+        LogPopularityRanker::magnitude(two.0)
+            .cmp(&LogPopularityRanker::magnitude(one.0))
+            .then_with(|| AlphabeticalRanker.compare_with_keys(one, two))
+    }
+}
+
+//decayed score desc as of now, so the names with the most recent hits come first, and then by popularity.
+pub struct DecayedRanker<'a> {
+    pub decayed_scores: &'a DecayedScores,
+    pub now: SystemTime,
+}
+
+impl Ranker for DecayedRanker<'_> {
+    fn compare(&self, word_data_one: &WordData, word_data_two: &WordData) -> Ordering {
+        self.compare_with_scores(
+            (word_data_one, self.score(word_data_one)),
+            (word_data_two, self.score(word_data_two)),
+        )
+    }
+
+    fn uses_scores(&self) -> bool {
+        true
+    }
+
+    fn score(&self, word_data: &WordData) -> f64 {
+        self.decayed_scores.score(&word_data.word, self.now)
+    }
+
+    fn compare_with_scores(&self, one: (&WordData, f64), two: (&WordData, f64)) -> Ordering {
+        two.1.total_cmp(&one.1).then_with(|| compare_word_data(one.0, two.0))
+    }
+
+    fn compare_with_decayed_score(&self, one: (&WordData, Option<&DecayedScore>), two: &WordData) -> Ordering {
+        let score_one = self.decayed_scores.score_of(one.1, self.now);
+
+        self.compare_with_scores((one.0, score_one), (two, self.score(two)))
+    }
+}
+
+//weighted geometric mean of the popularity and the decayed score, desc, so a name needs some of both to rank high and the
+//popularity doesn't win just by being counted over a longer time. Then by popularity.
+pub struct BlendedRanker<'a> {
+    pub decayed_scores: &'a DecayedScores,
+    pub now: SystemTime,
+    //percentage of the blend given to the decayed score.
+    pub decay_weight: u8,
+}
+
+impl BlendedRanker<'_> {
+    fn blended_score(&self, word_data: &WordData, decayed_score: f64) -> f64 {
+        let decay_weight = f64::from(self.decay_weight.min(100)) / 100.0;
+
+        (1.0 - decay_weight) * (word_data.popularity as f64).ln_1p() + decay_weight * decayed_score.ln_1p()
+    }
+}
+
+impl Ranker for BlendedRanker<'_> {
+    fn compare(&self, word_data_one: &WordData, word_data_two: &WordData) -> Ordering {
+        self.compare_with_scores(
+            (word_data_one, self.score(word_data_one)),
+            (word_data_two, self.score(word_data_two)),
+        )
+    }
+
+    fn uses_scores(&self) -> bool {
+        true
+    }
+
+    //the blended score, so the logarithms are also worked out once per name.
+    fn score(&self, word_data: &WordData) -> f64 {
+        self.blended_score(word_data, self.decayed_scores.score(&word_data.word, self.now))
+    }
+
+    fn compare_with_scores(&self, one: (&WordData, f64), two: (&WordData, f64)) -> Ordering {
+        two.1.total_cmp(&one.1).then_with(|| compare_word_data(one.0, two.0))
+    }
+
+    fn compare_with_decayed_score(&self, one: (&WordData, Option<&DecayedScore>), two: &WordData) -> Ordering {
+        let score_one = self.blended_score(one.0, self.decayed_scores.score_of(one.1, self.now));
+
+        self.compare_with_scores((one.0, score_one), (two, self.score(two)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RankingStrategy {
+    Popularity,
+    Alphabetical,
+    ShortestFirst,
+    LogPopularity,
+    Decayed,
+    Blended,
+}
+
+pub const DEFAULT_DECAY_WEIGHT: u8 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ranking {
+    pub strategy: RankingStrategy,
+    //names that are exactly the prefix go before the others, instead of being ranked along with them.
+    pub exact_match_first: bool,
+    //percentage of the blended ranking given to the decayed score, the rest goes to the popularity.
+    pub decay_weight: u8,
+}
+
+impl Default for Ranking {
+    fn default() -> Ranking {
+        Ranking {
+            strategy: RankingStrategy::Popularity,
+            exact_match_first: true,
+            decay_weight: DEFAULT_DECAY_WEIGHT,
+        }
+    }
+}
+
+impl Ranking {
+    //the cached suggestions are already in this order, other rankings have to look at every matching name.
+    pub fn is_cached(&self) -> bool {
+        self.strategy == RankingStrategy::Popularity && self.exact_match_first
+    }
+
+    //the rankers looking at the decayed scores compare them as of now, which has to stay the same while sorting.
+    pub fn ranker<'a>(&self, decayed_scores: &'a DecayedScores, now: SystemTime) -> Box<dyn Ranker + 'a> {
+        match self.strategy {
+            RankingStrategy::Popularity => Box::new(PopularityRanker),
+            RankingStrategy::Alphabetical => Box::new(AlphabeticalRanker),
+            RankingStrategy::ShortestFirst => Box::new(ShortestFirstRanker),
+            RankingStrategy::LogPopularity => Box::new(LogPopularityRanker),
+            RankingStrategy::Decayed => Box::new(DecayedRanker { decayed_scores, now }),
+            RankingStrategy::Blended => Box::new(BlendedRanker {
+                decayed_scores,
+                now,
+                decay_weight: self.decay_weight,
+            }),
+        }
+    }
+
+    //names starting with the prefix go before the ones with an inner word starting with it, whatever the strategy.
+    pub fn compare(&self, ranker: &dyn Ranker, one: (MatchKind, &WordData), two: (MatchKind, &WordData)) -> Ordering {
+        self.group(one.0)
+            .cmp(&self.group(two.0))
+            .then_with(|| ranker.compare(one.1, two.1))
+    }
+
+    //same as compare, with the decayed score of the first name given instead of the current one.
+    pub fn compare_with_decayed_score(
+        &self,
+        ranker: &dyn Ranker,
+        one: (MatchKind, &WordData, Option<&DecayedScore>),
+        two: (MatchKind, &WordData),
+    ) -> Ordering {
+        self.group(one.0)
+            .cmp(&self.group(two.0))
+            .then_with(|| ranker.compare_with_decayed_score((one.1, one.2), two.1))
+    }
+
+    //leaves the first count names in the order of the ranking and drops the others. Only those are sorted, so it's cheaper
+    //than sorting them all when there are many more names than that.
+    pub fn keep_first(&self, ranker: &dyn Ranker, ranked_words: &mut Vec<(MatchKind, &WordData)>, count: usize) {
+        if ranker.uses_scores() {
+            let mut scored_words: Vec<((MatchKind, &WordData), f64)> = ranked_words
+                .drain(..)
+                .map(|ranked_word| (ranked_word, ranker.score(ranked_word.1)))
+                .collect();
+            keep_first_by(&mut scored_words, count, |(one, one_score), (two, two_score)| {
+                self.group(one.0)
+                    .cmp(&self.group(two.0))
+                    .then_with(|| ranker.compare_with_scores((one.1, *one_score), (two.1, *two_score)))
+            });
+
+            ranked_words.extend(scored_words.into_iter().map(|(ranked_word, _)| ranked_word));
+            return;
+        }
+
+        if !ranker.uses_keys() {
+            keep_first_by(ranked_words, count, |one, two| self.compare(ranker, *one, *two));
+            return;
+        }
+
+        let mut keyed_words: Vec<((MatchKind, &WordData), String)> = ranked_words
+            .drain(..)
+            .map(|ranked_word| (ranked_word, normalize_key(&ranked_word.1.word)))
+            .collect();
+        keep_first_by(&mut keyed_words, count, |(one, one_key), (two, two_key)| {
+            self.group(one.0)
+                .cmp(&self.group(two.0))
+                .then_with(|| ranker.compare_with_keys((one.1, one_key), (two.1, two_key)))
+        });
+
+        ranked_words.extend(keyed_words.into_iter().map(|(ranked_word, _)| ranked_word));
+    }
+
+    fn group(&self, match_kind: MatchKind) -> MatchKind {
+        match match_kind {
+            MatchKind::Exact if !self.exact_match_first => MatchKind::Prefix,
+            match_kind => match_kind,
+        }
+    }
+}
+
+fn keep_first_by<T>(items: &mut Vec<T>, count: usize, mut compare: impl FnMut(&T, &T) -> Ordering) {
+    if count < items.len() {
+        items.select_nth_unstable_by(count, &mut compare);
+        items.truncate(count);
+    }
+    items.sort_by(compare);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trie::{ITrie, Trie};
+    use std::time::{Duration, Instant};
+
+    fn words() -> Vec<WordData> {
+        vec![
+            WordData::new("annabel".to_string(), 40),
+            WordData::new("Ann".to_string(), 5),
+            WordData::new("Anna".to_string(), 50),
+            WordData::new("Anne".to_string(), 700),
+            WordData::new("Annie".to_string(), 50),
+            WordData::new("Annika".to_string(), 0),
+        ]
+    }
+
+    fn ranked_names(ranker: &dyn Ranker) -> Vec<String> {
+        let mut words = words();
+        words.sort_by(|one, two| ranker.compare(one, two));
+
+        words.into_iter().map(|word_data| word_data.word).collect()
+    }
+
+    #[test]
+    fn t_popularity_ranker() {
+        assert_eq!(
+            vec!["Anne", "Anna", "Annie", "annabel", "Ann", "Annika"],
+            ranked_names(&PopularityRanker)
+        );
+    }
+
+    #[test]
+    fn t_alphabetical_ranker() {
+        assert_eq!(
+            vec!["Ann", "Anna", "annabel", "Anne", "Annie", "Annika"],
+            ranked_names(&AlphabeticalRanker)
+        );
+    }
+
+    #[test]
+    fn t_shortest_first_ranker() {
+        assert_eq!(
+            vec!["Ann", "Anne", "Anna", "Annie", "Annika", "annabel"],
+            ranked_names(&ShortestFirstRanker)
+        );
+    }
+
+    #[test]
+    fn t_log_popularity_ranker() {
+        //700, then 40 and 50, then 5 and 0
+        assert_eq!(
+            vec!["Anne", "Anna", "annabel", "Annie", "Ann", "Annika"],
+            ranked_names(&LogPopularityRanker)
+        );
+    }
+
+    #[test]
+    fn t_decayed_and_blended_rankers() {
+        let now = SystemTime::now();
+        let mut decayed_scores = DecayedScores::new(Duration::from_secs(60 * 60));
+        //worth 1 hit now
+        decayed_scores.add("Ann", 8, now - Duration::from_secs(3 * 60 * 60));
+        decayed_scores.add("Annika", 100, now);
+
+        let blended_ranker = |decay_weight| BlendedRanker {
+            decayed_scores: &decayed_scores,
+            now,
+            decay_weight,
+        };
+
+        assert_eq!(
+            vec!["Annika", "Ann", "Anne", "Anna", "Annie", "annabel"],
+            ranked_names(&DecayedRanker {
+                decayed_scores: &decayed_scores,
+                now
+            })
+        );
+        assert_eq!(
+            vec!["Anne", "Annika", "Anna", "Annie", "annabel", "Ann"],
+            ranked_names(&blended_ranker(50))
+        );
+        assert_eq!(ranked_names(&PopularityRanker), ranked_names(&blended_ranker(0)));
+        assert_eq!(
+            vec!["Annika", "Ann", "Anne", "Anna", "Annie", "annabel"],
+            ranked_names(&blended_ranker(100))
+        );
+    }
+
+    #[test]
+    fn t_ranking_exact_match_first() {
+        let exact_word = WordData::new("Ann".to_string(), 5);
+        let prefix_word = WordData::new("Anne".to_string(), 700);
+        let inner_word = WordData::new("Mary Ann".to_string(), 900);
+
+        let ranking = Ranking::default();
+        assert_eq!(
+            Ordering::Less,
+            ranking.compare(&PopularityRanker, (MatchKind::Exact, &exact_word), (MatchKind::Prefix, &prefix_word))
+        );
+
+        let ranking = Ranking {
+            exact_match_first: false,
+            ..Ranking::default()
+        };
+        assert_eq!(
+            Ordering::Greater,
+            ranking.compare(&PopularityRanker, (MatchKind::Exact, &exact_word), (MatchKind::Prefix, &prefix_word))
+        );
+        assert_eq!(
+            Ordering::Less,
+            ranking.compare(&PopularityRanker, (MatchKind::Exact, &exact_word), (MatchKind::InnerWord, &inner_word))
+        );
+        assert!(!ranking.is_cached());
+    }
+
+    #[test]
+    fn t_keep_first() {
+        let words = words();
+        let decayed_scores = DecayedScores::default();
+        let now = SystemTime::now();
+
+        for strategy in [
+            RankingStrategy::Popularity,
+            RankingStrategy::Alphabetical,
+            RankingStrategy::ShortestFirst,
+            RankingStrategy::LogPopularity,
+            RankingStrategy::Decayed,
+            RankingStrategy::Blended,
+        ] {
+            let ranking = Ranking {
+                strategy,
+                exact_match_first: false,
+                ..Ranking::default()
+            };
+            let ranker = ranking.ranker(&decayed_scores, now);
+
+            let mut sorted_words: Vec<(MatchKind, &WordData)> =
+                words.iter().map(|word_data| (MatchKind::Prefix, word_data)).collect();
+            sorted_words.sort_by(|one, two| ranking.compare(ranker.as_ref(), *one, *two));
+
+            //only the first names are kept, in the same order as sorting them all
+            for count in [0, 1, 3, words.len(), words.len() + 1] {
+                let mut ranked_words: Vec<(MatchKind, &WordData)> =
+                    words.iter().rev().map(|word_data| (MatchKind::Prefix, word_data)).collect();
+                ranking.keep_first(ranker.as_ref(), &mut ranked_words, count);
+
+                assert_eq!(&sorted_words[..count.min(words.len())], &ranked_words[..], "{:?} {}", strategy, count);
+            }
+        }
+    }
+
+    #[test]
+    fn t_get_ranked_typeahead_words() {
+        let trie = Trie::initialize(
+            "{\"Ann\": 5, \"Anna\": 50, \"Annabel\": 40, \"Anne\": 700, \"Mary Ann\": 900, \"Joanna\": 1000}",
+            4,
+        )
+        .unwrap();
+
+        let ranked_names = |strategy, exact_match_first| -> Vec<String> {
+            let ranking = Ranking {
+                strategy,
+                exact_match_first,
+                ..Ranking::default()
+            };
+
+            trie.get_ranked_typeahead_words("ann".to_string(), &ranking)
+                .unwrap()
+                .into_iter()
+                .map(|word_data| word_data.word)
+                .collect()
+        };
+
+        assert_eq!(
+            trie.get_typeahead_words("ann".to_string()).unwrap(),
+            trie.get_ranked_typeahead_words("ann".to_string(), &Ranking::default()).unwrap()
+        );
+        //the names with an inner word starting with the prefix still go last
+        assert_eq!(
+            vec!["Anne", "Anna", "Annabel", "Ann"],
+            ranked_names(RankingStrategy::Popularity, false)
+        );
+        assert_eq!(
+            vec!["Ann", "Anna", "Annabel", "Anne"],
+            ranked_names(RankingStrategy::Alphabetical, true)
+        );
+        assert_eq!(
+            vec!["Ann", "Anne", "Anna", "Annabel"],
+            ranked_names(RankingStrategy::ShortestFirst, false)
+        );
+        assert_eq!(
+            vec!["Ann", "Anne", "Anna", "Annabel"],
+            ranked_names(RankingStrategy::LogPopularity, true)
+        );
+    }
+
+    #[test]
+    fn t_rankings_names_json_empty_prefix() {
+        let file_content = std::fs::read_to_string("names.json").unwrap();
+        let mut trie = Trie::initialize(&file_content, 10).unwrap();
+        let now = SystemTime::now();
+
+        let hit_names: Vec<String> =
+            trie.all_words().into_iter().step_by(50).map(|word_data| word_data.word.clone()).collect();
+        for (index, name) in hit_names.into_iter().enumerate() {
+            trie.hit(name, now - Duration::from_secs(index as u64 * 3600)).unwrap();
+        }
+
+        let start = Instant::now();
+        for _ in 0..10 {
+            trie.get_ranked_typeahead_words(String::new(), &Ranking::default()).unwrap();
+        }
+        println!("{} names, 10 lookups. cached: {:?}", trie.all_words().len(), start.elapsed());
+
+        for strategy in [
+            RankingStrategy::Alphabetical,
+            RankingStrategy::ShortestFirst,
+            RankingStrategy::LogPopularity,
+            RankingStrategy::Decayed,
+            RankingStrategy::Blended,
+        ] {
+            let ranking = Ranking {
+                strategy,
+                ..Ranking::default()
+            };
+
+            let start = Instant::now();
+            for _ in 0..10 {
+                trie.ranked_words_with_prefix("", &ranking, now, 10);
+            }
+            println!("{:?}: {:?}", strategy, start.elapsed());
+
+            let ranked_words = trie.ranked_words_with_prefix("", &ranking, now, 10);
+
+            //the same names as sorting all of them, comparing them as they are
+            let ranker = ranking.ranker(&trie.decayed_scores, now);
+            let mut sorted_words = trie.words_matching_prefix("");
+            sorted_words.sort_by(|one, two| ranking.compare(ranker.as_ref(), *one, *two));
+            sorted_words.truncate(10);
+
+            assert_eq!(sorted_words, ranked_words, "{:?}", strategy);
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use crate::app_error::AppError;
+use crate::index;
+use crate::source::{self, SourceOptions};
+use crate::trie::{ITrie, LoadInfo, NameChange, Trie, TrieOptions};
+use log::{error, info, warn};
+use signal_hook::consts::SIGHUP;
+use signal_hook::iterator::Signals;
+use std::fs;
+use std::io;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+//where the names are loaded from on startup, and loaded again when they change.
+#[derive(Debug, Clone)]
+pub enum NamesSource {
+    File(String, SourceOptions),
+    Index(String),
+}
+
+impl NamesSource {
+    pub fn path(&self) -> &str {
+        match self {
+            NamesSource::File(path, _) | NamesSource::Index(path) => path,
+        }
+    }
+
+    pub fn load(&self, options: TrieOptions) -> Result<Trie, AppError> {
+        let started_at = Instant::now();
+
+        let mut trie = match self {
+            NamesSource::File(path, source_options) => source::load(path, source_options, options)?,
+            NamesSource::Index(path) => index::load(path, options)?,
+        };
+
+        trie.load_info = Some(LoadInfo {
+            source: self.path().to_string(),
+            duration: started_at.elapsed(),
+        });
+
+        Ok(trie)
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(self.path()).and_then(|metadata| metadata.modified()).ok()
+    }
+}
+
+//builds a new trie from the source without blocking the requests, then swaps it in with the changes made since the names
+//were first loaded: the names inserted, set or removed, and the popularity added. The current trie stays when the source
+//can't be loaded.
+pub fn reload(source: &NamesSource, options: TrieOptions, shared_trie: &RwLock<Trie>) -> Result<(), AppError> {
+    let mut new_trie = source.load(options)?;
+
+    let mut trie = shared_trie.write().map_err(|_e| AppError::UnexpectedError)?;
+
+    //removals go first, so a name set with another casing than the one in the source replaces it
+    let mut name_changes: Vec<(&String, &NameChange)> = trie.name_changes.iter().collect();
+    name_changes.sort_by_key(|(word, name_change)| (matches!(name_change, NameChange::Set(_)), word.as_str()));
+
+    for (word, name_change) in name_changes {
+        match name_change {
+            NameChange::Set(popularity) => {
+                if let Err(e) = new_trie.insert_word(word.clone(), *popularity) {
+                    warn!("Dropping the popularity set to {}: {}", word, e);
+                }
+            }
+            //the source may not have the name anymore
+            NameChange::Removed => {
+                let _ = new_trie.remove_word(word.clone());
+            }
+        }
+    }
+
+    //kept even when they don't apply to the new source, as a later one may have the name again
+    new_trie.name_changes.extend(trie.name_changes.drain());
+
+    let mut dropped_names = 0;
+    for (word, amount) in &trie.popularity_increments {
+        if let Err(e) = new_trie.add_popularity(word.clone(), *amount) {
+            warn!("Dropping the {} popularity added to {}: {}", amount, word, e);
+            dropped_names += 1;
+        }
+    }
+
+    info!(
+        "Reloaded {}, carried over the changes to {} names and the popularity added to {} names, {} dropped",
+        source.path(),
+        new_trie.name_changes.len(),
+        trie.popularity_increments.len() - dropped_names,
+        dropped_names
+    );
+
+    let mut decayed_scores = std::mem::take(&mut trie.decayed_scores);
+    decayed_scores.retain(|word| new_trie.find_word(word).is_some_and(|word_data| word_data.word == word));
+    new_trie.decayed_scores = decayed_scores;
+
+    *trie = new_trie;
+
+    Ok(())
+}
+
+fn reload_logged(source: &NamesSource, options: TrieOptions, shared_trie: &RwLock<Trie>) {
+    if let Err(e) = reload(source, options, shared_trie) {
+        error!("Could not reload {}, keeping the current names: {}", source.path(), e);
+    }
+}
+
+//reloads the names on SIGHUP and, with an interval, whenever the modification time of the source changes.
+pub fn watch(
+    source: NamesSource,
+    options: TrieOptions,
+    shared_trie: Arc<RwLock<Trie>>,
+    interval: Option<Duration>,
+) -> io::Result<()> {
+    let mut signals = Signals::new([SIGHUP])?;
+
+    if let Some(interval) = interval {
+        let source = source.clone();
+        let shared_trie = shared_trie.clone();
+
+        thread::spawn(move || {
+            let mut last_modified = source.modified();
+
+            loop {
+                thread::sleep(interval);
+
+                let modified = source.modified();
+                if modified != last_modified {
+                    //a file still being written fails to load, and gets a newer modification time once it is done
+                    info!("{} changed, reloading", source.path());
+                    last_modified = modified;
+                    reload_logged(&source, options, &shared_trie);
+                }
+            }
+        });
+    }
+
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            info!("Received SIGHUP, reloading {}", source.path());
+            reload_logged(&source, options, &shared_trie);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::tests::{temp_file_path, TempPath};
+    use crate::snapshot::SnapshotStore;
+    use crate::trie::WordData;
+
+    //the file is removed once the returned path is dropped, so it has to be kept until the end of the test.
+    fn names_file(name: &str, content: &str) -> (NamesSource, TempPath) {
+        let path = temp_file_path(name);
+        fs::write(&path, content).unwrap();
+
+        (NamesSource::File(path.to_string(), SourceOptions::default()), path)
+    }
+
+    #[test]
+    fn t_reload_carries_increments() {
+        let (source, _path) = names_file("reload-names.json", "{\"Aar\": 361, \"Aari\": 151, \"Abe\": 10}");
+        let shared_trie = RwLock::new(source.load(10.into()).unwrap());
+
+        {
+            let mut trie = shared_trie.write().unwrap();
+            trie.increase_popularity("Aari".to_string()).unwrap();
+            trie.increase_popularity("Aari".to_string()).unwrap();
+            trie.increase_popularity("Abe".to_string()).unwrap();
+        }
+
+        //"Abe" is gone from the new file, "Abel" is new
+        fs::write(source.path(), "{\"Aar\": 400, \"Aari\": 500, \"Abel\": 3}").unwrap();
+        reload(&source, 10.into(), &shared_trie).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aari".to_string(), 502),
+            WordData::new("Aar".to_string(), 400),
+            WordData::new("Abel".to_string(), 3),
+        ];
+
+        assert_eq!(
+            expected_words,
+            shared_trie.read().unwrap().get_typeahead_words("a".to_string()).unwrap()
+        );
+
+        //so do the decayed scores, except for the names that are gone
+        {
+            let now = SystemTime::now();
+            let trie = shared_trie.read().unwrap();
+            assert!(trie.decayed_scores.score("Aari", now) > 1.99);
+            assert_eq!(0.0, trie.decayed_scores.score("Abe", now));
+        }
+
+        //the increments keep counting from the first load
+        shared_trie.write().unwrap().increase_popularity("Aar".to_string()).unwrap();
+        fs::write(source.path(), "{\"Aar\": 1, \"Aari\": 2}").unwrap();
+        reload(&source, 10.into(), &shared_trie).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aari".to_string(), 4),
+            WordData::new("Aar".to_string(), 2),
+        ];
+
+        assert_eq!(
+            expected_words,
+            shared_trie.read().unwrap().get_typeahead_words("a".to_string()).unwrap()
+        );
+    }
+
+    #[test]
+    fn t_reload_after_snapshot_keeps_changes() {
+        let (source, _path) = names_file("reload-snapshot.json", "{\"Aar\": 361, \"Aari\": 151, \"Abe\": 10}");
+        let dir = temp_file_path("reload-snapshot");
+        let _ = fs::remove_dir_all(&dir);
+
+        {
+            let mut trie = source.load(10.into()).unwrap();
+            trie.increase_popularity("Aari".to_string()).unwrap();
+            trie.insert_word("Abby".to_string(), 7).unwrap();
+
+            SnapshotStore::open(&dir, 2).unwrap().take(&trie, None).unwrap();
+        }
+
+        //restarting from the snapshot, then a hit and more changes by the admin
+        let (_, trie) = SnapshotStore::open(&dir, 2).unwrap().load_newest(10.into()).unwrap().unwrap();
+        let shared_trie = RwLock::new(trie);
+        {
+            let mut trie = shared_trie.write().unwrap();
+            trie.increase_popularity("Aari".to_string()).unwrap();
+            trie.insert_word("Abel".to_string(), 3).unwrap();
+            trie.insert_word("Aar".to_string(), 5).unwrap();
+            trie.increase_popularity("Aar".to_string()).unwrap();
+            trie.remove_word("Abe".to_string()).unwrap();
+        }
+
+        fs::write(source.path(), "{\"Aar\": 400, \"Aari\": 500, \"Abe\": 20, \"Adam\": 1}").unwrap();
+        reload(&source, 10.into(), &shared_trie).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aari".to_string(), 502),
+            WordData::new("Abby".to_string(), 7),
+            WordData::new("Aar".to_string(), 6),
+            WordData::new("Abel".to_string(), 3),
+            WordData::new("Adam".to_string(), 1),
+        ];
+
+        assert_eq!(
+            expected_words,
+            shared_trie.read().unwrap().get_typeahead_words("a".to_string()).unwrap()
+        );
+
+        //and again, as the changes are kept for the next reload
+        reload(&source, 10.into(), &shared_trie).unwrap();
+
+        assert_eq!(
+            expected_words,
+            shared_trie.read().unwrap().get_typeahead_words("a".to_string()).unwrap()
+        );
+    }
+
+    #[test]
+    fn t_reload_invalid_file_keeps_current_names() {
+        let (source, _path) = names_file("reload-invalid.json", "{\"Aar\": 361}");
+        let shared_trie = RwLock::new(source.load(10.into()).unwrap());
+
+        fs::write(source.path(), "{\"Aar\": 3").unwrap();
+        let error = reload(&source, 10.into(), &shared_trie).unwrap_err();
+
+        assert_eq!(
+            AppError::InvalidSourceLine {
+                line: 1,
+                reason: "EOF while parsing an object at column 9".to_string()
+            },
+            error
+        );
+        assert_eq!(
+            vec![WordData::new("Aar".to_string(), 361)],
+            shared_trie.read().unwrap().get_typeahead_words("a".to_string()).unwrap()
+        );
+
+        fs::remove_file(source.path()).unwrap();
+
+        assert_eq!(Err(AppError::InvalidFileContent), reload(&source, 10.into(), &shared_trie));
+    }
+
+    #[test]
+    fn t_reload_index() {
+        let path = temp_file_path("reload-names.idx");
+        index::write(&Trie::initialize("{\"Aar\": 361}", 10).unwrap(), &path).unwrap();
+
+        let source = NamesSource::Index(path.clone());
+        let shared_trie = RwLock::new(source.load(10.into()).unwrap());
+        shared_trie.write().unwrap().increase_popularity("Aar".to_string()).unwrap();
+
+        index::write(&Trie::initialize("{\"Aar\": 10, \"Abe\": 5}", 10).unwrap(), &path).unwrap();
+        reload(&source, 10.into(), &shared_trie).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aar".to_string(), 11),
+            WordData::new("Abe".to_string(), 5),
+        ];
+
+        assert_eq!(
+            expected_words,
+            shared_trie.read().unwrap().get_typeahead_words("a".to_string()).unwrap()
+        );
+    }
+}
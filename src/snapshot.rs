@@ -0,0 +1,459 @@
+use crate::app_error::AppError;
+use crate::decay::DecayedScore;
+use crate::journal::Journal;
+use crate::trie::{ITrie, LoadInfo, NameChange, Popularity, Trie, TrieOptions};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::Instant;
+
+const SNAPSHOT_PREFIX: &str = "snapshot-";
+const SNAPSHOT_EXTENSION: &str = ".json";
+const SIDECAR_EXTENSION: &str = ".sidecar.json";
+
+// Directory with full copies of the names, in the same format as the source file. Every snapshot gets an increasing id,
+// the newest one is loaded on startup and the older ones are kept around for rolling back.
+#[derive(Debug)]
+pub struct SnapshotStore {
+    dir: PathBuf,
+    retention: usize,
+    state: Mutex<SnapshotState>,
+}
+
+#[derive(Debug)]
+struct SnapshotState {
+    next_id: u64,
+    //content of the newest snapshot and of its sidecar, so unchanged names are not written again.
+    last_content: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+//what a snapshot can't hold in the format of the source file, written next to it: the changes made since the names were
+//loaded from the source, so they are still carried over when it's reloaded, and the decayed scores, as the journal with
+//the times of the hits is reset afterwards. Older snapshots don't have one.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Sidecar {
+    #[serde(default)]
+    popularity_increments: BTreeMap<String, Popularity>,
+    #[serde(default)]
+    name_changes: BTreeMap<String, NameChange>,
+    #[serde(default)]
+    decayed_scores: BTreeMap<String, DecayedScore>,
+}
+
+impl SnapshotStore {
+    //opens the directory, creating it if needed. Besides the newest snapshot, `retention` older ones are kept.
+    pub fn open(dir: &str, retention: usize) -> Result<SnapshotStore, AppError> {
+        fs::create_dir_all(dir).map_err(|e| {
+            error!("Could not create snapshot directory {}: {}", dir, e);
+            AppError::UnexpectedError
+        })?;
+
+        let store = SnapshotStore {
+            dir: PathBuf::from(dir),
+            retention,
+            state: Mutex::new(SnapshotState {
+                next_id: 1,
+                last_content: None,
+            }),
+        };
+
+        let next_id = store.snapshot_ids()?.last().map_or(1, |id| id + 1);
+        store.state.lock().map_err(|_e| AppError::UnexpectedError)?.next_id = next_id;
+
+        Ok(store)
+    }
+
+    //loads the newest snapshot that can be read, skipping the broken ones. Returns its id along with the names.
+    pub fn load_newest(&self, options: TrieOptions) -> Result<Option<(u64, Trie)>, AppError> {
+        for id in self.snapshot_ids()?.into_iter().rev() {
+            let path = self.snapshot_path(id);
+            let started_at = Instant::now();
+
+            let loaded = fs::read_to_string(&path)
+                .map_err(|_e| AppError::InvalidFileContent)
+                .and_then(|file_content| {
+                    let (sidecar, sidecar_content) = self.read_sidecar(id)?;
+                    let mut trie = Trie::initialize(&file_content, options)?;
+                    trie.popularity_increments = sidecar.popularity_increments.into_iter().collect();
+                    trie.name_changes = sidecar.name_changes.into_iter().collect();
+                    for (word, decayed_score) in sidecar.decayed_scores {
+                        trie.decayed_scores.insert(word, decayed_score);
+                    }
+
+                    Ok((trie, (file_content.into_bytes(), sidecar_content)))
+                });
+
+            match loaded {
+                Ok((mut trie, content)) => {
+                    info!("Loaded snapshot {}", path.display());
+                    trie.load_info = Some(LoadInfo {
+                        source: path.display().to_string(),
+                        duration: started_at.elapsed(),
+                    });
+                    self.state.lock().map_err(|_e| AppError::UnexpectedError)?.last_content = Some(content);
+
+                    return Ok(Some((id, trie)));
+                }
+                Err(e) => warn!("Skipping snapshot {}: {}", path.display(), e),
+            }
+        }
+
+        Ok(None)
+    }
+
+    //writes the names to a new snapshot, then resets the journal and removes the snapshots past the retention. The caller
+    //must hold the trie's lock, so no popularity is increased in the meantime. Returns None when nothing changed.
+    pub fn take(&self, trie: &Trie, journal: Option<&Journal>) -> Result<Option<u64>, AppError> {
+        let values: BTreeMap<&str, Popularity> = trie
+            .all_words()
+            .into_iter()
+            .map(|word_data| (word_data.word.as_str(), word_data.popularity))
+            .collect();
+        let sidecar = Sidecar {
+            popularity_increments: trie
+                .popularity_increments
+                .iter()
+                .map(|(word, amount)| (word.clone(), *amount))
+                .collect(),
+            name_changes: trie
+                .name_changes
+                .iter()
+                .map(|(word, name_change)| (word.clone(), *name_change))
+                .collect(),
+            decayed_scores: trie
+                .decayed_scores
+                .iter()
+                .map(|(word, decayed_score)| (word.clone(), *decayed_score))
+                .collect(),
+        };
+        let content = (
+            serde_json::to_vec(&values).map_err(|_e| AppError::UnexpectedError)?,
+            serde_json::to_vec(&sidecar).map_err(|_e| AppError::UnexpectedError)?,
+        );
+
+        let mut state = self.state.lock().map_err(|_e| AppError::UnexpectedError)?;
+
+        if state.last_content.as_ref() == Some(&content) {
+            return Ok(None);
+        }
+
+        let id = state.next_id;
+        //the sidecar goes first, so a snapshot is never found without its own
+        self.write_atomically(&self.sidecar_path(id), &content.1)
+            .and_then(|_| self.write_atomically(&self.snapshot_path(id), &content.0))
+            .map_err(|e| {
+                error!("Could not write snapshot {}: {}", id, e);
+                AppError::UnexpectedError
+            })?;
+
+        state.next_id = id + 1;
+        state.last_content = Some(content);
+        drop(state);
+
+        if let Some(journal) = journal {
+            journal.reset(id)?;
+        }
+
+        self.remove_old_snapshots()?;
+
+        info!("Wrote snapshot {} with {} names", id, values.len());
+
+        Ok(Some(id))
+    }
+
+    //takes a snapshot of the shared names, logging any failure. Increasing the popularity waits until it is written.
+    pub fn take_shared(&self, shared_trie: &RwLock<Trie>, journal: Option<&Journal>) {
+        let result = shared_trie
+            .read()
+            .map_err(|_e| AppError::UnexpectedError)
+            .and_then(|trie| self.take(&trie, journal));
+
+        if let Err(e) = result {
+            error!("Could not take snapshot: {}", e);
+        }
+    }
+
+    //the content goes to a temporary file that is renamed over the final path only once it is on disk, so a crash never
+    //leaves a partial snapshot behind.
+    fn write_atomically(&self, path: &Path, content: &[u8]) -> std::io::Result<()> {
+        let temp_path = path.with_extension("json.tmp");
+
+        let mut file = File::create(&temp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+
+        fs::rename(&temp_path, path)?;
+
+        //makes the rename itself durable
+        File::open(&self.dir)?.sync_all()
+    }
+
+    fn remove_old_snapshots(&self) -> Result<(), AppError> {
+        let snapshot_ids = self.snapshot_ids()?;
+        let kept = self.retention + 1;
+
+        for id in snapshot_ids.iter().take(snapshot_ids.len().saturating_sub(kept)) {
+            let path = self.snapshot_path(*id);
+
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Could not remove snapshot {}: {}", path.display(), e);
+            }
+
+            let sidecar_path = self.sidecar_path(*id);
+
+            match fs::remove_file(&sidecar_path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => {
+                    warn!("Could not remove snapshot sidecar {}: {}", sidecar_path.display(), e)
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    //ids of the snapshots in the directory, oldest first.
+    fn snapshot_ids(&self) -> Result<Vec<u64>, AppError> {
+        let entries = fs::read_dir(&self.dir).map_err(|e| {
+            error!("Could not read snapshot directory {}: {}", self.dir.display(), e);
+            AppError::UnexpectedError
+        })?;
+
+        let mut snapshot_ids: Vec<u64> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()?
+                    .strip_prefix(SNAPSHOT_PREFIX)?
+                    .strip_suffix(SNAPSHOT_EXTENSION)?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+        snapshot_ids.sort_unstable();
+
+        Ok(snapshot_ids)
+    }
+
+    fn snapshot_path(&self, id: u64) -> PathBuf {
+        self.dir
+            .join(format!("{}{:010}{}", SNAPSHOT_PREFIX, id, SNAPSHOT_EXTENSION))
+    }
+
+    fn sidecar_path(&self, id: u64) -> PathBuf {
+        self.dir
+            .join(format!("{}{:010}{}", SNAPSHOT_PREFIX, id, SIDECAR_EXTENSION))
+    }
+
+    //the sidecar of the snapshot along with its content, an empty one when the snapshot has none.
+    fn read_sidecar(&self, id: u64) -> Result<(Sidecar, Vec<u8>), AppError> {
+        match fs::read(self.sidecar_path(id)) {
+            Ok(content) => {
+                let sidecar = serde_json::from_slice(&content).map_err(|_e| AppError::InvalidFileContent)?;
+
+                Ok((sidecar, content))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok((Sidecar::default(), Vec::new())),
+            Err(_e) => Err(AppError::InvalidFileContent),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::tests::temp_file_path;
+    use crate::journal::{FsyncPolicy, JournalRecord};
+    use crate::trie::WordData;
+    use std::time::{Duration, SystemTime};
+
+    fn snapshot_file_names(dir: &str) -> Vec<String> {
+        let mut file_names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        file_names.sort();
+
+        file_names
+    }
+
+    #[test]
+    fn t_take_and_load_newest() {
+        let dir = temp_file_path("snapshot-load");
+        let mut trie = Trie::initialize("{\"Aar\": 361, \"Aari\": 151}", 10).unwrap();
+
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+        assert_eq!(Some(1), store.take(&trie, None).unwrap());
+
+        trie.increase_popularity("Aari".to_string()).unwrap();
+        assert_eq!(Some(2), store.take(&trie, None).unwrap());
+
+        assert_eq!(
+            "{\"Aar\":361,\"Aari\":152}",
+            fs::read_to_string(format!("{}/snapshot-0000000002.json", dir)).unwrap()
+        );
+
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+        let (id, loaded_trie) = store.load_newest(10.into()).unwrap().unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aar".to_string(), 361),
+            WordData::new("Aari".to_string(), 152),
+        ];
+
+        assert_eq!(2, id);
+        assert_eq!(expected_words, loaded_trie.get_typeahead_words("aa".to_string()).unwrap());
+    }
+
+    #[test]
+    fn t_load_newest_restores_changes() {
+        let dir = temp_file_path("snapshot-changes");
+        let mut trie = Trie::initialize("{\"Aar\": 361, \"Aari\": 151, \"Abe\": 10}", 10).unwrap();
+        trie.increase_popularity("Aari".to_string()).unwrap();
+        trie.insert_word("Abel".to_string(), 3).unwrap();
+        trie.remove_word("Abe".to_string()).unwrap();
+
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+        store.take(&trie, None).unwrap();
+
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+        let (_, loaded_trie) = store.load_newest(10.into()).unwrap().unwrap();
+
+        assert_eq!(trie.popularity_increments, loaded_trie.popularity_increments);
+        assert_eq!(trie.name_changes, loaded_trie.name_changes);
+
+        //snapshots written before the sidecars were added still load, without changes
+        fs::remove_file(format!("{}/snapshot-0000000001.sidecar.json", dir)).unwrap();
+        let (_, loaded_trie) = store.load_newest(10.into()).unwrap().unwrap();
+
+        assert!(loaded_trie.popularity_increments.is_empty());
+        assert!(loaded_trie.name_changes.is_empty());
+        assert_eq!(Some(&WordData::new("Abel".to_string(), 3)), loaded_trie.find_word("Abel"));
+    }
+
+    #[test]
+    fn t_load_newest_restores_decayed_scores() {
+        let dir = temp_file_path("snapshot-decayed-scores");
+        let half_life = Duration::from_secs(60 * 60);
+        let options = TrieOptions {
+            suggestion_number: 10,
+            decay_half_life: half_life,
+            ..TrieOptions::default()
+        };
+        let now = SystemTime::now();
+
+        let mut trie = Trie::initialize("{\"Aar\": 361, \"Aari\": 151}", options).unwrap();
+        trie.hit("Aar".to_string(), now - half_life).unwrap();
+        trie.hit("Aar".to_string(), now - half_life).unwrap();
+        trie.hit("Aari".to_string(), now).unwrap();
+
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+        store.take(&trie, None).unwrap();
+
+        //after a restart the hits are only in the snapshot, so the scores come from its sidecar
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+        let (_, loaded_trie) = store.load_newest(options).unwrap().unwrap();
+
+        assert!((loaded_trie.decayed_scores.score("Aar", now) - 1.0).abs() < 1e-3);
+        assert!((loaded_trie.decayed_scores.score("Aari", now) - 1.0).abs() < 1e-3);
+        assert!(loaded_trie.decayed_scores.score("Aar", now + half_life) < 0.51);
+    }
+
+    #[test]
+    fn t_load_newest_empty_dir() {
+        let dir = temp_file_path("snapshot-empty");
+
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+
+        assert!(store.load_newest(10.into()).unwrap().is_none());
+    }
+
+    #[test]
+    fn t_load_newest_skips_invalid_snapshot() {
+        let dir = temp_file_path("snapshot-invalid");
+        let trie = Trie::initialize("{\"Aar\": 361}", 10).unwrap();
+
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+        store.take(&trie, None).unwrap();
+        fs::write(format!("{}/snapshot-0000000002.json", dir), "{\"Aar\": 3").unwrap();
+
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+        let (id, loaded_trie) = store.load_newest(10.into()).unwrap().unwrap();
+
+        assert_eq!(1, id);
+        assert_eq!(
+            vec![WordData::new("Aar".to_string(), 361)],
+            loaded_trie.get_typeahead_words("aa".to_string()).unwrap()
+        );
+
+        //the broken snapshot keeps its id
+        assert_eq!(Some(3), store.take(&Trie::initialize("{\"Abe\": 1}", 10).unwrap(), None).unwrap());
+    }
+
+    #[test]
+    fn t_take_skips_unchanged_names() {
+        let dir = temp_file_path("snapshot-unchanged");
+        let trie = Trie::initialize("{\"Aar\": 361}", 10).unwrap();
+
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+        assert_eq!(Some(1), store.take(&trie, None).unwrap());
+        assert_eq!(None, store.take(&trie, None).unwrap());
+
+        //also after loading it back
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+        store.load_newest(10.into()).unwrap();
+        assert_eq!(None, store.take(&trie, None).unwrap());
+    }
+
+    #[test]
+    fn t_take_removes_old_snapshots() {
+        let dir = temp_file_path("snapshot-retention");
+        let mut trie = Trie::initialize("{\"Aar\": 361}", 10).unwrap();
+
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+        for _ in 0..5 {
+            trie.increase_popularity("Aar".to_string()).unwrap();
+            store.take(&trie, None).unwrap();
+        }
+
+        //no temporary file is left behind either
+        assert_eq!(
+            vec![
+                "snapshot-0000000003.json",
+                "snapshot-0000000003.sidecar.json",
+                "snapshot-0000000004.json",
+                "snapshot-0000000004.sidecar.json",
+                "snapshot-0000000005.json",
+                "snapshot-0000000005.sidecar.json"
+            ],
+            snapshot_file_names(&dir)
+        );
+    }
+
+    #[test]
+    fn t_take_resets_journal() {
+        let dir = temp_file_path("snapshot-journal");
+        let journal_path = temp_file_path("snapshot-journal.log");
+        let trie = Trie::initialize("{\"Aar\": 361}", 10).unwrap();
+
+        let (journal, _) = Journal::open(&journal_path, FsyncPolicy::Always, 1).unwrap();
+        journal
+            .append(&JournalRecord::Hit {
+                name: "Aar".to_string(),
+                at: None,
+            })
+            .unwrap();
+
+        let store = SnapshotStore::open(&dir, 2).unwrap();
+        store.take(&trie, Some(&journal)).unwrap();
+        drop(journal);
+
+        let (_journal, records) = Journal::open(&journal_path, FsyncPolicy::Always, 1).unwrap();
+        assert_eq!(vec![JournalRecord::Snapshot { id: 1 }], records);
+    }
+}
@@ -0,0 +1,729 @@
+use crate::app_error::AppError;
+use crate::normalization::normalize_key;
+use crate::trie::{OrderedEntries, Popularity, Trie, TrieOptions};
+use crate::validation::{self, Issue, Problem};
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceFormat {
+    //an object with names and popularities, like names.json.
+    Json,
+    //comma separated values, with a header naming the columns.
+    Csv,
+    //same as csv, separated by tabs.
+    Tsv,
+    //one object per line, with the same keys as the csv columns.
+    Ndjson,
+    //one name per line, all of them with the default popularity.
+    Lines,
+}
+
+//what happens to names of the source file with the same casing insensitive key, e.g. "Rose-Marie" and "Rose-marie".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    //refuse the file.
+    Reject,
+    //keep the name with the higher popularity, the first one on a tie.
+    KeepHigher,
+    //keep the first spelling, with the popularities of all of them added up.
+    Sum,
+    //keep every spelling as a variant of the same node. Repeated spellings keep the higher popularity.
+    KeepBoth,
+}
+
+// How a source file is read. The columns name the csv and tsv headers, and the keys of the ndjson records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceOptions {
+    //detected from the file when not set.
+    pub format: Option<SourceFormat>,
+    pub name_column: String,
+    pub popularity_column: String,
+    //popularity of the names listed without one.
+    pub default_popularity: Popularity,
+    //refuse the file on any problem found by the validation, not only on the lines that can't be read.
+    pub strict: bool,
+    pub collision_policy: CollisionPolicy,
+}
+
+impl Default for SourceOptions {
+    fn default() -> Self {
+        SourceOptions {
+            format: None,
+            name_column: "name".to_string(),
+            popularity_column: "times".to_string(),
+            default_popularity: 0,
+            strict: false,
+            collision_policy: CollisionPolicy::KeepHigher,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceEntry {
+    pub line: usize,
+    pub name: String,
+    pub popularity: Popularity,
+}
+
+impl SourceFormat {
+    //the format given by the extension of the file, or else guessed from its first line.
+    pub fn detect(path: &str, content: &str, options: &SourceOptions) -> SourceFormat {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+
+        match extension.as_deref() {
+            Some("json") => return SourceFormat::Json,
+            Some("csv") => return SourceFormat::Csv,
+            Some("tsv") => return SourceFormat::Tsv,
+            Some("ndjson") | Some("jsonl") => return SourceFormat::Ndjson,
+            _ => {}
+        }
+
+        let first_line = match numbered_lines(content).next() {
+            Some((_, line)) => line.trim(),
+            None => return SourceFormat::Json,
+        };
+
+        if first_line.starts_with('{') {
+            //a record holds the name column, while names.json has the names themselves as keys
+            let record = serde_json::from_str::<serde_json::Map<String, Value>>(first_line);
+            if matches!(record, Ok(record) if record.get(&options.name_column).is_some_and(Value::is_string)) {
+                SourceFormat::Ndjson
+            } else {
+                SourceFormat::Json
+            }
+        } else if first_line.contains('\t') {
+            SourceFormat::Tsv
+        } else if first_line.contains(',') {
+            SourceFormat::Csv
+        } else {
+            SourceFormat::Lines
+        }
+    }
+}
+
+//reads the source file, along with its format.
+pub fn read_file(path: &str, options: &SourceOptions) -> Result<(String, SourceFormat), AppError> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        error!("Could not read {}: {}", path, e);
+        AppError::InvalidFileContent
+    })?;
+
+    let format = options
+        .format
+        .unwrap_or_else(|| SourceFormat::detect(path, &content, options));
+    info!("Reading {} as {:?}", path, format);
+
+    Ok((content, format))
+}
+
+//loads the names of the source file. Lines that can't be read fail the loading, and so does any other problem in strict
+//mode, otherwise they are only logged.
+pub fn load(path: &str, options: &SourceOptions, trie_options: TrieOptions) -> Result<Trie, AppError> {
+    let (content, format) = read_file(path, options)?;
+
+    let (entries, report) = validation::validate(&content, format, options);
+    report.log(path);
+
+    if let Some(issue) = report.issues.iter().find(|issue| options.strict || issue.problem.is_error()) {
+        return Err(issue.to_error());
+    }
+
+    let entries = resolve_collisions(entries, options.collision_policy)?;
+
+    Trie::from_entries(entries.into_iter().map(|entry| (entry.name, entry.popularity)), trie_options)
+}
+
+//merges the names with the same casing insensitive key, or with the same spelling for KeepBoth, according to the policy.
+//The result only depends on the order of the entries, which keep the place of their first name.
+pub fn resolve_collisions(entries: Vec<SourceEntry>, policy: CollisionPolicy) -> Result<Vec<SourceEntry>, AppError> {
+    let mut resolved_entries: Vec<SourceEntry> = Vec::with_capacity(entries.len());
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for entry in entries {
+        let key = match policy {
+            CollisionPolicy::KeepBoth => entry.name.clone(),
+            _ => normalize_key(&entry.name),
+        };
+
+        let kept_entry = match positions.entry(key) {
+            Entry::Occupied(position) => &mut resolved_entries[*position.get()],
+            Entry::Vacant(position) => {
+                position.insert(resolved_entries.len());
+                resolved_entries.push(entry);
+                continue;
+            }
+        };
+
+        match policy {
+            CollisionPolicy::Reject => {
+                let issue = Issue {
+                    line: entry.line,
+                    problem: Problem::Duplicate {
+                        name: entry.name,
+                        first_name: kept_entry.name.clone(),
+                        first_line: kept_entry.line,
+                    },
+                };
+                return Err(issue.to_error());
+            }
+            CollisionPolicy::KeepHigher | CollisionPolicy::KeepBoth => {
+                if entry.popularity > kept_entry.popularity {
+                    *kept_entry = entry;
+                }
+            }
+            CollisionPolicy::Sum => {
+                kept_entry.popularity = kept_entry
+                    .popularity
+                    .checked_add(entry.popularity)
+                    .ok_or_else(|| AppError::InvalidSourceLine {
+                        line: entry.line,
+                        reason: AppError::PopularityOverflow.to_string(),
+                    })?;
+            }
+        }
+    }
+
+    Ok(resolved_entries)
+}
+
+//the names of the content, in the order they appear. Fails on the first line that can't be read. Loading goes through
+//the validation instead, so the tests use it to read what they write.
+#[cfg(test)]
+pub fn parse(content: &str, format: SourceFormat, options: &SourceOptions) -> Result<Vec<SourceEntry>, AppError> {
+    parse_lenient(content, format, options)
+        .and_then(|entries| entries.into_iter().collect())
+        .map_err(|issue| issue.to_error())
+}
+
+//parses every line on its own, so one that can't be read doesn't hide the others. Fails only when nothing can be read,
+//e.g. on invalid JSON or a header without the name column.
+pub(crate) fn parse_lenient(
+    content: &str,
+    format: SourceFormat,
+    options: &SourceOptions,
+) -> Result<Vec<Result<SourceEntry, Issue>>, Issue> {
+    let content = content.trim_start_matches('\u{feff}');
+
+    match format {
+        SourceFormat::Json => parse_json(content),
+        format => parse_by_line(content, format, options),
+    }
+}
+
+fn invalid_content(line: usize, reason: impl Into<String>) -> Issue {
+    Issue {
+        line,
+        problem: Problem::InvalidContent(reason.into()),
+    }
+}
+
+fn invalid_popularity(line: usize, name: &str, popularity: impl ToString) -> Issue {
+    Issue {
+        line,
+        problem: Problem::InvalidPopularity {
+            name: name.to_string(),
+            popularity: popularity.to_string(),
+        },
+    }
+}
+
+//lines numbered from 1, without their line break. Blank lines are left out.
+fn numbered_lines(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty())
+}
+
+fn parse_json(content: &str) -> Result<Vec<Result<SourceEntry, Issue>>, Issue> {
+    parse_json_entries(content).map_err(|e| json_issue(&e))
+}
+
+//same as parse_json, keeping the error of serde for the callers that report it their own way.
+pub(crate) fn parse_json_entries(content: &str) -> Result<Vec<Result<SourceEntry, Issue>>, serde_json::Error> {
+    //the popularities are checked afterwards, so that each wrong one is reported with its name
+    let OrderedEntries(entries): OrderedEntries<Value> = serde_json::from_str(content)?;
+
+    //serde doesn't tell where each name is, so it is looked up after the previous one. A name written with escapes
+    //that serde wouldn't use is not found, and gets the line of the previous one
+    let mut offset = 0;
+    let mut line = 1;
+
+    Ok(entries
+        .into_iter()
+        .map(|(name, popularity)| {
+            let key = serde_json::to_string(&name).unwrap_or_default();
+
+            if let Some(position) = content[offset..].find(&key) {
+                line += content[offset..offset + position].matches('\n').count();
+                offset += position + key.len();
+            }
+
+            match popularity.as_u64() {
+                Some(popularity) => Ok(SourceEntry { line, name, popularity }),
+                None => Err(invalid_popularity(line, &name, popularity)),
+            }
+        })
+        .collect())
+}
+
+fn json_issue(e: &serde_json::Error) -> Issue {
+    let message = e.to_string();
+    let position = format!(" at line {} column {}", e.line(), e.column());
+    let message = message.strip_suffix(&position).unwrap_or(&message);
+
+    //errors about the whole content, like a wrong type, have no column
+    match e.column() {
+        0 => invalid_content(e.line(), message),
+        column => invalid_content(e.line(), format!("{} at column {}", message, column)),
+    }
+}
+
+fn parse_by_line(
+    content: &str,
+    format: SourceFormat,
+    options: &SourceOptions,
+) -> Result<Vec<Result<SourceEntry, Issue>>, Issue> {
+    let mut parser = LineParser::new(format, options.clone());
+    let mut entries = Vec::new();
+
+    for (line, content) in numbered_lines(content) {
+        entries.extend(parser.parse_line(line, content)?);
+    }
+
+    Ok(entries)
+}
+
+// Reads the line based formats a line at a time, so a body can be parsed while it arrives. The lines must come in order,
+// as the first one is the header of the csv and tsv formats.
+#[derive(Debug)]
+pub struct LineParser {
+    format: SourceFormat,
+    options: SourceOptions,
+    //read from the header.
+    columns: Option<Columns>,
+    is_first_line: bool,
+}
+
+#[derive(Debug)]
+struct Columns {
+    count: usize,
+    name_index: usize,
+    popularity_index: Option<usize>,
+}
+
+impl LineParser {
+    pub fn new(format: SourceFormat, options: SourceOptions) -> LineParser {
+        LineParser {
+            format,
+            options,
+            columns: None,
+            is_first_line: true,
+        }
+    }
+
+    //the entry of the line, or the issue that keeps it from being read. Blank lines and headers have none. Fails when no
+    //line can be read at all, like after a header without the name column, or for JSON, which can't be read by line.
+    pub fn parse_line(&mut self, line: usize, content: &str) -> Result<Option<Result<SourceEntry, Issue>>, Issue> {
+        if content.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let is_first_line = std::mem::replace(&mut self.is_first_line, false);
+
+        match self.format {
+            SourceFormat::Json => Err(invalid_content(line, "JSON can't be read line by line")),
+            SourceFormat::Csv | SourceFormat::Tsv if is_first_line => {
+                self.columns = Some(self.parse_header(line, content)?);
+                Ok(None)
+            }
+            SourceFormat::Csv | SourceFormat::Tsv => {
+                let columns = self
+                    .columns
+                    .as_ref()
+                    .ok_or_else(|| invalid_content(line, "Missing the header"))?;
+
+                Ok(Some(parse_delimited_line(line, content, self.delimiter(), columns, &self.options)))
+            }
+            SourceFormat::Ndjson => Ok(Some(parse_ndjson_line(line, content, &self.options))),
+            //a first line equal to the name column is taken as a header, like the "Name" of PerformanceTestsDataSet.txt.
+            SourceFormat::Lines if is_first_line && content.trim().eq_ignore_ascii_case(&self.options.name_column) => {
+                Ok(None)
+            }
+            SourceFormat::Lines => Ok(Some(Ok(SourceEntry {
+                line,
+                name: content.to_string(),
+                popularity: self.options.default_popularity,
+            }))),
+        }
+    }
+
+    fn delimiter(&self) -> char {
+        match self.format {
+            SourceFormat::Tsv => '\t',
+            _ => ',',
+        }
+    }
+
+    fn parse_header(&self, line: usize, content: &str) -> Result<Columns, Issue> {
+        let columns = split_csv_line(content, self.delimiter()).map_err(|reason| invalid_content(line, reason))?;
+        let column_index = |column: &str| {
+            columns
+                .iter()
+                .position(|header_column| header_column.trim().eq_ignore_ascii_case(column))
+        };
+
+        let name_index = column_index(&self.options.name_column).ok_or_else(|| {
+            invalid_content(line, format!("Missing the '{}' column", self.options.name_column))
+        })?;
+
+        Ok(Columns {
+            count: columns.len(),
+            name_index,
+            popularity_index: column_index(&self.options.popularity_column),
+        })
+    }
+}
+
+fn parse_delimited_line(
+    line: usize,
+    content: &str,
+    delimiter: char,
+    columns: &Columns,
+    options: &SourceOptions,
+) -> Result<SourceEntry, Issue> {
+    let fields = split_csv_line(content, delimiter).map_err(|reason| invalid_content(line, reason))?;
+
+    if fields.len() != columns.count {
+        return Err(invalid_content(
+            line,
+            format!("Expected {} fields, found {}", columns.count, fields.len()),
+        ));
+    }
+
+    let name = &fields[columns.name_index];
+    let popularity = match columns.popularity_index {
+        Some(popularity_index) => {
+            let popularity = fields[popularity_index].trim();
+            popularity
+                .parse()
+                .map_err(|_e| invalid_popularity(line, name, popularity))?
+        }
+        None => options.default_popularity,
+    };
+
+    Ok(SourceEntry {
+        line,
+        name: name.clone(),
+        popularity,
+    })
+}
+
+fn parse_ndjson_line(line: usize, content: &str, options: &SourceOptions) -> Result<SourceEntry, Issue> {
+    let record: serde_json::Map<String, Value> =
+        serde_json::from_str(content).map_err(|e| invalid_content(line, format!("Invalid JSON: {}", e)))?;
+
+    let name = record
+        .get(&options.name_column)
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid_content(line, format!("Missing or invalid '{}'", options.name_column)))?;
+
+    let popularity = match record.get(&options.popularity_column) {
+        Some(popularity) => popularity
+            .as_u64()
+            .ok_or_else(|| invalid_popularity(line, name, popularity))?,
+        None => options.default_popularity,
+    };
+
+    Ok(SourceEntry {
+        line,
+        name: name.to_string(),
+        popularity,
+    })
+}
+
+//splits a line on the delimiter. Fields can be quoted to hold the delimiter, with "" standing for a quote inside them.
+pub(crate) fn split_csv_line(line: &str, delimiter: char) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut is_quoted = false;
+
+    while let Some(char) = chars.next() {
+        match char {
+            '"' if is_quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if is_quoted => is_quoted = false,
+            '"' if field.is_empty() => is_quoted = true,
+            char if char == delimiter && !is_quoted => fields.push(std::mem::take(&mut field)),
+            char => field.push(char),
+        }
+    }
+
+    if is_quoted {
+        return Err("Unclosed quote".to_string());
+    }
+    fields.push(field);
+
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::tests::temp_file_path;
+    use crate::trie::{ITrie, WordData};
+
+    fn source_entry(line: usize, name: &str, popularity: Popularity) -> SourceEntry {
+        SourceEntry {
+            line,
+            name: name.to_string(),
+            popularity,
+        }
+    }
+
+    fn line_error(line: usize, reason: &str) -> AppError {
+        AppError::InvalidSourceLine {
+            line,
+            reason: reason.to_string(),
+        }
+    }
+
+    fn parse_with_defaults(content: &str, format: SourceFormat) -> Result<Vec<SourceEntry>, AppError> {
+        parse(content, format, &SourceOptions::default())
+    }
+
+    #[test]
+    fn t_detect_format() {
+        let options = SourceOptions::default();
+        let cases = [
+            ("names.json", "Aar", SourceFormat::Json),
+            ("names.CSV", "Aar", SourceFormat::Csv),
+            ("names.tsv", "Aar", SourceFormat::Tsv),
+            ("names.jsonl", "Aar", SourceFormat::Ndjson),
+            ("names", "{\"Aar\": 361, \"name\": 3}", SourceFormat::Json),
+            ("names", "\n{\"name\": \"Aar\", \"times\": 361}\n", SourceFormat::Ndjson),
+            ("names.txt", "name\ttimes\nAar\t361", SourceFormat::Tsv),
+            ("names.txt", "name,times", SourceFormat::Csv),
+            ("names.txt", "Name\r\nAaren\r\n", SourceFormat::Lines),
+        ];
+
+        for (path, content, format) in cases {
+            assert_eq!(format, SourceFormat::detect(path, content, &options), "{}", path);
+        }
+    }
+
+    #[test]
+    fn t_parse_json_keeps_order_and_lines() {
+        let content = "{\n  \"Zoë\": 7,\n  \"Aar\": 361, \"Abe\": 3,\n  \"aar\": 1\n}";
+
+        assert_eq!(
+            vec![
+                source_entry(2, "Zoë", 7),
+                source_entry(3, "Aar", 361),
+                source_entry(3, "Abe", 3),
+                source_entry(4, "aar", 1),
+            ],
+            parse_with_defaults(content, SourceFormat::Json).unwrap()
+        );
+    }
+
+    #[test]
+    fn t_parse_json_errors() {
+        let error = parse_with_defaults("{\n  \"Aar\": 361,\n  \"Abe\": -1\n}", SourceFormat::Json).unwrap_err();
+
+        assert_eq!(
+            "Source file has invalid content at line 3: Popularity -1 of \"Abe\" is not a whole number from 0 to 18446744073709551615",
+            error.to_string()
+        );
+        assert_eq!(
+            Err(line_error(1, "invalid type: sequence, expected an object with names and popularities")),
+            parse_with_defaults("[\"Aar\"]", SourceFormat::Json)
+        );
+    }
+
+    #[test]
+    fn t_parse_csv_with_header_mapping() {
+        let options = SourceOptions {
+            name_column: "first_name".to_string(),
+            popularity_column: "count".to_string(),
+            ..SourceOptions::default()
+        };
+        let content = "id,Count,first_name\r\n1,361,Aar\r\n\r\n2, 5,\"Smith, Jr\"\r\n";
+
+        assert_eq!(
+            vec![source_entry(2, "Aar", 361), source_entry(4, "Smith, Jr", 5)],
+            parse(content, SourceFormat::Csv, &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn t_parse_tsv_without_popularity_column() {
+        let options = SourceOptions {
+            default_popularity: 10,
+            ..SourceOptions::default()
+        };
+
+        assert_eq!(
+            vec![source_entry(2, "Aar", 10), source_entry(3, "Smith, Jr", 10)],
+            parse("name\tgender\nAar\tf\nSmith, Jr\tm", SourceFormat::Tsv, &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn t_parse_delimited_errors() {
+        let cases = [
+            ("first,times\nAar,361", line_error(1, "Missing the 'name' column")),
+            ("name,times\nAar,361\nAbe", line_error(3, "Expected 2 fields, found 1")),
+            ("name,times\n\nAar,many", line_error(3, "Popularity many of \"Aar\" is not a whole number from 0 to 18446744073709551615")),
+            ("name,times\n\"Aar,361", line_error(2, "Unclosed quote")),
+        ];
+
+        for (content, error) in cases {
+            assert_eq!(Err(error), parse_with_defaults(content, SourceFormat::Csv));
+        }
+    }
+
+    #[test]
+    fn t_parse_ndjson() {
+        let content = "{\"name\": \"Aar\", \"times\": 361}\n\n{\"name\": \"Abe\"}\n";
+
+        assert_eq!(
+            vec![source_entry(1, "Aar", 361), source_entry(3, "Abe", 0)],
+            parse_with_defaults(content, SourceFormat::Ndjson).unwrap()
+        );
+
+        let cases = [
+            ("{\"name\": \"Aar\"}\nnot json", line_error(2, "Invalid JSON: expected ident at line 1 column 2")),
+            ("{\"times\": 3}", line_error(1, "Missing or invalid 'name'")),
+            ("{\"name\": \"Aar\", \"times\": -3}", line_error(1, "Popularity -3 of \"Aar\" is not a whole number from 0 to 18446744073709551615")),
+        ];
+
+        for (content, error) in cases {
+            assert_eq!(Err(error), parse_with_defaults(content, SourceFormat::Ndjson));
+        }
+    }
+
+    #[test]
+    fn t_load_performance_data_set() {
+        let trie = load("PerformanceTestsDataSet.txt", &SourceOptions::default(), 10.into()).unwrap();
+
+        assert_eq!(4947, trie.all_words().len());
+        assert!(trie.find_word("Name").is_none());
+        assert_eq!(
+            Some(&WordData::new("Aaren".to_string(), 0)),
+            trie.find_word("aaren")
+        );
+        assert_eq!(10, trie.get_typeahead_words("a".to_string()).unwrap().len());
+    }
+
+    #[test]
+    fn t_resolve_collisions() {
+        let entries = vec![
+            source_entry(1, "Rose-marie", 3),
+            source_entry(2, "Aar", 361),
+            source_entry(3, "Rose-Marie", 40),
+            source_entry(4, "ROSE-MARIE", 40),
+            source_entry(5, "Rose-marie", 5),
+        ];
+
+        let cases = [
+            (
+                CollisionPolicy::KeepHigher,
+                vec![source_entry(3, "Rose-Marie", 40), source_entry(2, "Aar", 361)],
+            ),
+            (
+                CollisionPolicy::Sum,
+                vec![source_entry(1, "Rose-marie", 88), source_entry(2, "Aar", 361)],
+            ),
+            (
+                CollisionPolicy::KeepBoth,
+                vec![
+                    source_entry(5, "Rose-marie", 5),
+                    source_entry(2, "Aar", 361),
+                    source_entry(3, "Rose-Marie", 40),
+                    source_entry(4, "ROSE-MARIE", 40),
+                ],
+            ),
+        ];
+
+        for (policy, resolved_entries) in cases {
+            assert_eq!(resolved_entries, resolve_collisions(entries.clone(), policy).unwrap(), "{:?}", policy);
+        }
+
+        //on a tie the first one stays, so the other spelling wins with the lines reversed
+        let mut reversed_entries = entries;
+        reversed_entries.reverse();
+
+        assert_eq!(
+            vec![source_entry(4, "ROSE-MARIE", 40), source_entry(2, "Aar", 361)],
+            resolve_collisions(reversed_entries, CollisionPolicy::KeepHigher).unwrap()
+        );
+    }
+
+    #[test]
+    fn t_resolve_collisions_errors() {
+        let entries = vec![source_entry(1, "Rose-Marie", 40), source_entry(2, "Rose-marie", u64::MAX)];
+
+        assert_eq!(
+            Err(line_error(2, "Name \"Rose-marie\" differs only in casing from \"Rose-Marie\" at line 1")),
+            resolve_collisions(entries.clone(), CollisionPolicy::Reject)
+        );
+        assert_eq!(
+            Err(line_error(2, "Word popularity can not grow any further.")),
+            resolve_collisions(entries, CollisionPolicy::Sum)
+        );
+    }
+
+    #[test]
+    fn t_load_keeping_both_spellings() {
+        let path = temp_file_path("source-keep-both.csv");
+        fs::write(&path, "name,times\nRose-Marie,40\nRose-marie,3\nRosa,10\n").unwrap();
+        let options = SourceOptions {
+            collision_policy: CollisionPolicy::KeepBoth,
+            ..SourceOptions::default()
+        };
+
+        let mut trie = load(&path, &options, 10.into()).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Rose-Marie".to_string(), 40),
+            WordData::new("Rosa".to_string(), 10),
+            WordData::new("Rose-marie".to_string(), 3),
+        ];
+        assert_eq!(expected_words, trie.get_typeahead_words("ros".to_string()).unwrap());
+        assert_eq!(
+            vec![
+                WordData::new("Rose-Marie".to_string(), 40),
+                WordData::new("Rose-marie".to_string(), 3)
+            ],
+            trie.get_typeahead_words("marie".to_string()).unwrap()
+        );
+
+        //each spelling is found by its own, the first one by any other casing
+        trie.increase_popularity("Rose-marie".to_string()).unwrap();
+        assert_eq!(Some(&WordData::new("Rose-marie".to_string(), 4)), trie.find_word("Rose-marie"));
+        assert_eq!(Some(&WordData::new("Rose-Marie".to_string(), 40)), trie.find_word("ROSE-MARIE"));
+
+        trie.remove_word("Rose-Marie".to_string()).unwrap();
+        assert_eq!(Some(&WordData::new("Rose-marie".to_string(), 4)), trie.find_word("rose-marie"));
+        assert_eq!(
+            vec![WordData::new("Rose-marie".to_string(), 4)],
+            trie.get_typeahead_words("marie".to_string()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use crate::app_error::AppError;
+use crate::trie::{Node, Popularity, Trie};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+const POPULARITY_PERCENTILES: [u8; 4] = [50, 90, 99, 100];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TrieStats {
+    pub word_count: usize,
+    //root included.
+    pub node_count: usize,
+    //length of the longest key, in chars.
+    pub max_depth: usize,
+    //how many nodes have each amount of children.
+    pub branching_factors: BTreeMap<usize, usize>,
+    pub heap_size_bytes: usize,
+    //wider than a popularity, as the sum of many of them can go past its limit.
+    pub total_popularity: u128,
+    pub max_popularity: Popularity,
+    //e.g. "p90": the popularity that 90% of the names don't go over.
+    pub popularity_percentiles: BTreeMap<String, Popularity>,
+    pub source: Option<String>,
+    pub load_time_ms: Option<u128>,
+}
+
+impl TrieStats {
+    pub fn compute(trie: &Trie) -> TrieStats {
+        let mut stats = TrieStats {
+            word_count: 0,
+            node_count: 0,
+            max_depth: 0,
+            branching_factors: BTreeMap::new(),
+            heap_size_bytes: std::mem::size_of::<Node>() + trie.root.heap_size(),
+            total_popularity: 0,
+            max_popularity: 0,
+            popularity_percentiles: BTreeMap::new(),
+            source: trie.load_info.as_ref().map(|load_info| load_info.source.clone()),
+            load_time_ms: trie.load_info.as_ref().map(|load_info| load_info.duration.as_millis()),
+        };
+
+        let mut popularities = Vec::new();
+        stats.visit(&trie.root, 0, &mut popularities);
+
+        popularities.sort_unstable();
+        stats.popularity_percentiles = POPULARITY_PERCENTILES
+            .iter()
+            .filter_map(|percentile| {
+                Some((format!("p{}", percentile), percentile_of(&popularities, *percentile)?))
+            })
+            .collect();
+
+        stats
+    }
+
+    fn visit(&mut self, node: &Node, depth: usize, popularities: &mut Vec<Popularity>) {
+        self.node_count += 1;
+        self.max_depth = self.max_depth.max(depth);
+        *self.branching_factors.entry(node.children.len()).or_insert(0) += 1;
+
+        for word_data in node.words() {
+            self.word_count += 1;
+            self.total_popularity += u128::from(word_data.popularity);
+            self.max_popularity = self.max_popularity.max(word_data.popularity);
+            popularities.push(word_data.popularity);
+        }
+
+        for child_node in node.children.values() {
+            self.visit(child_node, depth + 1, popularities);
+        }
+    }
+}
+
+//nearest rank percentile of the sorted popularities.
+fn percentile_of(sorted_popularities: &[Popularity], percentile: u8) -> Option<Popularity> {
+    let rank = (sorted_popularities.len() * usize::from(percentile)).div_ceil(100);
+
+    sorted_popularities.get(rank.max(1) - 1).copied()
+}
+
+// Statistics are computed walking the whole trie under its read lock, so they are kept for a while instead of being
+// computed again on every request. Requests arriving while they are computed wait for them instead of walking it too.
+pub struct StatsCache {
+    max_age: Duration,
+    cached: Mutex<Option<(Instant, Arc<TrieStats>)>>,
+}
+
+impl StatsCache {
+    pub fn new(max_age: Duration) -> StatsCache {
+        StatsCache {
+            max_age,
+            cached: Mutex::new(None),
+        }
+    }
+
+    pub fn get(&self, shared_trie: &RwLock<Trie>) -> Result<Arc<TrieStats>, AppError> {
+        let mut cached = self.cached.lock().map_err(|_e| AppError::UnexpectedError)?;
+
+        if let Some((computed_at, stats)) = cached.as_ref() {
+            if computed_at.elapsed() < self.max_age {
+                return Ok(stats.clone());
+            }
+        }
+
+        let stats = {
+            let trie = shared_trie.read().map_err(|_e| AppError::UnexpectedError)?;
+            Arc::new(TrieStats::compute(&trie))
+        };
+        *cached = Some((Instant::now(), stats.clone()));
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trie::{ITrie, LoadInfo};
+
+    #[test]
+    fn t_compute() {
+        let mut trie = Trie::initialize("{\"Ab\": 5, \"Abc\": 10, \"Ac\": 1, \"B\": 18446744073709551615}", 10).unwrap();
+        trie.load_info = Some(LoadInfo {
+            source: "names.json".to_string(),
+            duration: Duration::from_millis(20),
+        });
+
+        let stats = TrieStats::compute(&trie);
+
+        assert_eq!(4, stats.word_count);
+        //root, a, ab, abc, ac and b
+        assert_eq!(6, stats.node_count);
+        assert_eq!(3, stats.max_depth);
+        assert_eq!(BTreeMap::from([(0, 3), (1, 1), (2, 2)]), stats.branching_factors);
+        assert_eq!(18446744073709551631, stats.total_popularity);
+        assert_eq!(u64::MAX, stats.max_popularity);
+        assert_eq!(
+            BTreeMap::from([
+                ("p50".to_string(), 5),
+                ("p90".to_string(), u64::MAX),
+                ("p99".to_string(), u64::MAX),
+                ("p100".to_string(), u64::MAX),
+            ]),
+            stats.popularity_percentiles
+        );
+        assert_eq!(Some("names.json".to_string()), stats.source);
+        assert_eq!(Some(20), stats.load_time_ms);
+        assert!(stats.heap_size_bytes > 0);
+    }
+
+    #[test]
+    fn t_compute_empty() {
+        let stats = TrieStats::compute(&Trie::initialize("{}", 10).unwrap());
+
+        assert_eq!(0, stats.word_count);
+        assert_eq!(1, stats.node_count);
+        assert!(stats.popularity_percentiles.is_empty());
+        assert_eq!(None, stats.source);
+    }
+
+    #[test]
+    fn t_percentile_of() {
+        let popularities: Vec<Popularity> = (1..=10).collect();
+
+        assert_eq!(Some(5), percentile_of(&popularities, 50));
+        assert_eq!(Some(9), percentile_of(&popularities, 90));
+        assert_eq!(Some(10), percentile_of(&popularities, 99));
+        assert_eq!(Some(1), percentile_of(&popularities, 0));
+        assert_eq!(None, percentile_of(&[], 50));
+    }
+
+    #[test]
+    fn t_cache_keeps_stats_until_max_age() {
+        let shared_trie = RwLock::new(Trie::initialize("{\"Ab\": 5}", 10).unwrap());
+        let cache = StatsCache::new(Duration::from_secs(60));
+
+        let stats = cache.get(&shared_trie).unwrap();
+        shared_trie.write().unwrap().increase_popularity("Ab".to_string()).unwrap();
+
+        assert_eq!(stats, cache.get(&shared_trie).unwrap());
+
+        let cache = StatsCache::new(Duration::ZERO);
+        assert_eq!(6, cache.get(&shared_trie).unwrap().max_popularity);
+    }
+}
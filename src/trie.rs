@@ -1,100 +1,740 @@
+use serde::de::{Deserializer, MapAccess, Visitor};
 use serde::{Deserialize, Serialize};
 
 use crate::app_error::AppError;
-use std::collections::HashMap;
+use crate::decay::{DecayedScores, DEFAULT_DECAY_HALF_LIFE};
+use crate::normalization::{index_key, normalize_key};
+use crate::ranking::{PopularityRanker, Ranker, Ranking};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime};
 
 pub trait ITrie {
-    fn initialize(file_content: &str, suggestion_number: u8) -> Result<Trie, AppError>;
-    fn insert_word(&mut self, word: String, popularity: u16) -> Result<(), AppError>;
+    fn initialize(file_content: &str, options: impl Into<TrieOptions>) -> Result<Self, AppError>
+    where
+        Self: Sized;
+    //adds the word, or replaces the one with the same casing insensitive key.
+    fn insert_word(&mut self, word: String, popularity: Popularity) -> Result<(), AppError>;
+    //removes the word with the same casing insensitive key, dropping the nodes left without words.
+    fn remove_word(&mut self, word: String) -> Result<WordData, AppError>;
     fn increase_popularity(&mut self, word: String) -> Result<WordData, AppError>;
     fn get_typeahead_words(&self, prefix: String) -> Result<Vec<WordData>, AppError>;
 }
 
+//wide enough to load any counter found in a source file and never reach the limit with increments alone.
+pub type Popularity = u64;
+
+pub const DEFAULT_MAX_FUZZY_EDITS: u8 = 2;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TrieOptions {
+    pub suggestion_number: u8,
+    //index and search names with their accents stripped, so "jose" finds "José".
+    pub accent_insensitive: bool,
+    //upper bound for the edits a fuzzy search can ask for, as the amount of visited nodes grows quickly with it.
+    pub max_fuzzy_edits: u8,
+    //how long it takes for the decayed score of a name to halve.
+    pub decay_half_life: Duration,
+}
+
+impl Default for TrieOptions {
+    fn default() -> TrieOptions {
+        TrieOptions {
+            suggestion_number: 0,
+            accent_insensitive: false,
+            max_fuzzy_edits: DEFAULT_MAX_FUZZY_EDITS,
+            decay_half_life: DEFAULT_DECAY_HALF_LIFE,
+        }
+    }
+}
+
+impl From<u8> for TrieOptions {
+    fn from(suggestion_number: u8) -> TrieOptions {
+        TrieOptions {
+            suggestion_number,
+            ..TrieOptions::default()
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Trie {
     pub root: Box<Node>,
     pub suggestion_number: u8,
+    pub accent_insensitive: bool,
+    pub max_fuzzy_edits: u8,
+    //popularity added to each name since it was loaded or last set, carried over when the names are reloaded.
+    pub popularity_increments: HashMap<String, Popularity>,
+    //names inserted, set or removed since the names were loaded, also carried over when they are reloaded.
+    pub name_changes: HashMap<String, NameChange>,
+    //recent popularity of the names, also carried over when the names are reloaded.
+    pub decayed_scores: DecayedScores,
+    //where the names were loaded from, when they come from a file.
+    pub load_info: Option<LoadInfo>,
+}
+
+//how a name matches a prefix, in the order they are suggested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MatchKind {
+    Exact,
+    Prefix,
+    InnerWord,
+}
+
+//what became of a name after the names were loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameChange {
+    //inserted, or given a new popularity, with the popularity increments on top of it.
+    Set(Popularity),
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadInfo {
+    pub source: String,
+    pub duration: Duration,
 }
 
 impl Trie {
+    #[cfg(test)]
     fn new(suggestion_number: u8) -> Trie {
+        Trie::with_options(suggestion_number.into())
+    }
+
+    pub(crate) fn with_options(options: TrieOptions) -> Trie {
         Trie {
             root: Box::new(Node::new(' ', None)),
-            suggestion_number,
+            suggestion_number: options.suggestion_number,
+            accent_insensitive: options.accent_insensitive,
+            max_fuzzy_edits: options.max_fuzzy_edits,
+            popularity_increments: HashMap::new(),
+            name_changes: HashMap::new(),
+            decayed_scores: DecayedScores::new(options.decay_half_life),
+            load_info: None,
+        }
+    }
+
+    //builds the trie from names read elsewhere, e.g. from a source file. A later name replaces an earlier one with the same key.
+    pub fn from_entries(
+        entries: impl IntoIterator<Item = (String, Popularity)>,
+        options: TrieOptions,
+    ) -> Result<Trie, AppError> {
+        let mut trie = Trie::with_options(options);
+
+        for (word, popularity) in entries {
+            trie.put_word(word, popularity)?;
         }
+
+        trie.rebuild_top_words();
+
+        Ok(trie)
+    }
+
+    //path of the word in the trie. On accent insensitive mode different names can share the same path, e.g. "Zoe" and "Zoë".
+    pub(crate) fn trie_key(&self, word: &str) -> String {
+        index_key(word, self.accent_insensitive)
+    }
+
+    //rebuilds the cached suggestions of every node. Used after bulk loading, when keeping them updated word by word would be wasteful.
+    pub fn rebuild_top_words(&mut self) {
+        Trie::build_top_words(&mut self.root, self.suggestion_number.into());
+    }
+
+    //every name in the trie, ordered by name.
+    pub fn all_words(&self) -> Vec<&WordData> {
+        self.words_with_prefix("")
     }
 
-    fn get_words_with_same_prefix(prefix_node: &Node, result_vec: &mut Vec<WordData>) {
-        for child_node in prefix_node.children.values() {
-            if let Some(word_data) = child_node.word_data.clone() {
-                result_vec.push(word_data);
+    //the names starting with the prefix, ignoring casing, ordered by name. Inner words are not taken into account.
+    pub fn words_with_prefix(&self, prefix: &str) -> Vec<&WordData> {
+        let mut node = &self.root;
+        for char in self.trie_key(prefix).chars() {
+            match node.children.get(&char) {
+                Some(child_node) => node = child_node,
+                None => return Vec::new(),
             }
+        }
+
+        let mut words = Vec::new();
+        Trie::collect_words(node, &mut words);
+        words.sort_by(|word_data_one, word_data_two| word_data_one.word.cmp(&word_data_two.word));
+
+        words
+    }
+
+    fn collect_words<'a>(node: &'a Node, words: &mut Vec<&'a WordData>) {
+        words.extend(node.words());
 
-            Trie::get_words_with_same_prefix(child_node, result_vec);
+        for child_node in node.children.values() {
+            Trie::collect_words(child_node, words);
         }
     }
-}
 
-impl ITrie for Trie {
-    fn initialize(file_content: &str, suggestion_number: u8) -> Result<Trie, AppError> {
-        let mut trie = Trie::new(suggestion_number);
+    //visits the names starting with the prefix in the order of their keys, along with their key, until visit returns false.
+    //With after, only the names whose key comes after it are visited, so a walk can go on where a previous one stopped.
+    //Unlike words_with_prefix, the names are neither collected nor sorted, only the children of each visited node.
+    pub fn visit_words_in_key_order<F>(&self, prefix: &str, after: Option<&str>, mut visit: F)
+    where
+        F: FnMut(&str, &WordData) -> bool,
+    {
+        let mut key = self.trie_key(prefix);
+        let mut node = &self.root;
+        for char in key.chars() {
+            match node.children.get(&char) {
+                Some(child_node) => node = child_node,
+                None => return,
+            }
+        }
+
+        Trie::visit_node_in_key_order(node, &mut key, after, &mut visit);
+    }
+
+    //returns false once visit asked to stop.
+    fn visit_node_in_key_order(
+        node: &Node,
+        key: &mut String,
+        after: Option<&str>,
+        visit: &mut dyn FnMut(&str, &WordData) -> bool,
+    ) -> bool {
+        if after.is_none_or(|after| key.as_str() > after) {
+            for word_data in node.words() {
+                if !visit(key, word_data) {
+                    return false;
+                }
+            }
+        }
+
+        let mut chars: Vec<char> = node.children.keys().copied().collect();
+        chars.sort_unstable();
 
-        let values: HashMap<String, u16> =
-            serde_json::from_str(file_content).map_err(|_e| AppError::InvalidFileContent)?;
+        for char in chars {
+            key.push(char);
+            //subtrees whose keys all come before after are skipped without walking them
+            let is_before = after.is_some_and(|after| key.as_str() < after && !after.starts_with(key.as_str()));
 
-        for (word, popularity) in values {
-            trie.insert_word(word, popularity)?;
+            if !is_before && !Trie::visit_node_in_key_order(&node.children[&char], key, after, visit) {
+                return false;
+            }
+            key.pop();
         }
 
-        Ok(trie)
+        true
+    }
+
+    //the first count names matching the prefix, in the order of the ranking: by default the exact matches, then the names
+    //starting with the prefix and then the ones with an inner word starting with it, each ordered by popularity desc and then
+    //by word asc. Unlike the suggestions, it walks the whole subtree while holding the read lock, so it's only used when the
+    //cached ones are not enough. Only the first count names are sorted though.
+    pub fn ranked_words_with_prefix(
+        &self,
+        prefix: &str,
+        ranking: &Ranking,
+        now: SystemTime,
+        count: usize,
+    ) -> Vec<(MatchKind, &WordData)> {
+        let mut ranked_words = self.words_matching_prefix(prefix);
+
+        let ranker = ranking.ranker(&self.decayed_scores, now);
+        ranking.keep_first(ranker.as_ref(), &mut ranked_words, count);
+
+        ranked_words
+    }
+
+    //every name matching the prefix along with how it matches it, in no particular order.
+    pub fn words_matching_prefix(&self, prefix: &str) -> Vec<(MatchKind, &WordData)> {
+        let mut node = &self.root;
+        for char in self.trie_key(prefix).chars() {
+            match node.children.get(&char) {
+                Some(child_node) => node = child_node,
+                None => return Vec::new(),
+            }
+        }
+
+        let mut ranked_words: Vec<(MatchKind, &WordData)> =
+            node.words().map(|word_data| (MatchKind::Exact, word_data)).collect();
+
+        let mut words = Vec::new();
+        for child_node in node.children.values() {
+            Trie::collect_words(child_node, &mut words);
+        }
+        ranked_words.extend(words.into_iter().map(|word_data| (MatchKind::Prefix, word_data)));
+
+        //a name is only returned once, even when several of its inner words start with the prefix
+        let mut inner_words = Vec::new();
+        Trie::collect_inner_words(node, &mut inner_words);
+
+        let mut ranked_names: HashSet<&str> = ranked_words.iter().map(|(_, word_data)| word_data.word.as_str()).collect();
+        for word_data in inner_words {
+            if ranked_names.insert(&word_data.word) {
+                ranked_words.push((MatchKind::InnerWord, word_data));
+            }
+        }
+
+        ranked_words
+    }
+
+    //the suggestions for the prefix in the order of the ranking. Only the default one can be answered from the cache.
+    pub fn get_ranked_typeahead_words(&self, prefix: String, ranking: &Ranking) -> Result<Vec<WordData>, AppError> {
+        if ranking.is_cached() {
+            return self.get_typeahead_words(prefix);
+        }
+
+        Ok(self
+            .ranked_words_with_prefix(&prefix, ranking, SystemTime::now(), self.suggestion_number.into())
+            .into_iter()
+            .map(|(_, word_data)| word_data.clone())
+            .collect())
+    }
+
+    fn collect_inner_words<'a>(node: &'a Node, inner_words: &mut Vec<&'a WordData>) {
+        inner_words.extend(&node.inner_words);
+
+        for child_node in node.children.values() {
+            Trie::collect_inner_words(child_node, inner_words);
+        }
+    }
+
+    fn build_top_words(node: &mut Node, suggestion_number: usize) {
+        for child_node in node.children.values_mut() {
+            Trie::build_top_words(child_node, suggestion_number);
+        }
+
+        node.refresh_top_words(suggestion_number);
     }
 
-    fn insert_word(&mut self, word: String, popularity: u16) -> Result<(), AppError> {
-        let mut node = &mut self.root;
-        let lowercase_word = word.to_ascii_lowercase();
+    //inserts the word, and its inner words, without touching the cached suggestions. Only a name with the same spelling is
+    //replaced, other casings are kept next to it, as the collisions of a source file are already resolved by then.
+    fn put_word(&mut self, word: String, popularity: Popularity) -> Result<(), AppError> {
+        let key = self.trie_key(&word);
+        let word_data = WordData::new(word, popularity);
+
+        for inner_word_key in inner_word_keys(&key) {
+            Trie::get_or_insert_node(&mut self.root, inner_word_key)?
+                .add_inner_word(word_data.clone());
+        }
+
+        Trie::get_or_insert_node(&mut self.root, &key)?.add_word(word_data);
+
+        Ok(())
+    }
 
-        for char in lowercase_word.chars() {
+    fn get_or_insert_node<'a>(mut node: &'a mut Node, key: &str) -> Result<&'a mut Node, AppError> {
+        for char in key.chars() {
             node.children
                 .entry(char)
                 .or_insert_with(|| Box::new(Node::new(char, None)));
 
-            // if !node.children.contains_key(&char) {
-            //     let new_node = Box::new(TrieNode::new(char.clone(), None));
-            //     node.children.insert(char, new_node);
-            // }
-
             node = node
                 .children
                 .get_mut(&char)
                 .ok_or(AppError::UnexpectedError)?;
         }
 
-        node.word_data = Some(WordData::new(word, popularity));
+        Ok(node)
+    }
 
-        Ok(())
+    //the popularity might have decreased if the word already existed, so every node on the path is rebuilt from its children on the way back.
+    fn insert_into_node(
+        node: &mut Node,
+        chars: &[char],
+        word_data: WordData,
+        suggestion_number: usize,
+        set_word: fn(&mut Node, WordData),
+    ) {
+        match chars.split_first() {
+            None => set_word(node, word_data),
+            Some((char, remaining_chars)) => {
+                let child_node = node
+                    .children
+                    .entry(*char)
+                    .or_insert_with(|| Box::new(Node::new(*char, None)));
+
+                Trie::insert_into_node(
+                    child_node,
+                    remaining_chars,
+                    word_data,
+                    suggestion_number,
+                    set_word,
+                );
+            }
+        }
+
+        node.refresh_top_words(suggestion_number);
     }
 
-    fn increase_popularity(&mut self, word: String) -> Result<WordData, AppError> {
-        let mut node = &mut self.root;
-        let lowercase_word = word.to_ascii_lowercase();
+    //the word with the same spelling or else the same casing insensitive key, if any.
+    //lookups, hits and renames all find the name this way, see Node::find_word.
+    pub fn find_word(&self, word: &str) -> Option<&WordData> {
+        let mut node = &self.root;
 
-        for char in lowercase_word.chars() {
-            node = node
-                .children
-                .get_mut(&char)
-                .ok_or(AppError::WordDoesNotExist)?;
+        for char in self.trie_key(word).chars() {
+            node = node.children.get(&char)?;
+        }
+
+        node.find_word(word)
+    }
+
+    //the word a hit on the name goes to, failing the same way as the hit would, so it can be journaled before it's applied.
+    pub fn word_to_hit(&self, word: &str) -> Result<&WordData, AppError> {
+        let word_data = self.find_word(word).ok_or_else(|| word_does_not_exist(word))?;
+        if word_data.popularity == Popularity::MAX {
+            return Err(AppError::PopularityOverflow);
+        }
+
+        Ok(word_data)
+    }
+
+    //the word a rename of the name goes to, failing the same way as the rename would, so it can be journaled before it's applied.
+    pub fn word_to_rename(&self, word: &str, new_word: &str) -> Result<&WordData, AppError> {
+        let word_data = self.find_word(word).ok_or_else(|| word_does_not_exist(word))?;
+
+        if let Some(existing_word_data) = self.find_word(new_word) {
+            if existing_word_data.word != word_data.word {
+                return Err(AppError::WordAlreadyExists);
+            }
+        }
+
+        Ok(word_data)
+    }
+
+    //gives the word a new name, e.g. to fix its casing, keeping its popularity. Fails if the new name belongs to another word.
+    pub fn rename_word(&mut self, word: String, new_word: String) -> Result<WordData, AppError> {
+        let current_word = self.word_to_rename(&word, &new_word)?.word.clone();
+
+        let increments = self.popularity_increments.remove(&current_word);
+        let decayed_score = self.decayed_scores.remove(&current_word);
+        let removed_word_data = self.remove_word(current_word)?;
+
+        self.insert_word(new_word.clone(), removed_word_data.popularity)?;
+        if let Some(increments) = increments {
+            self.popularity_increments.insert(new_word.clone(), increments);
+            //so they aren't counted twice when the names are reloaded
+            self.name_changes.insert(
+                new_word.clone(),
+                NameChange::Set(removed_word_data.popularity.saturating_sub(increments)),
+            );
+        }
+        if let Some(decayed_score) = decayed_score {
+            self.decayed_scores.insert(new_word.clone(), decayed_score);
+        }
+
+        Ok(WordData::new(new_word, removed_word_data.popularity))
+    }
+
+    //one more hit for an existing word, given at the time: it adds to both its popularity and its decayed score.
+    pub fn hit(&mut self, word: String, at: SystemTime) -> Result<WordData, AppError> {
+        let updated_word_data = self.add_popularity(word, 1)?;
+        self.decayed_scores.add(&updated_word_data.word, 1, at);
+
+        Ok(updated_word_data)
+    }
+
+    //adds to the popularity of an existing word, keeping track of the amount in popularity_increments.
+    pub fn add_popularity(&mut self, word: String, amount: Popularity) -> Result<WordData, AppError> {
+        let chars: Vec<char> = self.trie_key(&word).chars().collect();
+        let suggestion_number = self.suggestion_number.into();
+
+        let updated_word_data =
+            Trie::increase_popularity_in_node(&mut self.root, &chars, &word, amount, suggestion_number)?;
+
+        for inner_word_key in inner_word_keys(&self.trie_key(&updated_word_data.word)) {
+            let inner_word_chars: Vec<char> = inner_word_key.chars().collect();
+
+            Trie::promote_inner_word_in_node(
+                &mut self.root,
+                &inner_word_chars,
+                &updated_word_data,
+                suggestion_number,
+            );
+        }
+
+        *self
+            .popularity_increments
+            .entry(updated_word_data.word.clone())
+            .or_insert(0) += amount;
+
+        Ok(updated_word_data)
+    }
+
+    //the cached suggestions along the path are rebuilt, as the removed word may have to make room for one that was left out.
+    fn remove_from_node(
+        node: &mut Node,
+        chars: &[char],
+        word: &str,
+        suggestion_number: usize,
+        take_word: fn(&mut Node, &str) -> Option<WordData>,
+    ) -> Option<WordData> {
+        let removed_word_data = match chars.split_first() {
+            None => take_word(node, word),
+            Some((char, remaining_chars)) => {
+                let child_node = node.children.get_mut(char)?;
+                let removed_word_data = Trie::remove_from_node(
+                    child_node,
+                    remaining_chars,
+                    word,
+                    suggestion_number,
+                    take_word,
+                );
+
+                if child_node.is_empty() {
+                    node.children.remove(char);
+                }
+
+                removed_word_data
+            }
+        };
+
+        if removed_word_data.is_some() {
+            node.refresh_top_words(suggestion_number);
         }
 
-        let mut updated_word_data = node.word_data.clone().ok_or(AppError::WordDoesNotExist)?;
-        updated_word_data.popularity += 1;
-        node.word_data = Some(updated_word_data.clone());
+        removed_word_data
+    }
+
+    //popularity only goes up here, so each node on the path just needs to promote the word in its cached suggestions.
+    fn increase_popularity_in_node(
+        node: &mut Node,
+        chars: &[char],
+        word: &str,
+        amount: Popularity,
+        suggestion_number: usize,
+    ) -> Result<WordData, AppError> {
+        let updated_word_data = match chars.split_first() {
+            None => {
+                let word_data = node.find_word_mut(word).ok_or_else(|| word_does_not_exist(word))?;
+                word_data.popularity = word_data
+                    .popularity
+                    .checked_add(amount)
+                    .ok_or(AppError::PopularityOverflow)?;
+                word_data.clone()
+            }
+            Some((char, remaining_chars)) => {
+                let child_node = node
+                    .children
+                    .get_mut(char)
+                    .ok_or_else(|| word_does_not_exist(word))?;
+
+                Trie::increase_popularity_in_node(
+                    child_node,
+                    remaining_chars,
+                    word,
+                    amount,
+                    suggestion_number,
+                )?
+            }
+        };
+
+        node.promote_top_word(&updated_word_data, suggestion_number);
 
         Ok(updated_word_data)
     }
 
+    //copies the new popularity of a word to the entry of one of its inner words.
+    fn promote_inner_word_in_node(
+        node: &mut Node,
+        chars: &[char],
+        word_data: &WordData,
+        suggestion_number: usize,
+    ) {
+        match chars.split_first() {
+            None => {
+                if let Some(inner_word) = node
+                    .inner_words
+                    .iter_mut()
+                    .find(|inner_word| inner_word.word == word_data.word)
+                {
+                    *inner_word = word_data.clone();
+                }
+            }
+            Some((char, remaining_chars)) => {
+                if let Some(child_node) = node.children.get_mut(char) {
+                    Trie::promote_inner_word_in_node(
+                        child_node,
+                        remaining_chars,
+                        word_data,
+                        suggestion_number,
+                    );
+                }
+            }
+        }
+
+        node.promote_top_inner_word(word_data, suggestion_number);
+    }
+}
+
+//builds the typeahead result of a prefix from the words whose key is exactly the prefix and the cached suggestions of its node.
+pub(crate) fn merge_suggestions<'a>(
+    ranker: &dyn Ranker,
+    exact_words: impl Iterator<Item = &'a WordData>,
+    top_words: &[WordData],
+    top_inner_words: &[WordData],
+    suggestion_number: usize,
+) -> Vec<WordData> {
+    //insert words that match prefix at first positions. There is more than one only when several names share the same key.
+    let mut words_with_same_prefix: Vec<WordData> = exact_words.cloned().collect();
+    words_with_same_prefix.sort_by(|word_data_one, word_data_two| ranker.compare(word_data_one, word_data_two));
+    let exact_matches = words_with_same_prefix.len();
+
+    //the cached suggestions are already ordered by popularity desc and then by word asc
+    for word_data in top_words {
+        if !words_with_same_prefix[..exact_matches].contains(word_data) {
+            words_with_same_prefix.push(word_data.clone());
+        }
+    }
+
+    //names that only have an inner word starting with the prefix come after the ones that start with it
+    for word_data in top_inner_words {
+        if !words_with_same_prefix
+            .iter()
+            .any(|same_prefix_word| same_prefix_word.word == word_data.word)
+        {
+            words_with_same_prefix.push(word_data.clone());
+        }
+    }
+
+    //return only SUGGESTION_NUMBER items
+    words_with_same_prefix.truncate(suggestion_number);
+
+    words_with_same_prefix
+}
+
+//keeps the best SUGGESTION_NUMBER words, ordered by popularity desc and then by word asc.
+pub(crate) fn rank_top_words<'a>(
+    words: impl Iterator<Item = &'a WordData>,
+    suggestion_number: usize,
+) -> Vec<WordData> {
+    let mut top_words: Vec<WordData> = words.cloned().collect();
+
+    top_words.sort_by(compare_word_data);
+    top_words.truncate(suggestion_number);
+
+    top_words
+}
+
+//approximate amount of heap memory held by a list of words, strings included.
+pub(crate) fn words_heap_size(words: &Vec<WordData>) -> usize {
+    words.capacity() * size_of::<WordData>()
+        + words.iter().map(|word_data| word_data.word.capacity()).sum::<usize>()
+}
+
+//keys of the inner words of a name, each one running until the end of the name. E.g., "mary ann smith" gives "ann smith" and
+//"smith", so typing "ann" or "smi" finds "Mary Ann Smith". The first word is left out as it is the name's own key.
+pub(crate) fn inner_word_keys(key: &str) -> Vec<&str> {
+    let mut inner_word_keys = Vec::new();
+    let mut previous_char_is_separator = false;
+
+    for (index, char) in key.char_indices() {
+        let is_separator = char == ' ' || char == '-';
+
+        if previous_char_is_separator && !is_separator {
+            inner_word_keys.push(&key[index..]);
+        }
+
+        previous_char_is_separator = is_separator;
+    }
+
+    inner_word_keys
+}
+
+pub(crate) fn word_does_not_exist(word: &str) -> AppError {
+    AppError::WordDoesNotExist {
+        name: word.to_string(),
+    }
+}
+
+//order by popularity desc and then by word asc
+pub(crate) fn compare_word_data(word_data_one: &WordData, word_data_two: &WordData) -> Ordering {
+    word_data_two
+        .popularity
+        .cmp(&word_data_one.popularity)
+        .then(word_data_one.word.cmp(&word_data_two.word))
+}
+
+impl ITrie for Trie {
+    //reads a JSON object of names and popularities as is, without the validation and collision policy of a source file.
+    fn initialize(file_content: &str, options: impl Into<TrieOptions>) -> Result<Trie, AppError> {
+        //read in the order of the file, so that names sharing a node always end up in the same order
+        let OrderedEntries(entries) = serde_json::from_str(file_content).map_err(|_e| AppError::InvalidFileContent)?;
+
+        Trie::from_entries(entries, options.into())
+    }
+
+    fn insert_word(&mut self, word: String, popularity: Popularity) -> Result<(), AppError> {
+        let chars: Vec<char> = self.trie_key(&word).chars().collect();
+        let suggestion_number = self.suggestion_number.into();
+        let replaced_word = self.find_word(&word).map(|word_data| word_data.word.clone());
+
+        //the new popularity replaces the increments too
+        if let Some(replaced_word) = replaced_word {
+            self.popularity_increments.remove(&replaced_word);
+            if replaced_word != word {
+                self.name_changes.insert(replaced_word, NameChange::Removed);
+            }
+        }
+        self.name_changes.insert(word.clone(), NameChange::Set(popularity));
+
+        let word_data = WordData::new(word, popularity);
+
+        for inner_word_key in inner_word_keys(&self.trie_key(&word_data.word)) {
+            let inner_word_chars: Vec<char> = inner_word_key.chars().collect();
+
+            Trie::insert_into_node(
+                &mut self.root,
+                &inner_word_chars,
+                word_data.clone(),
+                suggestion_number,
+                Node::set_inner_word,
+            );
+        }
+
+        Trie::insert_into_node(
+            &mut self.root,
+            &chars,
+            word_data,
+            suggestion_number,
+            Node::set_word,
+        );
+
+        Ok(())
+    }
+
+    fn remove_word(&mut self, word: String) -> Result<WordData, AppError> {
+        let chars: Vec<char> = self.trie_key(&word).chars().collect();
+        let suggestion_number = self.suggestion_number.into();
+
+        let removed_word_data =
+            Trie::remove_from_node(&mut self.root, &chars, &word, suggestion_number, Node::take_word)
+                .ok_or_else(|| word_does_not_exist(&word))?;
+
+        for inner_word_key in inner_word_keys(&self.trie_key(&removed_word_data.word)) {
+            let inner_word_chars: Vec<char> = inner_word_key.chars().collect();
+
+            Trie::remove_from_node(
+                &mut self.root,
+                &inner_word_chars,
+                &removed_word_data.word,
+                suggestion_number,
+                Node::take_inner_word,
+            );
+        }
+
+        self.popularity_increments.remove(&removed_word_data.word);
+        self.name_changes.insert(removed_word_data.word.clone(), NameChange::Removed);
+        self.decayed_scores.remove(&removed_word_data.word);
+
+        Ok(removed_word_data)
+    }
+
+    fn increase_popularity(&mut self, word: String) -> Result<WordData, AppError> {
+        self.hit(word, SystemTime::now())
+    }
+
     fn get_typeahead_words(&self, prefix: String) -> Result<Vec<WordData>, AppError> {
         let mut node = &self.root;
-        let prefix = prefix.to_ascii_lowercase();
+        let prefix = self.trie_key(&prefix);
 
         for char in prefix.chars() {
             if let Some(new_node) = node.children.get(&char) {
@@ -105,36 +745,62 @@ impl ITrie for Trie {
             }
         }
 
-        let mut words_with_same_prefix: Vec<WordData> = Vec::new();
-        let prefix_word_data: Option<WordData> = node.word_data.clone();
+        //the cached suggestions are in the order of the popularity ranker, so the exact matches are ranked the same way
+        Ok(merge_suggestions(
+            &PopularityRanker,
+            node.words(),
+            &node.top_words,
+            &node.top_inner_words,
+            self.suggestion_number.into(),
+        ))
+    }
+}
+
+// Names and popularities of a JSON object in the order they are written, keeping the repeated ones, which a map would
+// silently merge.
+pub struct OrderedEntries<V>(pub Vec<(String, V)>);
 
-        Trie::get_words_with_same_prefix(node, &mut words_with_same_prefix);
+impl<'de, V: Deserialize<'de>> Deserialize<'de> for OrderedEntries<V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OrderedEntriesVisitor<V>(PhantomData<V>);
 
-        //order by popularity desc and then by word asc
-        words_with_same_prefix.sort_by(|word_data_one, word_data_two| {
-            word_data_two
-                .popularity
-                .cmp(&word_data_one.popularity)
-                .then(word_data_one.word.cmp(&word_data_two.word))
-        });
+        impl<'de, V: Deserialize<'de>> Visitor<'de> for OrderedEntriesVisitor<V> {
+            type Value = OrderedEntries<V>;
 
-        //insert word that match prefix at first position
-        if let Some(word_data) = prefix_word_data {
-            words_with_same_prefix.splice(0..0, vec![word_data].iter().cloned());
-        }
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an object with names and popularities")
+            }
 
-        //return only SUGGESTION_NUMBER items
-        words_with_same_prefix.truncate(self.suggestion_number.into());
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<OrderedEntries<V>, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
 
-        Ok(words_with_same_prefix)
+                Ok(OrderedEntries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(OrderedEntriesVisitor(PhantomData))
     }
 }
 
-#[derive(Debug, Clone)]
+//also the layout of the binary index: changing it requires bumping INDEX_FORMAT_VERSION.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Node {
     pub children: HashMap<char, Box<Node>>,
+    //redundant with the key in the parent's children, only read when debugging.
+    #[allow(dead_code)]
     pub letter: char,
     pub word_data: Option<WordData>,
+    //other names sharing this node's key, e.g. "Zoë" next to "Zoe" on accent insensitive mode.
+    pub variants: Vec<WordData>,
+    //names with an inner word whose key ends at this node, e.g. "Rose-Marie" on the node of "marie".
+    pub inner_words: Vec<WordData>,
+    //best SUGGESTION_NUMBER words of this subtree (own word included), ordered by popularity desc and then by word asc.
+    pub top_words: Vec<WordData>,
+    //same as top_words, but for the inner words of this subtree.
+    pub top_inner_words: Vec<WordData>,
 }
 
 impl Node {
@@ -143,22 +809,211 @@ impl Node {
             children: HashMap::new(),
             letter,
             word_data,
+            variants: Vec::new(),
+            inner_words: Vec::new(),
+            top_words: Vec::new(),
+            top_inner_words: Vec::new(),
+        }
+    }
+
+    pub fn words(&self) -> impl Iterator<Item = &WordData> {
+        self.word_data.iter().chain(self.variants.iter())
+    }
+
+    //position in words() of the name with the same spelling, or else with the same casing insensitive key. Both can be
+    //there when the source file was loaded keeping every casing.
+    fn word_position(&self, word: &str) -> Option<usize> {
+        self.words().position(|word_data| word_data.word == word).or_else(|| {
+            let key = normalize_key(word);
+            self.words()
+                .position(|word_data| normalize_key(&word_data.word) == key)
+        })
+    }
+
+    fn word_at_mut(&mut self, position: usize) -> Option<&mut WordData> {
+        match position {
+            0 => self.word_data.as_mut(),
+            _ => self.variants.get_mut(position - 1),
+        }
+    }
+
+    //replaces the matching name, or adds it as a new variant.
+    fn set_word(&mut self, word_data: WordData) {
+        let position = self.word_position(&word_data.word);
+
+        match position {
+            Some(position) => *self.word_at_mut(position).expect("position of an existing word") = word_data,
+            None => self.add_word(word_data),
+        }
+    }
+
+    //replaces the name with the same spelling, or adds it as a new variant.
+    fn add_word(&mut self, word_data: WordData) {
+        let position = self.words().position(|current| current.word == word_data.word);
+
+        match position {
+            Some(position) => *self.word_at_mut(position).expect("position of an existing word") = word_data,
+            None if self.word_data.is_none() => self.word_data = Some(word_data),
+            None => self.variants.push(word_data),
+        }
+    }
+
+    fn set_inner_word(&mut self, word_data: WordData) {
+        let position = self
+            .inner_words
+            .iter()
+            .position(|inner_word| inner_word.word == word_data.word)
+            .or_else(|| {
+                let key = normalize_key(&word_data.word);
+                self.inner_words
+                    .iter()
+                    .position(|inner_word| normalize_key(&inner_word.word) == key)
+            });
+
+        match position {
+            Some(position) => self.inner_words[position] = word_data,
+            None => self.inner_words.push(word_data),
+        }
+    }
+
+    fn add_inner_word(&mut self, word_data: WordData) {
+        match self
+            .inner_words
+            .iter_mut()
+            .find(|inner_word| inner_word.word == word_data.word)
+        {
+            Some(inner_word) => *inner_word = word_data,
+            None => self.inner_words.push(word_data),
         }
     }
+
+    //takes out the name with the same casing insensitive key, moving the first variant to its place.
+    fn take_word(&mut self, word: &str) -> Option<WordData> {
+        let position = self.word_position(word)?;
+
+        match position {
+            0 => {
+                let removed_word_data = self.word_data.take();
+                if !self.variants.is_empty() {
+                    self.word_data = Some(self.variants.remove(0));
+                }
+
+                removed_word_data
+            }
+            position => Some(self.variants.remove(position - 1)),
+        }
+    }
+
+    fn take_inner_word(&mut self, word: &str) -> Option<WordData> {
+        let index = self
+            .inner_words
+            .iter()
+            .position(|inner_word| inner_word.word == word)?;
+
+        Some(self.inner_words.remove(index))
+    }
+
+    //a node without words nor children is only a leftover of removed words.
+    fn is_empty(&self) -> bool {
+        self.children.is_empty() && self.word_data.is_none() && self.inner_words.is_empty()
+    }
+
+    //the word of the node a lookup of, or a change to, the name goes to: its only one, so "jose" finds "José" on accent
+    //insensitive mode, or else the one with the name's spelling or casing.
+    fn find_word(&self, word: &str) -> Option<&WordData> {
+        if self.variants.is_empty() {
+            return self.word_data.as_ref();
+        }
+
+        self.words().nth(self.word_position(word)?)
+    }
+
+    fn find_word_mut(&mut self, word: &str) -> Option<&mut WordData> {
+        if self.variants.is_empty() {
+            return self.word_data.as_mut();
+        }
+
+        let position = self.word_position(word)?;
+        self.word_at_mut(position)
+    }
+
+    //recomputes the cached suggestions from the node's own words and the cached suggestions of its children.
+    fn refresh_top_words(&mut self, suggestion_number: usize) {
+        self.top_words = rank_top_words(
+            self.words()
+                .chain(self.children.values().flat_map(|child_node| &child_node.top_words)),
+            suggestion_number,
+        );
+        self.top_inner_words = rank_top_words(
+            self.inner_words
+                .iter()
+                .chain(self.children.values().flat_map(|child_node| &child_node.top_inner_words)),
+            suggestion_number,
+        );
+    }
+
+    //approximate amount of heap memory held by the node and its subtree.
+    pub fn heap_size(&self) -> usize {
+        //every slot of the hash map holds the entry plus one control byte
+        let children_size = self.children.capacity() * (size_of::<(char, Box<Node>)>() + 1)
+            + self
+                .children
+                .values()
+                .map(|child_node| size_of::<Node>() + child_node.heap_size())
+                .sum::<usize>();
+
+        children_size
+            + self.word_data.as_ref().map_or(0, |word_data| word_data.word.capacity())
+            + words_heap_size(&self.variants)
+            + words_heap_size(&self.inner_words)
+            + words_heap_size(&self.top_words)
+            + words_heap_size(&self.top_inner_words)
+    }
+
+    //updates the cached suggestions after the popularity of a word of this subtree went up.
+    fn promote_top_word(&mut self, word_data: &WordData, suggestion_number: usize) {
+        promote_word(&mut self.top_words, word_data, suggestion_number);
+    }
+
+    fn promote_top_inner_word(&mut self, word_data: &WordData, suggestion_number: usize) {
+        promote_word(&mut self.top_inner_words, word_data, suggestion_number);
+    }
+}
+
+pub(crate) fn promote_word(
+    top_words: &mut Vec<WordData>,
+    word_data: &WordData,
+    suggestion_number: usize,
+) {
+    if let Some(position) = top_words
+        .iter()
+        .position(|top_word| top_word.word == word_data.word)
+    {
+        top_words.remove(position);
+    }
+
+    let position = top_words
+        .binary_search_by(|top_word| compare_word_data(top_word, word_data))
+        .unwrap_or_else(|position| position);
+
+    if position < suggestion_number {
+        top_words.insert(position, word_data.clone());
+        top_words.truncate(suggestion_number);
+    }
 }
 
-//storing the word in the node so we can work with lowercase all over the way avoiding case insensitive problems.
-//assuming we can't have 2 same words but with different casing. E.g., Rose-Marie and Rose-marie
+//storing the word in the node so we can work with normalized keys all over the way avoiding case insensitive problems.
+//2 same words with different casing, e.g. Rose-Marie and Rose-marie, are only both kept with the keep_both collision policy
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct WordData {
     #[serde(rename(serialize = "name"))]
     pub word: String,
     #[serde(rename(serialize = "times"))]
-    pub popularity: u16,
+    pub popularity: Popularity,
 }
 
 impl WordData {
-    pub fn new(word: String, popularity: u16) -> WordData {
+    pub fn new(word: String, popularity: Popularity) -> WordData {
         WordData { word, popularity }
     }
 }
@@ -166,8 +1021,10 @@ impl WordData {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::{Arc, RwLock};
+    use std::thread;
 
-    fn compare_tries(root_a: &Box<Node>, root_b: &Box<Node>) -> bool {
+    fn compare_tries(root_a: &Node, root_b: &Node) -> bool {
         //two comparisons because order can change.
         println!("Comparing b to a");
         let is_b_equal_a = recursively_compare_tries(root_a, root_b);
@@ -178,7 +1035,7 @@ mod tests {
         is_b_equal_a && is_a_equal_b
     }
 
-    fn recursively_compare_tries(node_a: &Box<Node>, node_b: &Box<Node>) -> bool {
+    fn recursively_compare_tries(node_a: &Node, node_b: &Node) -> bool {
         let mut return_value = true;
 
         if node_a.letter != node_b.letter || node_a.word_data != node_b.word_data {
@@ -187,22 +1044,25 @@ mod tests {
         }
 
         for child_a in &node_a.children {
-            let child_b = node_b.children.get(&child_a.0);
-
-            if child_b.is_none() || return_value == false {
-                println!("child_a: {:?}, child_b: {:?}", child_a, child_b);
-                return false;
-            } else {
-                return_value = recursively_compare_tries(&child_a.1, child_b.unwrap());
+            let child_b = node_b.children.get(child_a.0);
+
+            match child_b {
+                Some(child_b) if return_value => {
+                    return_value = recursively_compare_tries(child_a.1, child_b);
+                }
+                _ => {
+                    println!("child_a: {:?}, child_b: {:?}", child_a, child_b);
+                    return false;
+                }
             }
         }
 
         return_value
     }
 
-    fn print_trie(node: &Box<Node>, mut i: u8) {
+    fn print_trie(node: &Node, mut i: u8) {
         println!("[{}] {}-{:?}", i, node.letter, node.word_data);
-        i = i + 1;
+        i += 1;
         for child in &node.children {
             print_trie(child.1, i);
         }
@@ -296,6 +1156,8 @@ mod tests {
             Box::new(Node::new('h', Some(WordData::new("Bah".to_string(), 5)))),
         );
 
+        expected_trie.rebuild_top_words();
+
         expected_trie
     }
 
@@ -397,6 +1259,8 @@ mod tests {
             Box::new(Node::new('a', Some(WordData::new("Ca".to_string(), 150)))),
         );
 
+        expected_trie.rebuild_top_words();
+
         expected_trie
     }
 
@@ -478,6 +1342,8 @@ mod tests {
             Box::new(Node::new('h', Some(WordData::new("Bah".to_string(), 5)))),
         );
 
+        expected_trie.rebuild_top_words();
+
         expected_trie
     }
 
@@ -489,58 +1355,201 @@ mod tests {
 
         let expected_trie = initialize_testing_trie();
 
-        print_trie(&expected_trie.root, 0);
-        print_trie(&trie.root, 0);
+        print_trie(&expected_trie.root, 0);
+        print_trie(&trie.root, 0);
+
+        assert!(compare_tries(&trie.root, &expected_trie.root));
+    }
+
+    #[test]
+    fn t_all_words() {
+        let options = TrieOptions {
+            accent_insensitive: true,
+            ..TrieOptions::default()
+        };
+        let trie = Trie::initialize("{\"Zoë\": 5, \"Rose-Marie\": 7, \"Zoe\": 9, \"Marie\": 3}", options).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Marie".to_string(), 3),
+            WordData::new("Rose-Marie".to_string(), 7),
+            WordData::new("Zoe".to_string(), 9),
+            WordData::new("Zoë".to_string(), 5),
+        ];
+
+        assert_eq!(expected_words.iter().collect::<Vec<_>>(), trie.all_words());
+    }
+
+    #[test]
+    fn t_initialize_invalid_file_content() {
+        let file_content = "";
+        let error = Trie::initialize(file_content, 10).unwrap_err();
+
+        assert_eq!(error, AppError::InvalidFileContent);
+    }
+
+    #[test]
+    fn t_insert_word_ok() {
+        let mut trie = initialize_testing_trie();
+        let word = "Ca".to_string();
+        let popularity = 150;
+        trie.insert_word(word, popularity).unwrap();
+
+        let expected_trie = insert_word_testing_trie();
+
+        print_trie(&trie.root, 0);
+        print_trie(&expected_trie.root, 0);
+
+        assert!(compare_tries(&expected_trie.root, &trie.root));
+    }
+
+    #[test]
+    fn t_increase_popularity_word_exists() {
+        let file_content =
+        "{\"Aar\":361,\"Aari\":151,\"Aba\":608,\"Abag\":704, \"Abe\": 300, \"Ba\": 5, \"Bah\": 5, \"Be\": 50, \"Bc\": 50}";
+        let mut trie = Trie::initialize(file_content, 10).unwrap();
+        let _result = trie.increase_popularity("Abe".to_string()).unwrap();
+
+        let expected_trie = increase_popularity_testing_trie();
+
+        // print_trie(&trie.root, 0);
+        // print_trie(&expected_trie.root, 0);
+
+        assert!(compare_tries(&trie.root, &expected_trie.root));
+    }
+
+    #[test]
+    fn t_increase_popularity_word_does_not_exist() {
+        let file_content =
+        "{\"Aar\":361,\"Aari\":151,\"Aba\":608,\"Abag\":704, \"Abe\": 300, \"Ba\": 5, \"Bah\": 5, \"Be\": 50, \"Bc\": 50}";
+        let mut trie = Trie::initialize(file_content, 10).unwrap();
+        let error = trie.increase_popularity("Abcd".to_string()).unwrap_err();
+
+        assert_eq!(error, word_does_not_exist("Abcd"));
+    }
+
+    #[test]
+    fn t_initialize_builds_top_words() {
+        let file_content =
+            "{\"A-b\": 23, \"Aar\":361,\"Aari\":151,\"Aba\":608,\"Abag\":704, \"Abe\": 300, \"Ba\": 5, \"Bah\": 5, \"Be\": 50, \"Bc\": 50}";
+        let trie = Trie::initialize(file_content, 2).unwrap();
+
+        let expected_root_words: Vec<WordData> = vec![
+            WordData::new("Abag".to_string(), 704),
+            WordData::new("Aba".to_string(), 608),
+        ];
+        let expected_b_words: Vec<WordData> = vec![
+            WordData::new("Bc".to_string(), 50),
+            WordData::new("Be".to_string(), 50),
+        ];
+
+        assert_eq!(expected_root_words, trie.root.top_words);
+        assert_eq!(expected_b_words, trie.root.children.get(&'b').unwrap().top_words);
+    }
+
+    #[test]
+    fn t_insert_word_lower_popularity_refreshes_top_words() {
+        let mut trie = initialize_testing_trie();
+        trie.suggestion_number = 2;
+        trie.insert_word("Abag".to_string(), 1).unwrap();
+
+        let words = trie.get_typeahead_words("Ab".to_string()).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aba".to_string(), 608),
+            WordData::new("Abe".to_string(), 300),
+        ];
+
+        assert_eq!(expected_words, words);
+    }
+
+    #[test]
+    fn t_increase_popularity_promotes_top_words() {
+        let mut trie = initialize_testing_trie();
+        trie.suggestion_number = 2;
+        trie.increase_popularity("Be".to_string()).unwrap();
+
+        let words = trie.get_typeahead_words("B".to_string()).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Be".to_string(), 51),
+            WordData::new("Bc".to_string(), 50),
+        ];
+
+        assert_eq!(expected_words, words);
+    }
+
+    #[test]
+    fn t_initialize_popularity_wider_than_u16() {
+        let file_content = "{\"Aar\": 4294967296, \"Aari\": 18446744073709551615, \"Ab\": 65536}";
+        let trie = Trie::initialize(file_content, 10).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aari".to_string(), u64::MAX),
+            WordData::new("Aar".to_string(), 4_294_967_296),
+            WordData::new("Ab".to_string(), 65_536),
+        ];
 
-        assert!(compare_tries(&trie.root, &expected_trie.root));
+        assert_eq!(expected_words, trie.get_typeahead_words("a".to_string()).unwrap());
     }
 
     #[test]
-    fn t_initialize_invalid_file_content() {
-        let file_content = "";
+    fn t_initialize_popularity_out_of_range() {
+        let file_content = "{\"Aar\": 18446744073709551616}";
+        let error = Trie::initialize(file_content, 10).unwrap_err();
+
+        assert_eq!(error, AppError::InvalidFileContent);
+
+        let file_content = "{\"Aar\": -1}";
         let error = Trie::initialize(file_content, 10).unwrap_err();
 
         assert_eq!(error, AppError::InvalidFileContent);
     }
 
     #[test]
-    fn t_insert_word_ok() {
-        let mut trie = initialize_testing_trie();
-        let word = "Ca".to_string();
-        let popularity = 150;
-        trie.insert_word(word, popularity).unwrap();
+    fn t_increase_popularity_overflow() {
+        let file_content = "{\"Aar\": 18446744073709551614, \"Aari\": 10}";
+        let mut trie = Trie::initialize(file_content, 10).unwrap();
 
-        let expected_trie = insert_word_testing_trie();
+        assert_eq!(
+            WordData::new("Aar".to_string(), u64::MAX),
+            trie.increase_popularity("Aar".to_string()).unwrap()
+        );
 
-        print_trie(&trie.root, 0);
-        print_trie(&expected_trie.root, 0);
+        let error = trie.increase_popularity("Aar".to_string()).unwrap_err();
 
-        assert!(compare_tries(&expected_trie.root, &trie.root));
+        assert_eq!(error, AppError::PopularityOverflow);
+
+        //the counter stays at its max and the rest of the trie keeps working
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aar".to_string(), u64::MAX),
+            WordData::new("Aari".to_string(), 11),
+        ];
+        trie.increase_popularity("Aari".to_string()).unwrap();
+
+        assert_eq!(expected_words, trie.get_typeahead_words("a".to_string()).unwrap());
     }
 
     #[test]
-    fn t_increase_popularity_word_exists() {
-        let file_content =
-        "{\"Aar\":361,\"Aari\":151,\"Aba\":608,\"Abag\":704, \"Abe\": 300, \"Ba\": 5, \"Bah\": 5, \"Be\": 50, \"Bc\": 50}";
-        let mut trie = Trie::initialize(file_content, 10).unwrap();
-        let _result = trie.increase_popularity("Abe".to_string()).unwrap();
+    fn t_add_popularity_counts_increments() {
+        let mut trie = Trie::initialize("{\"Aar\": 361, \"Rose-Marie\": 10, \"Marie\": 12}", 10).unwrap();
 
-        let expected_trie = increase_popularity_testing_trie();
+        trie.add_popularity("Rose-Marie".to_string(), 5).unwrap();
+        trie.increase_popularity("ROSE-MARIE".to_string()).unwrap();
+        trie.increase_popularity("Aar".to_string()).unwrap();
+        trie.increase_popularity("Brazil".to_string()).unwrap_err();
 
-        // print_trie(&trie.root, 0);
-        // print_trie(&expected_trie.root, 0);
+        let expected_increments: HashMap<String, Popularity> =
+            vec![("Rose-Marie".to_string(), 6), ("Aar".to_string(), 1)].into_iter().collect();
 
-        assert!(compare_tries(&trie.root, &expected_trie.root));
-    }
+        assert_eq!(expected_increments, trie.popularity_increments);
 
-    #[test]
-    fn t_increase_popularity_word_does_not_exist() {
-        let file_content =
-        "{\"Aar\":361,\"Aari\":151,\"Aba\":608,\"Abag\":704, \"Abe\": 300, \"Ba\": 5, \"Bah\": 5, \"Be\": 50, \"Bc\": 50}";
-        let mut trie = Trie::initialize(file_content, 10).unwrap();
-        let error = trie.increase_popularity("Abcd".to_string()).unwrap_err();
+        //the inner word follows the new popularity too
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Marie".to_string(), 12),
+            WordData::new("Rose-Marie".to_string(), 16),
+        ];
 
-        assert_eq!(error, AppError::WordDoesNotExist);
+        assert_eq!(expected_words, trie.get_typeahead_words("marie".to_string()).unwrap());
     }
 
     #[test]
@@ -701,4 +1710,533 @@ mod tests {
 
         assert_eq!(expected_words, words);
     }
+
+    #[test]
+    fn t_concurrent_reads_and_increases() {
+        let trie = initialize_testing_trie();
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
+        let increases_per_thread = 200;
+
+        let writers: Vec<_> = ["Aar", "Abe", "Bah", "Be"]
+            .iter()
+            .map(|word| {
+                let shared_trie = Arc::clone(&shared_trie);
+                thread::spawn(move || {
+                    for _ in 0..increases_per_thread {
+                        shared_trie
+                            .write()
+                            .unwrap()
+                            .increase_popularity(word.to_string())
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        let readers: Vec<_> = ["", "A", "Ab", "B", "Brazil"]
+            .iter()
+            .map(|prefix| {
+                let shared_trie = Arc::clone(&shared_trie);
+                thread::spawn(move || {
+                    for _ in 0..increases_per_thread {
+                        let words = shared_trie
+                            .read()
+                            .unwrap()
+                            .get_typeahead_words(prefix.to_string())
+                            .unwrap();
+
+                        //results must always come sorted, even while popularity is changing
+                        let exact_match = usize::from(
+                            words.first().map(|word_data| word_data.word.to_ascii_lowercase())
+                                == Some(prefix.to_ascii_lowercase()),
+                        );
+                        assert!(words[exact_match..]
+                            .windows(2)
+                            .all(|pair| compare_word_data(&pair[0], &pair[1]) == Ordering::Less));
+                    }
+                })
+            })
+            .collect();
+
+        for handle in writers.into_iter().chain(readers) {
+            handle.join().unwrap();
+        }
+
+        let trie = shared_trie.read().unwrap();
+        let words = trie.get_typeahead_words("B".to_string()).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Be".to_string(), 250),
+            WordData::new("Bah".to_string(), 205),
+            WordData::new("Bc".to_string(), 50),
+            WordData::new("Ba".to_string(), 5),
+        ];
+
+        assert_eq!(expected_words, words);
+        assert_eq!(
+            vec![WordData::new("Aar".to_string(), 561), WordData::new("Aari".to_string(), 151)],
+            trie.get_typeahead_words("Aar".to_string()).unwrap()
+        );
+        assert_eq!(
+            vec![WordData::new("Abe".to_string(), 500)],
+            trie.get_typeahead_words("Abe".to_string()).unwrap()
+        );
+    }
+
+    #[test]
+    fn t_concurrent_reads_do_not_block_each_other() {
+        let trie = initialize_testing_trie();
+        let shared_trie: Arc<RwLock<Trie>> = Arc::new(RwLock::new(trie));
+
+        //a reader holding the lock must not prevent other threads from reading
+        let read_guard = shared_trie.read().unwrap();
+
+        let other_reader = {
+            let shared_trie = Arc::clone(&shared_trie);
+            thread::spawn(move || {
+                shared_trie
+                    .try_read()
+                    .map(|trie| trie.get_typeahead_words("Ab".to_string()).unwrap().len())
+                    .ok()
+            })
+        };
+
+        assert_eq!(Some(3), other_reader.join().unwrap());
+        assert_eq!(3, read_guard.get_typeahead_words("Ab".to_string()).unwrap().len());
+    }
+
+    #[test]
+    fn t_get_typeahead_words_unicode_case_insensitive() {
+        let file_content =
+            "{\"Élodie\": 120, \"Elodie\": 80, \"Émile\": 95, \"Ängelika\": 40, \"Straße\": 7, \"Σοφία\": 33}";
+        let trie = Trie::initialize(file_content, 10).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Élodie".to_string(), 120),
+            WordData::new("Émile".to_string(), 95),
+        ];
+
+        assert_eq!(expected_words, trie.get_typeahead_words("É".to_string()).unwrap());
+        assert_eq!(expected_words, trie.get_typeahead_words("é".to_string()).unwrap());
+        assert_eq!(
+            vec![WordData::new("Élodie".to_string(), 120)],
+            trie.get_typeahead_words("ÉLODIE".to_string()).unwrap()
+        );
+        assert_eq!(
+            vec![WordData::new("Ängelika".to_string(), 40)],
+            trie.get_typeahead_words("äNG".to_string()).unwrap()
+        );
+        assert_eq!(
+            vec![WordData::new("Straße".to_string(), 7)],
+            trie.get_typeahead_words("STRASS".to_string()).unwrap()
+        );
+        assert_eq!(
+            vec![WordData::new("Σοφία".to_string(), 33)],
+            trie.get_typeahead_words("ΣΟΦ".to_string()).unwrap()
+        );
+    }
+
+    #[test]
+    fn t_get_typeahead_words_decomposed_prefix() {
+        //"Élodie" is stored precomposed and searched with a combining acute accent, and the other way around for "Émile"
+        let file_content = "{\"\u{c9}lodie\": 120, \"E\u{301}mile\": 95, \"Elodie\": 80}";
+        let trie = Trie::initialize(file_content, 10).unwrap();
+
+        assert_eq!(
+            vec![WordData::new("\u{c9}lodie".to_string(), 120)],
+            trie.get_typeahead_words("e\u{301}l".to_string()).unwrap()
+        );
+        assert_eq!(
+            vec![WordData::new("E\u{301}mile".to_string(), 95)],
+            trie.get_typeahead_words("\u{c9}m".to_string()).unwrap()
+        );
+    }
+
+    #[test]
+    fn t_increase_popularity_unicode_case_insensitive() {
+        let file_content = "{\"Élodie\": 120, \"Elodie\": 80}";
+        let mut trie = Trie::initialize(file_content, 10).unwrap();
+
+        let result = trie.increase_popularity("ÉLODIE".to_string()).unwrap();
+
+        assert_eq!(WordData::new("Élodie".to_string(), 121), result);
+        assert_eq!(
+            vec![WordData::new("Elodie".to_string(), 80)],
+            trie.get_typeahead_words("elodie".to_string()).unwrap()
+        );
+    }
+
+    fn accent_insensitive_testing_trie() -> Trie {
+        let file_content =
+            "{\"José\": 50, \"Jose\": 80, \"Josefa\": 60, \"Zoë\": 30, \"Zoe\": 30, \"Zoey\": 10, \"Élodie\": 20}";
+        let options = TrieOptions {
+            suggestion_number: 10,
+            accent_insensitive: true,
+            ..TrieOptions::default()
+        };
+
+        Trie::initialize(file_content, options).unwrap()
+    }
+
+    #[test]
+    fn t_get_typeahead_words_accent_insensitive() {
+        let trie = accent_insensitive_testing_trie();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Jose".to_string(), 80),
+            WordData::new("José".to_string(), 50),
+            WordData::new("Josefa".to_string(), 60),
+        ];
+
+        assert_eq!(expected_words, trie.get_typeahead_words("jose".to_string()).unwrap());
+        assert_eq!(expected_words, trie.get_typeahead_words("JOSÉ".to_string()).unwrap());
+        assert_eq!(
+            vec![WordData::new("Élodie".to_string(), 20)],
+            trie.get_typeahead_words("elo".to_string()).unwrap()
+        );
+    }
+
+    #[test]
+    fn t_get_typeahead_words_accent_insensitive_same_popularity() {
+        let trie = accent_insensitive_testing_trie();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Zoe".to_string(), 30),
+            WordData::new("Zoë".to_string(), 30),
+            WordData::new("Zoey".to_string(), 10),
+        ];
+
+        assert_eq!(expected_words, trie.get_typeahead_words("zoe".to_string()).unwrap());
+        assert_eq!(expected_words, trie.get_typeahead_words("Zo".to_string()).unwrap());
+    }
+
+    #[test]
+    fn t_get_typeahead_words_accent_sensitive_by_default() {
+        let file_content = "{\"José\": 50, \"Zoë\": 30}";
+        let trie = Trie::initialize(file_content, 10).unwrap();
+
+        assert_eq!(Vec::<WordData>::new(), trie.get_typeahead_words("jose".to_string()).unwrap());
+        assert_eq!(Vec::<WordData>::new(), trie.get_typeahead_words("zoe".to_string()).unwrap());
+    }
+
+    #[test]
+    fn t_increase_popularity_accent_insensitive() {
+        let mut trie = accent_insensitive_testing_trie();
+
+        assert_eq!(
+            WordData::new("José".to_string(), 51),
+            trie.increase_popularity("JOSÉ".to_string()).unwrap()
+        );
+        assert_eq!(
+            WordData::new("Jose".to_string(), 81),
+            trie.increase_popularity("jose".to_string()).unwrap()
+        );
+        //only one name shares the key, so the accent can be left out
+        assert_eq!(
+            WordData::new("Élodie".to_string(), 21),
+            trie.increase_popularity("elodie".to_string()).unwrap()
+        );
+        //"Zoe" and "Zoë" share the key and neither is written like this
+        assert_eq!(
+            word_does_not_exist("zoé"),
+            trie.increase_popularity("zoé".to_string()).unwrap_err()
+        );
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Zoë".to_string(), 31),
+            WordData::new("Zoe".to_string(), 30),
+        ];
+        trie.increase_popularity("ZOË".to_string()).unwrap();
+
+        assert_eq!(expected_words, trie.get_typeahead_words("zoe".to_string()).unwrap()[..2]);
+    }
+
+    fn multi_part_names_testing_trie() -> Trie {
+        let file_content =
+            "{\"Rose-Marie\": 300, \"Marie\": 200, \"Mariel\": 250, \"Mary Ann\": 150, \"Ann\": 100, \"Anna-Ann\": 90, \"Jean  Marie\": 400}";
+        Trie::initialize(file_content, 10).unwrap()
+    }
+
+    #[test]
+    fn t_inner_word_keys() {
+        assert_eq!(vec!["marie"], inner_word_keys("rose-marie"));
+        assert_eq!(vec!["ann smith", "smith"], inner_word_keys("mary ann smith"));
+        assert_eq!(vec!["marie"], inner_word_keys("jean - marie"));
+        assert_eq!(vec!["marie-"], inner_word_keys("-marie-"));
+    }
+
+    #[test]
+    fn t_get_typeahead_words_inner_words_after_whole_names() {
+        let trie = multi_part_names_testing_trie();
+
+        let words = trie.get_typeahead_words("marie".to_string()).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Marie".to_string(), 200),
+            WordData::new("Mariel".to_string(), 250),
+            WordData::new("Jean  Marie".to_string(), 400),
+            WordData::new("Rose-Marie".to_string(), 300),
+        ];
+
+        assert_eq!(expected_words, words);
+    }
+
+    #[test]
+    fn t_get_typeahead_words_inner_words_one_entry_per_name() {
+        let trie = multi_part_names_testing_trie();
+
+        let words = trie.get_typeahead_words("ann".to_string()).unwrap();
+
+        //"Anna-Ann" starts with the prefix and also has an inner word starting with it
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Ann".to_string(), 100),
+            WordData::new("Anna-Ann".to_string(), 90),
+            WordData::new("Mary Ann".to_string(), 150),
+        ];
+
+        assert_eq!(expected_words, words);
+    }
+
+    #[test]
+    fn t_get_typeahead_words_inner_words_truncated() {
+        let mut trie = multi_part_names_testing_trie();
+        trie.suggestion_number = 3;
+
+        let words = trie.get_typeahead_words("mar".to_string()).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Mariel".to_string(), 250),
+            WordData::new("Marie".to_string(), 200),
+            WordData::new("Mary Ann".to_string(), 150),
+        ];
+
+        assert_eq!(expected_words, words);
+    }
+
+    #[test]
+    fn t_increase_popularity_updates_inner_words() {
+        let mut trie = multi_part_names_testing_trie();
+        trie.suggestion_number = 1;
+        trie.rebuild_top_words();
+
+        for _ in 0..150 {
+            trie.increase_popularity("ROSE-MARIE".to_string()).unwrap();
+        }
+
+        assert_eq!(
+            vec![WordData::new("Rose-Marie".to_string(), 450)],
+            trie.get_typeahead_words("r".to_string()).unwrap()
+        );
+        assert_eq!(
+            vec![WordData::new("Rose-Marie".to_string(), 450)],
+            trie.root.children.get(&'m').unwrap().top_inner_words
+        );
+    }
+
+    #[test]
+    fn t_insert_word_indexes_inner_words() {
+        let mut trie = multi_part_names_testing_trie();
+        trie.insert_word("Anne-Marie".to_string(), 50).unwrap();
+        trie.insert_word("Rose-Marie".to_string(), 10).unwrap();
+
+        let words = trie.get_typeahead_words("marie".to_string()).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Marie".to_string(), 200),
+            WordData::new("Mariel".to_string(), 250),
+            WordData::new("Jean  Marie".to_string(), 400),
+            WordData::new("Anne-Marie".to_string(), 50),
+            WordData::new("Rose-Marie".to_string(), 10),
+        ];
+
+        assert_eq!(expected_words, words);
+    }
+
+    fn node_count(node: &Node) -> usize {
+        1 + node.children.values().map(|child_node| node_count(child_node)).sum::<usize>()
+    }
+
+    #[test]
+    fn t_remove_word_prunes_nodes() {
+        let mut trie = insert_word_testing_trie();
+
+        let removed_word_data = trie.remove_word("ca".to_string()).unwrap();
+
+        assert_eq!(WordData::new("Ca".to_string(), 150), removed_word_data);
+        assert!(compare_tries(&initialize_testing_trie().root, &trie.root));
+        assert!(!trie.root.children.contains_key(&'c'));
+    }
+
+    #[test]
+    fn t_remove_word_keeps_nodes_of_other_words() {
+        let mut trie = initialize_testing_trie();
+        let nodes_before = node_count(&trie.root);
+
+        //"Aba" is on the way to "Abag", and "Abag" is the only word below "Aba"
+        trie.remove_word("Aba".to_string()).unwrap();
+        assert_eq!(nodes_before, node_count(&trie.root));
+
+        trie.remove_word("Abag".to_string()).unwrap();
+        assert_eq!(nodes_before - 2, node_count(&trie.root));
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aar".to_string(), 361),
+            WordData::new("Abe".to_string(), 300),
+            WordData::new("Aari".to_string(), 151),
+            WordData::new("A-b".to_string(), 23),
+        ];
+
+        assert_eq!(expected_words, trie.get_typeahead_words("a".to_string()).unwrap());
+    }
+
+    #[test]
+    fn t_remove_word_refills_top_words() {
+        let mut trie = initialize_testing_trie();
+        trie.suggestion_number = 2;
+        trie.rebuild_top_words();
+
+        trie.remove_word("Abag".to_string()).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Aba".to_string(), 608),
+            WordData::new("Aar".to_string(), 361),
+        ];
+
+        assert_eq!(expected_words, trie.get_typeahead_words("a".to_string()).unwrap());
+    }
+
+    #[test]
+    fn t_remove_word_does_not_exist() {
+        let mut trie = initialize_testing_trie();
+
+        assert_eq!(Err(word_does_not_exist("Brazil")), trie.remove_word("Brazil".to_string()));
+        assert_eq!(Err(word_does_not_exist("Ab")), trie.remove_word("Ab".to_string()));
+        assert!(compare_tries(&initialize_testing_trie().root, &trie.root));
+    }
+
+    #[test]
+    fn t_remove_word_removes_inner_words() {
+        let mut trie = multi_part_names_testing_trie();
+        trie.increase_popularity("Rose-Marie".to_string()).unwrap();
+
+        trie.remove_word("ROSE-MARIE".to_string()).unwrap();
+
+        let expected_words: Vec<WordData> = vec![
+            WordData::new("Marie".to_string(), 200),
+            WordData::new("Mariel".to_string(), 250),
+            WordData::new("Jean  Marie".to_string(), 400),
+        ];
+
+        assert_eq!(expected_words, trie.get_typeahead_words("marie".to_string()).unwrap());
+        assert!(trie.get_typeahead_words("rose".to_string()).unwrap().is_empty());
+        assert!(trie.popularity_increments.is_empty());
+    }
+
+    #[test]
+    fn t_remove_word_accent_insensitive_variant() {
+        let options = TrieOptions {
+            accent_insensitive: true,
+            ..TrieOptions::from(10)
+        };
+        let mut trie = Trie::initialize("{\"Zoe\": 9, \"Zoë\": 5}", options).unwrap();
+
+        trie.remove_word("Zoe".to_string()).unwrap();
+        assert_eq!(
+            vec![WordData::new("Zoë".to_string(), 5)],
+            trie.get_typeahead_words("zoe".to_string()).unwrap()
+        );
+
+        //only the exact name is removed, not another one sharing its key
+        assert_eq!(Err(word_does_not_exist("Zoe")), trie.remove_word("Zoe".to_string()));
+
+        trie.remove_word("zoë".to_string()).unwrap();
+        assert!(trie.root.children.is_empty());
+    }
+
+    #[test]
+    fn t_words_with_prefix() {
+        let trie = Trie::initialize("{\"Aar\": 361, \"Abe\": 3, \"ABEL\": 5, \"Rose-Abe\": 9}", 10).unwrap();
+
+        let expected_words = [WordData::new("ABEL".to_string(), 5), WordData::new("Abe".to_string(), 3)];
+
+        assert_eq!(expected_words.iter().collect::<Vec<&WordData>>(), trie.words_with_prefix("abe"));
+        assert_eq!(4, trie.words_with_prefix("").len());
+        assert!(trie.words_with_prefix("abc").is_empty());
+    }
+
+    #[test]
+    fn t_visit_words_in_key_order() {
+        let trie = Trie::initialize("{\"Rose-Abe\": 9, \"Abe\": 3, \"ABEL\": 5, \"Aar\": 361, \"Ab\": 1}", 10).unwrap();
+
+        let visit = |prefix: &str, after: Option<&str>, count: usize| {
+            let mut words = Vec::new();
+            trie.visit_words_in_key_order(prefix, after, |key, word_data| {
+                words.push((key.to_string(), word_data.word.clone()));
+                words.len() < count
+            });
+
+            words
+        };
+        let word = |key: &str, name: &str| (key.to_string(), name.to_string());
+
+        assert_eq!(
+            vec![word("aar", "Aar"), word("ab", "Ab"), word("abe", "Abe"), word("abel", "ABEL"), word("rose-abe", "Rose-Abe")],
+            visit("", None, 10)
+        );
+        assert_eq!(vec![word("aar", "Aar"), word("ab", "Ab")], visit("", None, 2));
+        assert_eq!(vec![word("abel", "ABEL"), word("rose-abe", "Rose-Abe")], visit("", Some("abe"), 10));
+        assert_eq!(vec![word("abe", "Abe"), word("abel", "ABEL")], visit("AB", Some("ab"), 10));
+        assert_eq!(vec![word("rose-abe", "Rose-Abe")], visit("", Some("ac"), 10));
+        assert!(visit("abc", None, 10).is_empty());
+    }
+
+    #[test]
+    fn t_find_word() {
+        let trie = initialize_testing_trie();
+
+        assert_eq!(Some(&WordData::new("Abag".to_string(), 704)), trie.find_word("ABAG"));
+        assert_eq!(None, trie.find_word("Ab"));
+        assert_eq!(None, trie.find_word("Abagail"));
+    }
+
+    #[test]
+    fn t_rename_word() {
+        let mut trie = multi_part_names_testing_trie();
+        trie.increase_popularity("Mary Ann".to_string()).unwrap();
+
+        //fixing the casing
+        assert_eq!(
+            WordData::new("Mary ann".to_string(), 151),
+            trie.rename_word("mary ann".to_string(), "Mary ann".to_string()).unwrap()
+        );
+
+        //to a new name, inner words included
+        trie.rename_word("Mary ann".to_string(), "Mary-Jane".to_string()).unwrap();
+
+        assert_eq!(
+            vec![WordData::new("Mary-Jane".to_string(), 151)],
+            trie.get_typeahead_words("jane".to_string()).unwrap()
+        );
+        assert_eq!(
+            vec![WordData::new("Ann".to_string(), 100), WordData::new("Anna-Ann".to_string(), 90)],
+            trie.get_typeahead_words("ann".to_string()).unwrap()
+        );
+        assert_eq!(None, trie.find_word("Mary Ann"));
+        assert_eq!(Some(&1), trie.popularity_increments.get("Mary-Jane"));
+    }
+
+    #[test]
+    fn t_rename_word_errors() {
+        let mut trie = multi_part_names_testing_trie();
+
+        assert_eq!(
+            Err(AppError::WordAlreadyExists),
+            trie.rename_word("Marie".to_string(), "mariel".to_string())
+        );
+        assert_eq!(
+            Err(word_does_not_exist("Maria")),
+            trie.rename_word("Maria".to_string(), "Mariana".to_string())
+        );
+        assert_eq!(Some(&WordData::new("Marie".to_string(), 200)), trie.find_word("Marie"));
+    }
 }
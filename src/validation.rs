@@ -0,0 +1,337 @@
+use crate::app_error::AppError;
+use crate::normalization::normalize_key;
+use crate::source::{self, CollisionPolicy, SourceEntry, SourceFormat, SourceOptions};
+use crate::trie::Popularity;
+use log::warn;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+
+//only the first problems are logged when loading, the validate command lists all of them.
+const MAX_LOGGED_ISSUES: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    //the line, or the whole file, can't be read.
+    InvalidContent(String),
+    InvalidPopularity { name: String, popularity: String },
+    EmptyName,
+    ControlCharacters(String),
+    SurroundingWhitespace(String),
+    //the name has the same key as an earlier one, or the same spelling when keeping both, so only one of them would be kept.
+    Duplicate {
+        name: String,
+        first_name: String,
+        first_line: usize,
+    },
+}
+
+impl Problem {
+    //errors stop the loading, the others only do so in strict mode.
+    pub fn is_error(&self) -> bool {
+        matches!(self, Problem::InvalidContent(_) | Problem::InvalidPopularity { .. })
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::InvalidContent(reason) => write!(formatter, "{}", reason),
+            Problem::InvalidPopularity { name, popularity } => write!(
+                formatter,
+                "Popularity {} of {:?} is not a whole number from 0 to {}",
+                popularity,
+                name,
+                Popularity::MAX
+            ),
+            Problem::EmptyName => write!(formatter, "Empty name"),
+            Problem::ControlCharacters(name) => write!(formatter, "Name {:?} has control characters", name),
+            Problem::SurroundingWhitespace(name) => {
+                write!(formatter, "Name {:?} has leading or trailing whitespace", name)
+            }
+            Problem::Duplicate {
+                name,
+                first_name,
+                first_line,
+            } if name == first_name => write!(formatter, "Name {:?} is repeated from line {}", name, first_line),
+            Problem::Duplicate {
+                name,
+                first_name,
+                first_line,
+            } => write!(
+                formatter,
+                "Name {:?} differs only in casing from {:?} at line {}",
+                name, first_name, first_line
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub line: usize,
+    pub problem: Problem,
+}
+
+impl Issue {
+    pub fn to_error(&self) -> AppError {
+        AppError::InvalidSourceLine {
+            line: self.line,
+            reason: self.problem.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "line {}: {}", self.line, self.problem)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    //names that could be read, problems or not.
+    pub names: usize,
+    //ordered by line, except for the lines of a JSON object that can't be read at all.
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    pub fn log(&self, path: &str) {
+        for issue in self.issues.iter().take(MAX_LOGGED_ISSUES) {
+            warn!("{}, {}", path, issue);
+        }
+
+        if self.issues.len() > MAX_LOGGED_ISSUES {
+            warn!(
+                "{}, {} more problems. Run the validate command to list all of them",
+                path,
+                self.issues.len() - MAX_LOGGED_ISSUES
+            );
+        }
+    }
+}
+
+//checks every name of the content, returning the entries that could be read along with the problems found.
+pub fn validate(content: &str, format: SourceFormat, options: &SourceOptions) -> (Vec<SourceEntry>, ValidationReport) {
+    let parsed_entries = match source::parse_lenient(content, format, options) {
+        Ok(parsed_entries) => parsed_entries,
+        Err(issue) => {
+            return (
+                Vec::new(),
+                ValidationReport {
+                    names: 0,
+                    issues: vec![issue],
+                },
+            )
+        }
+    };
+
+    let mut entries: Vec<SourceEntry> = Vec::new();
+    let mut issues = Vec::new();
+    //index of the first entry of each key
+    let mut first_entries: HashMap<String, usize> = HashMap::new();
+
+    for parsed_entry in parsed_entries {
+        let entry = match parsed_entry {
+            Ok(entry) => entry,
+            Err(issue) => {
+                issues.push(issue);
+                continue;
+            }
+        };
+
+        let line = entry.line;
+        let name = &entry.name;
+
+        if name.trim().is_empty() {
+            issues.push(Issue {
+                line,
+                problem: Problem::EmptyName,
+            });
+        } else {
+            if name.chars().any(char::is_control) {
+                issues.push(Issue {
+                    line,
+                    problem: Problem::ControlCharacters(name.clone()),
+                });
+            }
+
+            if name.trim() != name {
+                issues.push(Issue {
+                    line,
+                    problem: Problem::SurroundingWhitespace(name.clone()),
+                });
+            }
+
+            //other casings are no duplicates when both of them are kept
+            let key = match options.collision_policy {
+                CollisionPolicy::KeepBoth => name.clone(),
+                _ => normalize_key(name),
+            };
+
+            match first_entries.entry(key) {
+                Entry::Occupied(first_entry) => {
+                    let first_entry = &entries[*first_entry.get()];
+
+                    issues.push(Issue {
+                        line,
+                        problem: Problem::Duplicate {
+                            name: name.clone(),
+                            first_name: first_entry.name.clone(),
+                            first_line: first_entry.line,
+                        },
+                    });
+                }
+                Entry::Vacant(first_entry) => {
+                    first_entry.insert(entries.len());
+                }
+            }
+        }
+
+        entries.push(entry);
+    }
+
+    let report = ValidationReport {
+        names: entries.len(),
+        issues,
+    };
+
+    (entries, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::tests::temp_file_path;
+    use crate::trie::{ITrie, WordData};
+    use std::fs;
+
+    fn issue(line: usize, problem: Problem) -> Issue {
+        Issue { line, problem }
+    }
+
+    #[test]
+    fn t_validate_reports_every_problem() {
+        let content = "{\n  \"Rose-Marie\": 40,\n  \"Rose-marie\": 3,\n  \"\": 1, \" Aar\": -5,\n  \"Abe\\u0007\": 2,\n  \"Rose-Marie\": 1,\n  \"Abel \": 12\n}";
+
+        let (entries, report) = validate(content, SourceFormat::Json, &SourceOptions::default());
+
+        assert_eq!(6, entries.len());
+        assert_eq!(
+            vec![
+                issue(
+                    3,
+                    Problem::Duplicate {
+                        name: "Rose-marie".to_string(),
+                        first_name: "Rose-Marie".to_string(),
+                        first_line: 2
+                    }
+                ),
+                issue(4, Problem::EmptyName),
+                issue(
+                    4,
+                    Problem::InvalidPopularity {
+                        name: " Aar".to_string(),
+                        popularity: "-5".to_string()
+                    }
+                ),
+                issue(5, Problem::ControlCharacters("Abe\u{7}".to_string())),
+                issue(
+                    6,
+                    Problem::Duplicate {
+                        name: "Rose-Marie".to_string(),
+                        first_name: "Rose-Marie".to_string(),
+                        first_line: 2
+                    }
+                ),
+                issue(7, Problem::SurroundingWhitespace("Abel ".to_string())),
+            ],
+            report.issues
+        );
+
+        let messages: Vec<String> = report.issues.iter().map(Issue::to_string).collect();
+        assert_eq!(
+            vec![
+                "line 3: Name \"Rose-marie\" differs only in casing from \"Rose-Marie\" at line 2",
+                "line 4: Empty name",
+                "line 4: Popularity -5 of \" Aar\" is not a whole number from 0 to 18446744073709551615",
+                "line 5: Name \"Abe\\u{7}\" has control characters",
+                "line 6: Name \"Rose-Marie\" is repeated from line 2",
+                "line 7: Name \"Abel \" has leading or trailing whitespace",
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn t_validate_keeps_going_after_invalid_lines() {
+        let content = "name,times\nAar,361\nAbe\nAbel,x\n\"Abe\",4\naar,1";
+
+        let (entries, report) = validate(content, SourceFormat::Csv, &SourceOptions::default());
+
+        assert_eq!(3, entries.len());
+        assert_eq!(
+            vec![3, 4, 6],
+            report.issues.iter().map(|issue| issue.line).collect::<Vec<usize>>()
+        );
+        assert!(report.issues[0].problem.is_error());
+        assert!(report.issues[1].problem.is_error());
+        assert!(!report.issues[2].problem.is_error());
+    }
+
+    #[test]
+    fn t_validate_invalid_json() {
+        let (entries, report) = validate("{\n  \"Aar\": 361,\n  \"Abe\" 3\n}", SourceFormat::Json, &SourceOptions::default());
+
+        assert!(entries.is_empty());
+        assert_eq!(
+            vec![issue(3, Problem::InvalidContent("expected `:` at column 9".to_string()))],
+            report.issues
+        );
+    }
+
+    #[test]
+    fn t_load_strict_mode() {
+        let path = temp_file_path("validation-strict.json");
+        fs::write(&path, "{\"Rose-Marie\": 40, \"Rose-marie\": 3}").unwrap();
+
+        let trie = source::load(&path, &SourceOptions::default(), 10.into()).unwrap();
+        assert_eq!(
+            vec![WordData::new("Rose-Marie".to_string(), 40)],
+            trie.get_typeahead_words("rose".to_string()).unwrap()
+        );
+
+        let options = SourceOptions {
+            strict: true,
+            ..SourceOptions::default()
+        };
+
+        assert_eq!(
+            Err(AppError::InvalidSourceLine {
+                line: 1,
+                reason: "Name \"Rose-marie\" differs only in casing from \"Rose-Marie\" at line 1".to_string()
+            }),
+            source::load(&path, &options, 10.into()).map(|_| ())
+        );
+
+        //unless both spellings are kept
+        let options = SourceOptions {
+            strict: true,
+            collision_policy: CollisionPolicy::KeepBoth,
+            ..SourceOptions::default()
+        };
+
+        let trie = source::load(&path, &options, 10.into()).unwrap();
+        assert_eq!(2, trie.get_typeahead_words("rose".to_string()).unwrap().len());
+
+        fs::write(&path, "{\"Rose-Marie\": 40, \"Rose-Marie\": 3}").unwrap();
+        assert_eq!(
+            Err(AppError::InvalidSourceLine {
+                line: 1,
+                reason: "Name \"Rose-Marie\" is repeated from line 1".to_string()
+            }),
+            source::load(&path, &options, 10.into()).map(|_| ())
+        );
+    }
+}